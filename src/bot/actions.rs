@@ -0,0 +1,282 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Actions users can take on notifications by reacting to them: muting the channel, blocking the
+//! author, removing the keywords, or dismissing the notification.
+
+use anyhow::Result;
+use serenity::{
+	builder::{CreateMessage, EditMessage},
+	client::Context,
+	model::{
+		channel::{Reaction, ReactionType},
+		id::{MessageId, UserId},
+	},
+};
+use tokio::task;
+
+use crate::{
+	bot::dispatcher,
+	db::{Block, Keyword, KeywordKind, Mute, Notification},
+	monitoring::Timer,
+	settings::settings,
+};
+
+/// Response to an action on a notification sent by an older version, which didn't store what the
+/// notification was about.
+const MISSING_CONTEXT: &str =
+	"I don't know enough about this notification to do that, sorry!";
+
+/// An action a user can take on a notification by reacting to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+	/// Mute the channel the original message was sent in.
+	Mute,
+	/// Block the author of the original message.
+	Block,
+	/// Remove the keywords the notification was sent for.
+	RemoveKeywords,
+	/// Delete the notification.
+	Dismiss,
+}
+
+/// Each action with the emoji used to take it, in the order they're added to notifications.
+const ACTIONS: [(&str, Action); 4] = [
+	("🔇", Action::Mute),
+	("🚫", Action::Block),
+	("🗑️", Action::RemoveKeywords),
+	("✅", Action::Dismiss),
+];
+
+impl Action {
+	/// Gets the action taken with the given emoji.
+	///
+	/// Variation selectors are ignored, so emoji picked with or without them are recognized.
+	fn from_emoji(emoji: &str) -> Option<Self> {
+		let emoji = emoji.trim_end_matches('\u{fe0f}');
+
+		ACTIONS
+			.iter()
+			.find(|(other, _)| other.trim_end_matches('\u{fe0f}') == emoji)
+			.map(|&(_, action)| action)
+	}
+}
+
+/// Adds a reaction for each action to a notification message sent to the user with the given ID.
+///
+/// The reactions are added in the background, so they don't hold up the notification they're
+/// added to or take turns from other notifications waiting to be sent. Errors are logged rather
+/// than returned, since the notification was already sent.
+pub fn add_actions(ctx: &Context, user_id: UserId, message_id: MessageId) {
+	let ctx = ctx.clone();
+
+	task::spawn(async move {
+		for (emoji, _) in ACTIONS.iter() {
+			let reaction = ReactionType::Unicode((*emoji).to_owned());
+
+			if let Err(e) =
+				dispatcher::react(&ctx, user_id, message_id, reaction).await
+			{
+				log::error!(
+					"Failed to add actions to notification {} for {}: {:?}",
+					message_id,
+					user_id,
+					e
+				);
+				return;
+			}
+		}
+	});
+}
+
+/// Takes the action the user chose by reacting to one of their notifications, if any.
+///
+/// The notification is edited to show the result, unless it was dismissed, in which case it's
+/// deleted.
+pub async fn handle_reaction(ctx: &Context, reaction: &Reaction) -> Result<()> {
+	if reaction.guild_id.is_some() {
+		return Ok(());
+	}
+
+	let user_id = match reaction.user_id {
+		Some(id) if id != ctx.cache.current_user_id().await => id,
+		_ => return Ok(()),
+	};
+
+	let action = match &reaction.emoji {
+		ReactionType::Unicode(emoji) => match Action::from_emoji(emoji) {
+			Some(action) => action,
+			None => return Ok(()),
+		},
+		_ => return Ok(()),
+	};
+
+	let notifications =
+		Notification::from_notification_message(reaction.message_id).await?;
+
+	match notifications.first() {
+		Some(notification) if notification.user_id == user_id => {}
+		Some(_) => return Ok(()),
+		None => return report_pruned(ctx, reaction, user_id).await,
+	}
+
+	let _timer = Timer::notification("action");
+
+	let response = match action {
+		Action::Mute => mute(&notifications[0]).await?,
+		Action::Block => block(&notifications[0]).await?,
+		Action::RemoveKeywords => remove_keywords(&notifications).await?,
+		Action::Dismiss => {
+			dispatcher::delete(ctx, user_id, reaction.message_id).await?;
+
+			return notifications[0].clone().delete().await;
+		}
+	};
+
+	let mut edit = EditMessage::default();
+
+	edit.content(response);
+
+	dispatcher::edit(ctx, user_id, reaction.message_id, edit).await?;
+
+	Ok(())
+}
+
+/// Tells the user a notification they reacted to is too old to act on, if it was removed from
+/// notification history.
+///
+/// Only messages the bot added this reaction to itself are notifications, so reactions to other
+/// messages are still ignored.
+async fn report_pruned(
+	ctx: &Context,
+	reaction: &Reaction,
+	user_id: UserId,
+) -> Result<()> {
+	let days = settings().database.notification_history_days;
+
+	if days == 0 {
+		return Ok(());
+	}
+
+	let message = reaction.message(ctx).await?;
+
+	if !message
+		.reactions
+		.iter()
+		.any(|r| r.me && r.reaction_type == reaction.emoji)
+	{
+		return Ok(());
+	}
+
+	let mut response = CreateMessage::default();
+
+	response.content(format!(
+		"This notification is more than {} days old, so I don't remember \
+		enough about it to do that, sorry!",
+		days
+	));
+
+	dispatcher::send(ctx, user_id, response).await?;
+
+	Ok(())
+}
+
+/// Mutes the channel a notification was about, returning a response describing the result.
+async fn mute(notification: &Notification) -> Result<String> {
+	let channel_id = match notification.channel_id {
+		Some(id) => id,
+		None => return Ok(MISSING_CONTEXT.to_owned()),
+	};
+
+	let mute = Mute {
+		user_id: notification.user_id,
+		channel_id,
+	};
+
+	if mute.clone().exists().await? {
+		return Ok(format!("🔇 You've already muted <#{}>.", channel_id));
+	}
+
+	mute.insert().await?;
+
+	Ok(format!("🔇 Muted <#{}>.", channel_id))
+}
+
+/// Blocks the author of the message a notification was about, returning a response describing the
+/// result.
+async fn block(notification: &Notification) -> Result<String> {
+	let author_id = match notification.author_id {
+		Some(id) => id,
+		None => return Ok(MISSING_CONTEXT.to_owned()),
+	};
+
+	let block = Block {
+		user_id: notification.user_id,
+		blocked_id: author_id,
+	};
+
+	if block.clone().exists().await? {
+		return Ok(format!("🚫 You've already blocked <@{}>.", author_id));
+	}
+
+	block.insert().await?;
+
+	Ok(format!("🚫 Blocked <@{}>.", author_id))
+}
+
+/// Removes the keywords a notification was sent for, returning a response describing the result.
+///
+/// `notifications` should be every notification stored for one notification message.
+async fn remove_keywords(notifications: &[Notification]) -> Result<String> {
+	let mut removed = vec![];
+
+	for notification in notifications {
+		let kind = match (
+			notification.channel_keyword,
+			notification.channel_id,
+			notification.guild_id,
+		) {
+			(true, Some(channel_id), _) => KeywordKind::Channel(channel_id),
+			(false, _, Some(guild_id)) => KeywordKind::Guild(guild_id),
+			_ => return Ok(MISSING_CONTEXT.to_owned()),
+		};
+
+		let keyword = Keyword {
+			keyword: notification.keyword.clone(),
+			user_id: notification.user_id,
+			kind,
+			match_kind: notification.match_kind,
+			segmentation: notification.segmentation,
+			case_sensitive: notification.case_sensitive,
+			exclusions: notification.exclusions,
+		};
+
+		if keyword.clone().exists().await? {
+			keyword.delete().await?;
+			removed.push(format!("\"{}\"", notification.keyword));
+		}
+	}
+
+	Ok(match &*removed {
+		[] if notifications.len() == 1 => {
+			"🗑️ You've already removed that keyword.".to_owned()
+		}
+		[] => "🗑️ You've already removed those keywords.".to_owned(),
+		[keyword] => format!("🗑️ Removed your keyword {}.", keyword),
+		_ => format!("🗑️ Removed your keywords {}.", removed.join(", ")),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn action_emoji() {
+		assert_eq!(Action::from_emoji("🔇"), Some(Action::Mute));
+		assert_eq!(Action::from_emoji("🗑️"), Some(Action::RemoveKeywords));
+		assert_eq!(Action::from_emoji("🗑"), Some(Action::RemoveKeywords));
+		assert_eq!(Action::from_emoji("✅"), Some(Action::Dismiss));
+		assert_eq!(Action::from_emoji("👍"), None);
+	}
+}
@@ -0,0 +1,166 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Tracking when users were last active in each server, to avoid notifying them about messages
+//! they've probably seen.
+//!
+//! This is only kept in memory, and only for as long as the patience duration, since activity is
+//! only checked against messages sent within that duration.
+
+use once_cell::sync::Lazy;
+use serenity::model::id::{GuildId, UserId};
+
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Mutex,
+	},
+	time::Instant,
+};
+
+use crate::{db::ActivitySignals, settings::settings};
+
+/// When each user was last active in each server, by kind of activity.
+static ACTIVITY: Lazy<Mutex<HashMap<(UserId, GuildId), LastActive>>> =
+	Lazy::new(Default::default);
+
+/// Number of times activity has been recorded, used to occasionally forget old activity.
+static RECORDED: AtomicUsize = AtomicUsize::new(0);
+
+/// How many times activity is recorded between removals of activity older than the patience
+/// duration.
+const PRUNE_INTERVAL: usize = 1000;
+
+/// When a user was last active in a server, for each kind of activity.
+#[derive(Debug, Default)]
+struct LastActive {
+	typing: Option<Instant>,
+	message: Option<Instant>,
+	voice: Option<Instant>,
+}
+
+impl LastActive {
+	/// Checks if the user was active in any of the given ways at or after `since`.
+	fn active_since(&self, signals: ActivitySignals, since: Instant) -> bool {
+		[
+			(ActivitySignals::TYPING, self.typing),
+			(ActivitySignals::MESSAGES, self.message),
+			(ActivitySignals::VOICE, self.voice),
+		]
+		.iter()
+		.any(|&(signal, last)| {
+			signals.contains(signal) && last.is_some_and(|last| last >= since)
+		})
+	}
+
+	/// Gets the last time the user was active in any way.
+	fn latest(&self) -> Option<Instant> {
+		self.typing.max(self.message).max(self.voice)
+	}
+}
+
+/// Records that a user was active in a server just now, in the given way.
+///
+/// `signal` should be exactly one kind of activity.
+pub fn record(user_id: UserId, guild_id: GuildId, signal: ActivitySignals) {
+	let now = Instant::now();
+
+	let mut activity = ACTIVITY.lock().unwrap();
+
+	let last_active = activity.entry((user_id, guild_id)).or_default();
+
+	if signal == ActivitySignals::TYPING {
+		last_active.typing = Some(now);
+	} else if signal == ActivitySignals::MESSAGES {
+		last_active.message = Some(now);
+	} else if signal == ActivitySignals::VOICE {
+		last_active.voice = Some(now);
+	}
+
+	if RECORDED.fetch_add(1, Ordering::Relaxed) % PRUNE_INTERVAL == 0 {
+		let patience = settings().behavior.patience;
+
+		activity.retain(|_, last_active| {
+			last_active
+				.latest()
+				.is_some_and(|latest| now - latest <= patience)
+		});
+	}
+}
+
+/// Checks if a user was active in a server in any of the given ways at or after `since`.
+pub fn active_since(
+	user_id: UserId,
+	guild_id: GuildId,
+	signals: ActivitySignals,
+	since: Instant,
+) -> bool {
+	if signals.is_empty() {
+		return false;
+	}
+
+	ACTIVITY
+		.lock()
+		.unwrap()
+		.get(&(user_id, guild_id))
+		.is_some_and(|last_active| last_active.active_since(signals, since))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::Duration;
+
+	#[test]
+	fn activity_signals() {
+		let start = Instant::now();
+		let later = start + Duration::from_secs(10);
+
+		let last_active = LastActive {
+			typing: Some(later),
+			message: Some(start),
+			voice: None,
+		};
+
+		assert!(last_active.active_since(ActivitySignals::TYPING, later));
+		assert!(!last_active.active_since(ActivitySignals::MESSAGES, later));
+		assert!(last_active.active_since(ActivitySignals::MESSAGES, start));
+		assert!(!last_active.active_since(ActivitySignals::VOICE, start));
+		assert!(!last_active.active_since(ActivitySignals::none(), start));
+		assert!(last_active.active_since(ActivitySignals::all(), later));
+	}
+
+	#[test]
+	fn default_activity_signals() {
+		let start = Instant::now();
+		let later = start + Duration::from_secs(10);
+		let signals = ActivitySignals::default();
+
+		assert_eq!(
+			signals,
+			ActivitySignals::TYPING.union(ActivitySignals::MESSAGES)
+		);
+
+		let typing = LastActive {
+			typing: Some(later),
+			message: None,
+			voice: None,
+		};
+		let message = LastActive {
+			typing: None,
+			message: Some(later),
+			voice: None,
+		};
+		let voice = LastActive {
+			typing: None,
+			message: None,
+			voice: Some(later),
+		};
+
+		assert!(typing.active_since(signals, start));
+		assert!(message.active_since(signals, start));
+		assert!(!voice.active_since(signals, start));
+	}
+}
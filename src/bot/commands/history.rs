@@ -0,0 +1,248 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Command for looking back through the notifications a user has been sent.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serenity::{
+	client::Context,
+	model::{channel::Message, id::GuildId},
+};
+
+use std::fmt::Write;
+
+use super::keywords::display_keyword;
+use crate::{
+	bot::{
+		responses::insert_command_response,
+		util::{error, user_timezone},
+	},
+	db::{HistoryFilter, Notification, UserSettings},
+	global::{EMBED_COLOR, HISTORY_PAGE_SIZE},
+	monitoring::Timer,
+	regex,
+};
+
+/// List notifications you've been sent.
+///
+/// Usage: `@Highlights history [server:<ID or all>] [keyword:<keyword>] [from:<YYYY-MM-DD>]
+/// [to:<YYYY-MM-DD>] [page]`
+///
+/// Notifications from other servers can only be listed in DMs, so they aren't shown publicly.
+pub async fn history(
+	ctx: &Context,
+	message: &Message,
+	args: &str,
+) -> Result<()> {
+	let _timer = Timer::command("history");
+
+	let settings = UserSettings::user_settings(message.author.id).await?;
+	let timezone = user_timezone(&settings);
+
+	let (filter, page) =
+		match parse_history_args(message.guild_id, timezone, args) {
+			Ok(parsed) => parsed,
+			Err(msg) => return error(ctx, message, msg).await,
+		};
+
+	let (notifications, total) = Notification::user_history(
+		message.author.id,
+		filter,
+		(page - 1).saturating_mul(HISTORY_PAGE_SIZE),
+		HISTORY_PAGE_SIZE,
+	)
+	.await?;
+
+	if total == 0 {
+		let msg = match (args.is_empty(), message.guild_id) {
+			(true, Some(_)) => {
+				"You don't have any notifications from this server in your history!"
+			}
+			(true, None) => "You don't have any notifications in your history!",
+			(false, _) => "You don't have any notifications matching that!",
+		};
+
+		return error(ctx, message, msg).await;
+	}
+
+	let pages = total.div_ceil(HISTORY_PAGE_SIZE);
+
+	if page > pages {
+		let msg = format!(
+			"There's no page {}; there {} only {} page{}!",
+			page,
+			if pages == 1 { "is" } else { "are" },
+			pages,
+			if pages == 1 { "" } else { "s" },
+		);
+
+		return error(ctx, message, msg).await;
+	}
+
+	let mut description = String::new();
+
+	for notification in notifications {
+		if let Some(sent_at) = notification.sent_at {
+			write!(
+				&mut description,
+				"`{}` ",
+				sent_at.with_timezone(&timezone).format("%Y-%m-%d %H:%M")
+			)
+			.unwrap();
+		}
+
+		description.push_str(&display_keyword(
+			&notification.keyword,
+			notification.match_kind,
+		));
+
+		if let Some(channel_id) = notification.channel_id {
+			write!(&mut description, " in <#{}>", channel_id).unwrap();
+		}
+
+		if let Some(author_id) = notification.author_id {
+			write!(&mut description, " by <@{}>", author_id).unwrap();
+		}
+
+		if let (Some(guild_id), Some(channel_id)) =
+			(notification.guild_id, notification.channel_id)
+		{
+			write!(
+				&mut description,
+				" ([jump](https://discord.com/channels/{}/{}/{}))",
+				guild_id, channel_id, notification.original_message
+			)
+			.unwrap();
+		}
+
+		description.push('\n');
+	}
+
+	let response = message
+		.channel_id
+		.send_message(ctx, |m| {
+			m.embed(|e| {
+				e.title(format!(
+					"{}'s notification history (page {}/{})",
+					message.author.name, page, pages
+				))
+				.description(description)
+				.footer(|f| f.text(format!("Times are in {}", timezone.name())))
+				.color(EMBED_COLOR)
+			})
+		})
+		.await?;
+
+	insert_command_response(ctx, message.id, response.id).await;
+
+	Ok(())
+}
+
+/// Parses the filters and page number given to the history command.
+///
+/// The history is filtered to the server the command was used in, if any; other servers (or `all`)
+/// can only be given in DMs. Dates are in the user's time zone, and the date range includes both
+/// ends.
+fn parse_history_args(
+	guild_id: Option<GuildId>,
+	timezone: Tz,
+	args: &str,
+) -> Result<(HistoryFilter, u32), String> {
+	let mut filter = HistoryFilter {
+		guild_id,
+		..Default::default()
+	};
+	let mut page = 1;
+	let mut from = None;
+	let mut to = None;
+
+	for captures in regex!(r#"(\S+?):(?:"((?:\\"|[^"])*)"|(\S+))|(\S+)"#)
+		.captures_iter(args)
+	{
+		if let Some(arg) = captures.get(4) {
+			page = parse_page(arg.as_str())?;
+			continue;
+		}
+
+		let value = match (captures.get(2), captures.get(3)) {
+			(Some(quoted), _) => quoted.as_str().replace(r#"\""#, "\""),
+			(_, Some(value)) => value.as_str().to_owned(),
+			_ => unreachable!("history argument without a value"),
+		};
+
+		match captures[1].to_lowercase().as_str() {
+			"server" => {
+				let other = match value.parse() {
+					_ if value.eq_ignore_ascii_case("all") => None,
+					Ok(id) => Some(GuildId(id)),
+					Err(_) => {
+						return Err(format!("Invalid server ID: {}", value))
+					}
+				};
+
+				if guild_id.is_some() && other != guild_id {
+					return Err("You can only list notifications from other \
+						servers in DMs!"
+						.to_owned());
+				}
+
+				filter.guild_id = other;
+			}
+			"keyword" => filter.keyword = Some(value),
+			"from" => from = Some(parse_date(&value)?),
+			"to" => to = Some(parse_date(&value)?),
+			"page" => page = parse_page(&value)?,
+			name => return Err(format!("Unknown filter: {}", name)),
+		}
+	}
+
+	if let (Some(from), Some(to)) = (from, to) {
+		if from > to {
+			return Err(
+				"The start date must be before the end date!".to_owned()
+			);
+		}
+	}
+
+	filter.after = from.and_then(|date| start_of_day(date, timezone));
+	filter.before = to
+		.and_then(|date| date.succ_opt())
+		.and_then(|date| start_of_day(date, timezone));
+
+	Ok((filter, page))
+}
+
+/// Parses a date like `2021-06-30`.
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+	NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+		format!("Invalid date: {} (dates look like 2021-06-30)", date)
+	})
+}
+
+/// Parses a page number, starting from 1.
+fn parse_page(page: &str) -> Result<u32, String> {
+	match page.parse() {
+		Ok(page) if page > 0 => Ok(page),
+		_ => Err(format!("Invalid page number: {}", page)),
+	}
+}
+
+/// Finds when the given day started in a time zone.
+///
+/// If midnight was skipped that day because of daylight saving time, the time an hour later is
+/// used.
+fn start_of_day(date: NaiveDate, timezone: Tz) -> Option<DateTime<Utc>> {
+	let midnight = date.and_hms(0, 0, 0);
+
+	timezone
+		.from_local_datetime(&midnight)
+		.earliest()
+		.or_else(|| {
+			timezone
+				.from_local_datetime(&(midnight + Duration::hours(1)))
+				.earliest()
+		})
+		.map(|time| time.with_timezone(&Utc))
+}
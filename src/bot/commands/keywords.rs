@@ -15,17 +15,20 @@ use serenity::{
 	Error as SerenityError,
 };
 
-use std::{collections::HashMap, fmt::Write};
+use std::{borrow::Cow, collections::HashMap, fmt::Write};
 
 use super::util::{
-	get_readable_channels_from_args, get_text_channels_in_guild,
+	get_readable_channels_from_args, get_text_channels_in_guild, parse_flags,
 };
 use crate::{
 	bot::{
+		expression::Expression,
+		highlighting::{compile_regex_keyword, parse_proximity_keyword},
 		responses::insert_command_response,
 		util::{error, success, MD_SYMBOL_REGEX},
 	},
-	db::{Ignore, Keyword, KeywordKind},
+	db::{Exclusions, Ignore, Keyword, KeywordKind, MatchKind, Segmentation},
+	global::{PROXIMITY_KEYWORD_MAX_DISTANCE, REGEX_KEYWORD_MAX_LEN},
 	monitoring::Timer,
 	regex,
 	settings::settings,
@@ -38,11 +41,482 @@ static CHANNEL_KEYWORD_REGEX: Lazy<Regex, fn() -> Regex> = Lazy::new(|| {
 	Regex::new(r#"^"((?:\\"|[^"])*)" (?:in|from) ((?:\S+(?:$| ))+)"#).unwrap()
 });
 
+/// Pattern for quoted keywords and ignored phrases, which are always taken literally.
+///
+/// Matches text such as `"--fuzzy"`.
+static QUOTED_KEYWORD_REGEX: Lazy<Regex, fn() -> Regex> =
+	Lazy::new(|| Regex::new(r#"^"((?:\\"|[^"])*)"$"#).unwrap());
+
+/// Pattern for regex keywords and ignored phrases, after `--regex`.
+///
+/// Matches text such as `/foo|bar/`.
+static REGEX_KEYWORD_REGEX: Lazy<Regex, fn() -> Regex> =
+	Lazy::new(|| Regex::new(r"^/(.+)/$").unwrap());
+
+/// Options for a keyword or ignored phrase, given as `--option` arguments before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeywordOptions {
+	match_kind: MatchKind,
+	segmentation: Segmentation,
+	case_sensitive: bool,
+	exclusions: Exclusions,
+}
+
+impl Default for KeywordOptions {
+	fn default() -> Self {
+		Self {
+			match_kind: MatchKind::Literal,
+			segmentation: Segmentation::Auto,
+			case_sensitive: false,
+			exclusions: Exclusions::none(),
+		}
+	}
+}
+
+/// Options that choose the kind of a keyword or ignored phrase, and the kinds they choose.
+const MATCH_KIND_OPTIONS: &[(&str, MatchKind)] = &[
+	("--regex", MatchKind::Regex),
+	("--wildcard", MatchKind::Wildcard),
+	("--fuzzy", MatchKind::Fuzzy),
+	("--stemmed", MatchKind::Stemmed),
+	("--expression", MatchKind::Expression),
+	("--near", MatchKind::Proximity),
+];
+
+/// Parses the options at the start of a command argument, in any order.
+///
+/// Options are `--regex`, `--wildcard`, `--fuzzy`, `--stemmed`, `--expression`, and `--near` to
+/// choose the kind of keyword, `--substring` and `--words` to choose its segmentation,
+/// `--case-sensitive`, and `--exclude=<names>` to exclude parts of messages, where `names` is
+/// parsed with [`parse_flags`](parse_flags). Parsing stops at the first argument that doesn't
+/// start with `--`, so keywords can start with any word.
+///
+/// Returns the options and the rest of the argument, or a message explaining the problem if an
+/// option isn't recognized or conflicts with another.
+fn parse_options(arg: &str) -> Result<(KeywordOptions, &str), String> {
+	let mut options = KeywordOptions::default();
+	let mut rest = arg.trim_start();
+	let mut match_kind = None;
+	let mut segmentation = None;
+
+	while rest.starts_with("--") {
+		let (option, after) = match rest.find(char::is_whitespace) {
+			Some(i) => (&rest[..i], rest[i..].trim_start()),
+			None => (rest, ""),
+		};
+
+		let lowercase = option.to_lowercase();
+
+		if let Some(names) = lowercase.strip_prefix("--exclude=") {
+			options.exclusions = match parse_flags(names) {
+				Some(exclusions) => exclusions,
+				None => {
+					return Err(
+						"I don't know what to exclude! You can exclude \
+						`code`, `code-blocks`, `quotes`, `spoilers`, `links`, \
+						or `all`."
+							.to_owned(),
+					)
+				}
+			};
+		} else if lowercase == "--case-sensitive" {
+			options.case_sensitive = true;
+		} else if lowercase == "--substring" || lowercase == "--words" {
+			let chosen = if lowercase == "--substring" {
+				Segmentation::Substring
+			} else {
+				Segmentation::Words
+			};
+
+			if segmentation.replace(chosen).is_some_and(|s| s != chosen) {
+				return Err("A keyword can't be matched both as a substring \
+					and as whole words!"
+					.to_owned());
+			}
+		} else if let Some(&(_, kind)) = MATCH_KIND_OPTIONS
+			.iter()
+			.find(|(name, _)| *name == lowercase)
+		{
+			if match_kind.replace(kind).is_some_and(|k| k != kind) {
+				return Err("A keyword can only be one of `--regex`, \
+					`--wildcard`, `--fuzzy`, `--stemmed`, `--expression`, \
+					or `--near`!"
+					.to_owned());
+			}
+		} else {
+			return Err(format!(
+				"I don't know the option `{}`! To use a keyword starting \
+				with `--`, put it in quotes.",
+				option
+			));
+		}
+
+		rest = after;
+	}
+
+	options.match_kind = match_kind.unwrap_or(MatchKind::Literal);
+	options.segmentation = segmentation.unwrap_or(Segmentation::Auto);
+
+	Ok((options, rest))
+}
+
+/// Removes the quotes around a quoted keyword or ignored phrase, unescaping quotes inside it.
+///
+/// Text that isn't quoted is returned unchanged.
+fn unquote(text: &str) -> Cow<'_, str> {
+	match QUOTED_KEYWORD_REGEX.captures(text) {
+		Some(captures) => {
+			Cow::Owned(captures.get(1).unwrap().as_str().replace(r#"\""#, "\""))
+		}
+		None => Cow::Borrowed(text),
+	}
+}
+
+/// Determines the text of a keyword or ignored phrase of the given kind, after any options and
+/// with any quotes removed.
+///
+/// Regex keywords are written as `/<pattern>/`, and the pattern is kept as written. Expression
+/// keywords are also kept as written, since their operators are uppercase. Other keywords are
+/// lowercased, unless `case_sensitive` is true.
+///
+/// Returns a message explaining the problem if a regex keyword isn't written between slashes.
+fn keyword_text(
+	text: &str,
+	match_kind: MatchKind,
+	case_sensitive: bool,
+) -> Result<String, &'static str> {
+	let lowercase = |text: &str| {
+		if case_sensitive {
+			text.to_owned()
+		} else {
+			text.to_lowercase()
+		}
+	};
+
+	match match_kind {
+		MatchKind::Regex => match REGEX_KEYWORD_REGEX.captures(text) {
+			Some(captures) => Ok(captures[1].to_owned()),
+			None => Err("Regex keywords look like `--regex /[regex]/`!"),
+		},
+		MatchKind::Expression => Ok(text.trim().to_owned()),
+		MatchKind::Proximity => Ok(lowercase(text.trim())),
+		_ => Ok(lowercase(text)),
+	}
+}
+
+/// A keyword or ignored phrase parsed from a command argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParsedKeyword<'a> {
+	keyword: String,
+	options: KeywordOptions,
+	/// The channels given after a quoted keyword and `in` or `from`, if any.
+	channels: Option<&'a str>,
+}
+
+/// Parses a keyword or ignored phrase, along with any options before it, from a command argument.
+///
+/// The keyword is everything after the options. It can be quoted, in which case it's always taken
+/// literally, and then followed by `in` or `from` and channels. This is the same form keywords are
+/// shown in by [`display_full_keyword`](display_full_keyword), so they can be removed by copying
+/// them from the list of keywords.
+///
+/// Returns a message explaining the problem if the options or keyword are invalid.
+fn parse_keyword_arg(arg: &str) -> Result<ParsedKeyword<'_>, String> {
+	let (options, rest) = parse_options(arg)?;
+
+	let (text, channels) = match CHANNEL_KEYWORD_REGEX.captures(rest) {
+		Some(captures) => (
+			Cow::Owned(captures[1].replace(r#"\""#, "\"")),
+			Some(captures.get(2).unwrap().as_str()),
+		),
+		None => (unquote(rest), None),
+	};
+
+	let keyword =
+		keyword_text(&text, options.match_kind, options.case_sensitive)?;
+
+	Ok(ParsedKeyword {
+		keyword,
+		options,
+		channels,
+	})
+}
+
+/// Parses an ignored phrase and its kind from a command argument, like
+/// [`parse_keyword_arg`](parse_keyword_arg).
+///
+/// Ignored phrases can't be limited to channels, and the only option they can have is their kind.
+/// Returns a message explaining the problem if the argument has anything else.
+fn parse_phrase_arg(arg: &str) -> Result<(String, MatchKind), String> {
+	let parsed = parse_keyword_arg(arg)?;
+
+	let phrase_options = KeywordOptions {
+		match_kind: parsed.options.match_kind,
+		..Default::default()
+	};
+
+	if parsed.options != phrase_options || parsed.channels.is_some() {
+		return Err("Ignored phrases can only be given one of `--regex`, \
+			`--wildcard`, `--fuzzy`, `--stemmed`, `--expression`, or `--near`, \
+			and apply to the whole server!"
+			.to_owned());
+	}
+
+	Ok((parsed.keyword, parsed.options.match_kind))
+}
+
+/// Formats a keyword or ignored phrase the way it's written in commands, after its options.
+///
+/// Keywords that would otherwise be read as options or quoted are quoted.
+fn keyword_arg(keyword: &str, match_kind: MatchKind) -> String {
+	if match_kind == MatchKind::Regex {
+		format!("/{}/", keyword)
+	} else if keyword.starts_with("--") || keyword.starts_with('"') {
+		format!("\"{}\"", keyword.replace('"', r#"\""#))
+	} else {
+		keyword.to_owned()
+	}
+}
+
+/// Formats a keyword or ignored phrase for display in a command response.
+pub(super) fn display_keyword(keyword: &str, match_kind: MatchKind) -> String {
+	match match_kind {
+		MatchKind::Literal => keyword.to_owned(),
+		MatchKind::Regex => format!("`/{}/`", keyword),
+		MatchKind::Wildcard => format!("`{}` (wildcard)", keyword),
+		MatchKind::Fuzzy => format!("{} (fuzzy)", keyword),
+		MatchKind::Stemmed => format!("{} (stemmed)", keyword),
+		MatchKind::Expression => format!("`{}` (expression)", keyword),
+		MatchKind::Proximity => format!("`{}` (proximity)", keyword),
+	}
+}
+
+/// Formats a keyword for display in a command response, the way it's written in commands with
+/// its options, so it can be copied to remove it.
+fn display_full_keyword(keyword: &Keyword) -> String {
+	display_keyword_arg(
+		&keyword.keyword,
+		KeywordOptions {
+			match_kind: keyword.match_kind,
+			segmentation: keyword.segmentation,
+			case_sensitive: keyword.case_sensitive,
+			exclusions: keyword.exclusions,
+		},
+	)
+}
+
+/// Formats an ignored phrase for display in a command response, the way it's written in commands
+/// with its kind, so it can be copied to unignore it.
+fn display_ignore(ignore: &Ignore) -> String {
+	display_keyword_arg(
+		&ignore.phrase,
+		KeywordOptions {
+			match_kind: ignore.match_kind,
+			..Default::default()
+		},
+	)
+}
+
+/// Formats a keyword or ignored phrase with its options the way it's written in commands, in
+/// inline code if it's of a kind that usually contains markdown symbols.
+fn display_keyword_arg(keyword: &str, options: KeywordOptions) -> String {
+	let mut parts = Vec::new();
+
+	if let Some(&(name, _)) = MATCH_KIND_OPTIONS
+		.iter()
+		.find(|&&(_, kind)| kind == options.match_kind)
+	{
+		parts.push(name.to_owned());
+	}
+
+	match options.segmentation {
+		Segmentation::Auto => {}
+		Segmentation::Words => parts.push("--words".to_owned()),
+		Segmentation::Substring => parts.push("--substring".to_owned()),
+	}
+
+	if options.case_sensitive {
+		parts.push("--case-sensitive".to_owned());
+	}
+
+	if !options.exclusions.is_empty() {
+		parts.push(format!(
+			"--exclude={}",
+			options.exclusions.names().collect::<Vec<_>>().join(",")
+		));
+	}
+
+	parts.push(keyword_arg(keyword, options.match_kind));
+
+	let display = parts.join(" ");
+
+	match options.match_kind {
+		MatchKind::Literal | MatchKind::Fuzzy | MatchKind::Stemmed => display,
+		_ => format!("`{}`", display),
+	}
+}
+
+/// Gets the length of a keyword or ignored phrase, not counting any wildcards.
+fn text_len(keyword: &str, match_kind: MatchKind) -> usize {
+	match match_kind {
+		MatchKind::Wildcard => keyword.replace('*', "").len(),
+		_ => keyword.len(),
+	}
+}
+
+/// Checks if a keyword can be highlighted.
+///
+/// Returns a message explaining the problem if it can't.
+fn keyword_error(
+	keyword: &str,
+	match_kind: MatchKind,
+	segmentation: Segmentation,
+) -> Option<String> {
+	if text_len(keyword, match_kind) < 3 {
+		return Some(
+			"You can't highlight keywords shorter than 3 characters!".into(),
+		);
+	}
+
+	if !is_valid_keyword(keyword) {
+		return Some("You can't add that keyword!".into());
+	}
+
+	if segmentation != Segmentation::Auto
+		&& matches!(match_kind, MatchKind::Regex | MatchKind::Fuzzy)
+	{
+		return Some(
+			"Regex and fuzzy keywords can't be matched as substrings or \
+			whole words!"
+				.into(),
+		);
+	}
+
+	match match_kind {
+		MatchKind::Literal | MatchKind::Stemmed => None,
+		MatchKind::Regex => regex_error(keyword),
+		MatchKind::Wildcard => wildcard_error(keyword),
+		MatchKind::Fuzzy => fuzzy_error(keyword),
+		MatchKind::Expression => expression_error(keyword),
+		MatchKind::Proximity => proximity_error(keyword),
+	}
+}
+
+/// Checks if a wildcard keyword or ignored phrase has any wildcards.
+///
+/// Returns a message explaining the problem if it doesn't.
+fn wildcard_error(keyword: &str) -> Option<String> {
+	if keyword.contains('*') {
+		None
+	} else {
+		Some(
+			"Wildcard keywords need at least one `*`; \
+			add it as a normal keyword instead!"
+				.into(),
+		)
+	}
+}
+
+/// Checks if a fuzzy keyword or ignored phrase has any words to match.
+///
+/// Returns a message explaining the problem if it doesn't.
+fn fuzzy_error(keyword: &str) -> Option<String> {
+	if regex!(r"\w").is_match(keyword) {
+		None
+	} else {
+		Some(
+			"Fuzzy keywords need at least one letter or number; \
+			add it as a normal keyword instead!"
+				.into(),
+		)
+	}
+}
+
+/// Checks if an expression keyword or ignored phrase is valid.
+///
+/// Returns a message explaining the problem if it isn't.
+fn expression_error(keyword: &str) -> Option<String> {
+	let expression = match Expression::parse(keyword) {
+		Ok(expression) => expression,
+		Err(msg) => return Some(msg),
+	};
+
+	if expression.terms().any(|term| term.len() < 3) {
+		return Some(
+			"Terms in expressions can't be shorter than 3 characters!".into(),
+		);
+	}
+
+	if expression.evaluate(&mut |_| false) {
+		return Some(
+			"That expression matches messages without any of its terms, \
+			so it would match almost every message!"
+				.into(),
+		);
+	}
+
+	None
+}
+
+/// Checks if a proximity keyword or ignored phrase is well-formed, with long enough terms and a
+/// reasonable distance.
+///
+/// Returns a message explaining the problem if it isn't.
+fn proximity_error(keyword: &str) -> Option<String> {
+	let (first, distance, second) = match parse_proximity_keyword(keyword) {
+		Some(parsed) => parsed,
+		None => return Some(
+			"Proximity keywords look like `--near [term] ~[distance] [term]`!"
+				.into(),
+		),
+	};
+
+	if first.len() < 3 || second.len() < 3 {
+		return Some(
+			"Terms in proximity keywords can't be shorter than 3 characters!"
+				.into(),
+		);
+	}
+
+	if distance > PROXIMITY_KEYWORD_MAX_DISTANCE {
+		return Some(format!(
+			"Terms in proximity keywords can't be more than {} words apart!",
+			PROXIMITY_KEYWORD_MAX_DISTANCE
+		));
+	}
+
+	None
+}
+
+/// Checks if a regex keyword or ignored phrase is valid and reasonably cheap to match.
+///
+/// Returns a message explaining the problem if it isn't.
+fn regex_error(pattern: &str) -> Option<String> {
+	if pattern.len() > REGEX_KEYWORD_MAX_LEN {
+		return Some(format!(
+			"Regexes can't be longer than {} characters!",
+			REGEX_KEYWORD_MAX_LEN
+		));
+	}
+
+	match compile_regex_keyword(pattern) {
+		Ok(regex) if regex.is_match("") => Some(
+			"That regex matches empty text, so it would match every message!"
+				.into(),
+		),
+		Ok(_) => None,
+		Err(regex::Error::CompiledTooBig(_)) => {
+			Some("That regex is too complex!".into())
+		}
+		Err(e) => Some(format!("That regex is invalid:\n```\n{}\n```", e)),
+	}
+}
+
 /// Add a keyword.
 ///
 /// Usage:
-/// - `@Highlights add <keyword>`
-/// - `@Highlights add "<keyword>" in <space-separated channel names, mentions, or IDs>`
+/// - `@Highlights add [options] <keyword>`
+/// - `@Highlights add [options] "<keyword>" in <space-separated channel names, mentions, or IDs>`
 pub async fn add(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 	let _timer = Timer::command("add");
 	let guild_id = require_guild!(ctx, message);
@@ -107,20 +581,33 @@ pub async fn add(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 		}
 	}
 
-	match CHANNEL_KEYWORD_REGEX.captures(args) {
-		Some(captures) => {
-			let keyword = captures
-				.get(1)
-				.context("Captures didn't contain keyword")?
-				.as_str();
-			let channel = captures
-				.get(2)
-				.context("Captures didn't contain channel")?
-				.as_str();
-
-			add_channel_keyword(ctx, message, guild_id, keyword, channel).await
+	let parsed = match parse_keyword_arg(args) {
+		Ok(parsed) => parsed,
+		Err(msg) => return error(ctx, message, msg).await,
+	};
+
+	match parsed.channels {
+		Some(channels) => {
+			add_channel_keyword(
+				ctx,
+				message,
+				guild_id,
+				parsed.keyword,
+				parsed.options,
+				channels,
+			)
+			.await
+		}
+		None => {
+			add_guild_keyword(
+				ctx,
+				message,
+				guild_id,
+				parsed.keyword,
+				parsed.options,
+			)
+			.await
 		}
-		None => add_guild_keyword(ctx, message, guild_id, args).await,
 	}
 }
 
@@ -129,25 +616,23 @@ async fn add_guild_keyword(
 	ctx: &Context,
 	message: &Message,
 	guild_id: GuildId,
-	args: &str,
+	keyword: String,
+	options: KeywordOptions,
 ) -> Result<()> {
-	if args.len() < 3 {
-		return error(
-			ctx,
-			message,
-			"You can't highlight keywords shorter than 3 characters!",
-		)
-		.await;
-	}
-
-	if !is_valid_keyword(args) {
-		return error(ctx, message, "You can't add that keyword!").await;
+	if let Some(msg) =
+		keyword_error(&keyword, options.match_kind, options.segmentation)
+	{
+		return error(ctx, message, msg).await;
 	}
 
 	let keyword = Keyword {
-		keyword: args.to_lowercase(),
+		keyword,
 		user_id: message.author.id,
 		kind: KeywordKind::Guild(guild_id),
+		match_kind: options.match_kind,
+		segmentation: options.segmentation,
+		case_sensitive: options.case_sensitive,
+		exclusions: options.exclusions,
 	};
 
 	if keyword.clone().exists().await? {
@@ -164,20 +649,14 @@ async fn add_channel_keyword(
 	ctx: &Context,
 	message: &Message,
 	guild_id: GuildId,
-	keyword: &str,
+	keyword: String,
+	options: KeywordOptions,
 	channels: &str,
 ) -> Result<()> {
-	if keyword.len() < 3 {
-		return error(
-			ctx,
-			message,
-			"You can't highlight keywords shorter than 3 characters!",
-		)
-		.await;
-	}
-
-	if !is_valid_keyword(keyword) {
-		return error(ctx, message, "You can't add that keyword!").await;
+	if let Some(msg) =
+		keyword_error(&keyword, options.match_kind, options.segmentation)
+	{
+		return error(ctx, message, msg).await;
 	}
 
 	let guild_channels = get_text_channels_in_guild(ctx, guild_id).await?;
@@ -207,9 +686,13 @@ async fn add_channel_keyword(
 
 	for channel in channel_args.found {
 		let keyword = Keyword {
-			keyword: keyword.to_lowercase(),
+			keyword: keyword.clone(),
 			user_id,
 			kind: KeywordKind::Channel(channel.id),
+			match_kind: options.match_kind,
+			segmentation: options.segmentation,
+			case_sensitive: options.case_sensitive,
+			exclusions: options.exclusions,
 		};
 
 		if keyword.clone().exists().await? {
@@ -222,7 +705,10 @@ async fn add_channel_keyword(
 
 	let mut msg = String::with_capacity(45);
 
-	let keyword = MD_SYMBOL_REGEX.replace_all(keyword, r"\$0");
+	let keyword = match options.match_kind {
+		MatchKind::Literal => MD_SYMBOL_REGEX.replace_all(&keyword, r"\$0"),
+		_ => display_keyword(&keyword, options.match_kind).into(),
+	};
 
 	if !added.is_empty() {
 		write!(
@@ -292,8 +778,9 @@ fn is_valid_keyword(keyword: &str) -> bool {
 /// Remove a keyword.
 ///
 /// Usage:
-/// - `@Highlights remove <keyword>`
-/// - `@Highlights remove "<keyword>" from <space-separated channel names, mentions, or IDs>`
+/// - `@Highlights remove [options] <keyword>`
+/// - `@Highlights remove [options] "<keyword>" from <space-separated channel names, mentions, or
+///   IDs>`
 pub async fn remove(
 	ctx: &Context,
 	message: &Message,
@@ -304,21 +791,33 @@ pub async fn remove(
 
 	require_nonempty_args!(args, ctx, message);
 
-	match CHANNEL_KEYWORD_REGEX.captures(args) {
-		Some(captures) => {
-			let keyword = captures
-				.get(1)
-				.context("Captures didn't contain keyword")?
-				.as_str();
-			let channel = captures
-				.get(2)
-				.context("Captures didn't contain channel")?
-				.as_str();
-
-			remove_channel_keyword(ctx, message, guild_id, keyword, channel)
-				.await
+	let parsed = match parse_keyword_arg(args) {
+		Ok(parsed) => parsed,
+		Err(msg) => return error(ctx, message, msg).await,
+	};
+
+	match parsed.channels {
+		Some(channels) => {
+			remove_channel_keyword(
+				ctx,
+				message,
+				guild_id,
+				parsed.keyword,
+				parsed.options,
+				channels,
+			)
+			.await
+		}
+		None => {
+			remove_guild_keyword(
+				ctx,
+				message,
+				guild_id,
+				parsed.keyword,
+				parsed.options,
+			)
+			.await
 		}
-		None => remove_guild_keyword(ctx, message, guild_id, args).await,
 	}
 }
 
@@ -327,12 +826,17 @@ async fn remove_guild_keyword(
 	ctx: &Context,
 	message: &Message,
 	guild_id: GuildId,
-	args: &str,
+	keyword: String,
+	options: KeywordOptions,
 ) -> Result<()> {
 	let keyword = Keyword {
-		keyword: args.to_lowercase(),
+		keyword,
 		user_id: message.author.id,
 		kind: KeywordKind::Guild(guild_id),
+		match_kind: options.match_kind,
+		segmentation: options.segmentation,
+		case_sensitive: options.case_sensitive,
+		exclusions: options.exclusions,
 	};
 
 	if !keyword.clone().exists().await? {
@@ -349,7 +853,8 @@ async fn remove_channel_keyword(
 	ctx: &Context,
 	message: &Message,
 	guild_id: GuildId,
-	keyword: &str,
+	keyword: String,
+	options: KeywordOptions,
 	channels: &str,
 ) -> Result<()> {
 	let guild_channels = get_text_channels_in_guild(ctx, guild_id).await?;
@@ -364,17 +869,19 @@ async fn remove_channel_keyword(
 	)
 	.await?;
 
-	let keyword = keyword.to_lowercase();
-
 	let mut removed = vec![];
 	let mut not_added = vec![];
 	let mut not_found = channel_args.not_found;
 
 	for channel in channel_args.found {
 		let keyword = Keyword {
-			keyword: keyword.to_owned(),
+			keyword: keyword.clone(),
 			user_id,
 			kind: KeywordKind::Channel(channel.id),
+			match_kind: options.match_kind,
+			segmentation: options.segmentation,
+			case_sensitive: options.case_sensitive,
+			exclusions: options.exclusions,
 		};
 
 		if !keyword.clone().exists().await? {
@@ -390,6 +897,10 @@ async fn remove_channel_keyword(
 			keyword: keyword.clone(),
 			user_id,
 			kind: KeywordKind::Channel(user_unreadable.id),
+			match_kind: options.match_kind,
+			segmentation: options.segmentation,
+			case_sensitive: options.case_sensitive,
+			exclusions: options.exclusions,
 		};
 
 		if !keyword.clone().exists().await? {
@@ -405,6 +916,10 @@ async fn remove_channel_keyword(
 			keyword: keyword.clone(),
 			user_id,
 			kind: KeywordKind::Channel(self_unreadable.id),
+			match_kind: options.match_kind,
+			segmentation: options.segmentation,
+			case_sensitive: options.case_sensitive,
+			exclusions: options.exclusions,
 		};
 
 		if !keyword.clone().exists().await? {
@@ -417,7 +932,10 @@ async fn remove_channel_keyword(
 
 	let mut msg = String::with_capacity(45);
 
-	let keyword = MD_SYMBOL_REGEX.replace_all(&keyword, r"\$0");
+	let keyword = match options.match_kind {
+		MatchKind::Literal => MD_SYMBOL_REGEX.replace_all(&keyword, r"\$0"),
+		_ => display_keyword(&keyword, options.match_kind).into(),
+	};
 
 	if !removed.is_empty() {
 		write!(
@@ -470,7 +988,7 @@ async fn remove_channel_keyword(
 
 /// Add an ignored phrase.
 ///
-/// Usage: `@Highlights ignore <phrase>`
+/// Usage: `@Highlights ignore [option] <phrase>`
 pub async fn ignore(
 	ctx: &Context,
 	message: &Message,
@@ -480,7 +998,12 @@ pub async fn ignore(
 
 	require_nonempty_args!(args, ctx, message);
 
-	if args.len() < 3 {
+	let (phrase, match_kind) = match parse_phrase_arg(args) {
+		Ok(parsed) => parsed,
+		Err(msg) => return error(ctx, message, msg).await,
+	};
+
+	if text_len(&phrase, match_kind) < 3 {
 		return error(
 			ctx,
 			message,
@@ -489,10 +1012,24 @@ pub async fn ignore(
 		.await;
 	}
 
+	let kind_error = match match_kind {
+		MatchKind::Literal | MatchKind::Stemmed => None,
+		MatchKind::Regex => regex_error(&phrase),
+		MatchKind::Wildcard => wildcard_error(&phrase),
+		MatchKind::Fuzzy => fuzzy_error(&phrase),
+		MatchKind::Expression => expression_error(&phrase),
+		MatchKind::Proximity => proximity_error(&phrase),
+	};
+
+	if let Some(msg) = kind_error {
+		return error(ctx, message, msg).await;
+	}
+
 	let ignore = Ignore {
 		user_id: message.author.id,
 		guild_id,
-		phrase: args.to_lowercase(),
+		phrase,
+		match_kind,
 	};
 
 	if ignore.clone().exists().await? {
@@ -506,7 +1043,7 @@ pub async fn ignore(
 
 /// Remove an ignored phrase.
 ///
-/// Usage: `@Highlights unignore <phrase>`
+/// Usage: `@Highlights unignore [option] <phrase>`
 pub async fn unignore(
 	ctx: &Context,
 	message: &Message,
@@ -516,10 +1053,16 @@ pub async fn unignore(
 
 	require_nonempty_args!(args, ctx, message);
 
+	let (phrase, match_kind) = match parse_phrase_arg(args) {
+		Ok(parsed) => parsed,
+		Err(msg) => return error(ctx, message, msg).await,
+	};
+
 	let ignore = Ignore {
 		user_id: message.author.id,
 		guild_id,
-		phrase: args.to_lowercase(),
+		phrase,
+		match_kind,
 	};
 
 	if !ignore.clone().exists().await? {
@@ -547,7 +1090,7 @@ pub async fn ignores(
 				Ignore::user_guild_ignores(message.author.id, guild_id)
 					.await?
 					.into_iter()
-					.map(|ignore| ignore.phrase)
+					.map(|ignore| display_ignore(&ignore))
 					.collect::<Vec<_>>();
 
 			if ignores.is_empty() {
@@ -589,7 +1132,7 @@ pub async fn ignores(
 				ignores_by_guild
 					.entry(ignore.guild_id)
 					.or_insert_with(Vec::new)
-					.push(ignore.phrase);
+					.push(display_ignore(&ignore));
 			}
 
 			let mut response = String::new();
@@ -673,7 +1216,7 @@ pub async fn keywords(
 				Keyword::user_guild_keywords(message.author.id, guild_id)
 					.await?
 					.into_iter()
-					.map(|keyword| keyword.keyword)
+					.map(|keyword| display_full_keyword(&keyword))
 					.collect::<Vec<_>>();
 
 			let guild_channels =
@@ -698,7 +1241,7 @@ pub async fn keywords(
 				channel_keywords
 					.entry(channel_id)
 					.or_insert_with(Vec::new)
-					.push(keyword.keyword);
+					.push(display_full_keyword(&keyword));
 			}
 
 			if guild_keywords.is_empty() && channel_keywords.is_empty() {
@@ -767,6 +1310,8 @@ pub async fn keywords(
 			let mut unknown_channel_keywords = HashMap::new();
 
 			for keyword in keywords {
+				let display = display_full_keyword(&keyword);
+
 				match keyword.kind {
 					KeywordKind::Guild(guild_id) => {
 						let guild_keywords = &mut keywords_by_guild
@@ -774,7 +1319,7 @@ pub async fn keywords(
 							.or_insert_with(|| (Vec::new(), HashMap::new()))
 							.0;
 
-						guild_keywords.push(keyword.keyword);
+						guild_keywords.push(display);
 					}
 					KeywordKind::Channel(channel_id) => {
 						let guild_id = ctx
@@ -792,13 +1337,13 @@ pub async fn keywords(
 									.1
 									.entry(channel_id)
 									.or_insert_with(Vec::new)
-									.push(keyword.keyword);
+									.push(display);
 							}
 							None => {
 								unknown_channel_keywords
 									.entry(channel_id)
 									.or_insert_with(Vec::new)
-									.push(keyword.keyword);
+									.push(display);
 							}
 						}
 					}
@@ -856,3 +1401,169 @@ pub async fn keywords(
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serenity::model::id::UserId;
+
+	fn parse(arg: &str) -> ParsedKeyword<'_> {
+		parse_keyword_arg(arg).unwrap()
+	}
+
+	/// Formats a keyword the way it's listed, then parses it the way it'd be copied from the list
+	/// into `remove`, without the inline code markdown.
+	fn round_trip(
+		keyword: &str,
+		options: KeywordOptions,
+	) -> ParsedKeyword<'static> {
+		let display = display_full_keyword(&Keyword {
+			keyword: keyword.to_owned(),
+			user_id: UserId(1),
+			kind: KeywordKind::Guild(GuildId(1)),
+			match_kind: options.match_kind,
+			segmentation: options.segmentation,
+			case_sensitive: options.case_sensitive,
+			exclusions: options.exclusions,
+		});
+
+		let copied = display.trim_matches('`').to_owned();
+
+		let parsed = parse_keyword_arg(&copied).unwrap();
+
+		ParsedKeyword {
+			keyword: parsed.keyword,
+			options: parsed.options,
+			channels: None,
+		}
+	}
+
+	#[test]
+	fn literal_keywords_starting_with_option_words() {
+		for word in &[
+			"regex",
+			"wildcard",
+			"fuzzy",
+			"stemmed",
+			"near",
+			"expression",
+			"substring",
+			"words",
+			"case-sensitive",
+			"excluding",
+		] {
+			let keyword = format!("{} of wisdom", word);
+
+			let added = parse(&keyword);
+			assert_eq!(added.keyword, keyword);
+			assert_eq!(added.options, KeywordOptions::default());
+			assert_eq!(added.channels, None);
+
+			let removed = round_trip(&added.keyword, added.options);
+			assert_eq!(removed, added);
+		}
+
+		let added = parse("regex /foo/");
+		assert_eq!(added.keyword, "regex /foo/");
+		assert_eq!(added.options.match_kind, MatchKind::Literal);
+	}
+
+	#[test]
+	fn keyword_options() {
+		let parsed = parse("--case-sensitive --words --exclude=code,quotes Go");
+
+		assert_eq!(parsed.keyword, "Go");
+		assert_eq!(
+			parsed.options,
+			KeywordOptions {
+				match_kind: MatchKind::Literal,
+				segmentation: Segmentation::Words,
+				case_sensitive: true,
+				exclusions: Exclusions::INLINE_CODE.union(Exclusions::QUOTES),
+			}
+		);
+
+		assert_eq!(
+			parse("--exclude=quotes,code --words --case-sensitive Go"),
+			parsed
+		);
+
+		let parsed = parse("--regex /\\bPR-\\d+\\b/");
+		assert_eq!(parsed.keyword, "\\bPR-\\d+\\b");
+		assert_eq!(parsed.options.match_kind, MatchKind::Regex);
+
+		let parsed = parse("--near Release ~3 Notes");
+		assert_eq!(parsed.keyword, "release ~3 notes");
+		assert_eq!(parsed.options.match_kind, MatchKind::Proximity);
+
+		assert!(parse_keyword_arg("--regex foo").is_err());
+		assert!(parse_keyword_arg("--fuzzy --stemmed foo").is_err());
+		assert!(parse_keyword_arg("--substring --words foo").is_err());
+		assert!(parse_keyword_arg("--exclude=code,nothing foo").is_err());
+		assert!(parse_keyword_arg("--force foo").is_err());
+	}
+
+	#[test]
+	fn quoted_keywords() {
+		let parsed = parse("\"--fuzzy\"");
+		assert_eq!(parsed.keyword, "--fuzzy");
+		assert_eq!(parsed.options, KeywordOptions::default());
+
+		let parsed = parse("\"say \\\"hi\\\"\"");
+		assert_eq!(parsed.keyword, "say \"hi\"");
+
+		let parsed = parse("--fuzzy \"kubernetes\" in general dev");
+		assert_eq!(parsed.keyword, "kubernetes");
+		assert_eq!(parsed.options.match_kind, MatchKind::Fuzzy);
+		assert_eq!(parsed.channels, Some("general dev"));
+
+		let parsed = parse("\"near miss\" from general");
+		assert_eq!(parsed.keyword, "near miss");
+		assert_eq!(parsed.options.match_kind, MatchKind::Literal);
+		assert_eq!(parsed.channels, Some("general"));
+	}
+
+	#[test]
+	fn displayed_keywords_round_trip() {
+		let keywords = [
+			("--force", MatchKind::Literal),
+			("\"quoted\"", MatchKind::Literal),
+			("pr-\\d+", MatchKind::Regex),
+			("deploy*", MatchKind::Wildcard),
+			("kubernetes", MatchKind::Fuzzy),
+			("build", MatchKind::Stemmed),
+			("outage AND (api OR gateway)", MatchKind::Expression),
+			("release ~3 notes", MatchKind::Proximity),
+		];
+
+		for &(keyword, match_kind) in &keywords {
+			let options = KeywordOptions {
+				match_kind,
+				segmentation: Segmentation::Substring,
+				case_sensitive: true,
+				exclusions: Exclusions::all(),
+			};
+
+			let parsed = round_trip(keyword, options);
+			assert_eq!(parsed.keyword, keyword);
+			assert_eq!(parsed.options, options);
+		}
+	}
+
+	#[test]
+	fn ignored_phrase_options() {
+		assert_eq!(
+			parse_phrase_arg("fuzzy logic").unwrap(),
+			("fuzzy logic".to_owned(), MatchKind::Literal)
+		);
+
+		assert_eq!(
+			parse_phrase_arg("--fuzzy logic").unwrap(),
+			("logic".to_owned(), MatchKind::Fuzzy)
+		);
+
+		assert!(parse_phrase_arg("--case-sensitive Go").is_err());
+		assert!(parse_phrase_arg("\"meme\" in general").is_err());
+	}
+}
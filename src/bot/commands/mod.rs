@@ -20,6 +20,15 @@ pub use blocks::{block, blocks, unblock};
 mod opt_out;
 pub use opt_out::{opt_in, opt_out};
 
+mod quiet_hours;
+pub use quiet_hours::{quiet, quiet_hours, unquiet};
+
+mod user_settings;
+pub use user_settings::{list_settings, set};
+
+mod history;
+pub use history::history;
+
 use anyhow::{Context as _, Result};
 use indoc::formatdoc;
 use serenity::{
@@ -31,9 +40,11 @@ use std::time::Instant;
 
 use crate::{
 	bot::{responses::insert_command_response, util::question},
-	global::EMBED_COLOR,
+	global::{
+		DIGEST_MAX_INTERVAL, DIGEST_MIN_INTERVAL, EMBED_COLOR,
+		HISTORY_PAGE_SIZE, NOTIFICATION_CONTEXT_MAX_MESSAGES,
+	},
 	monitoring::Timer,
-	require_embed_perms,
 	settings::settings,
 };
 
@@ -179,16 +190,82 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 				{name} will notify you (in DMs) about any messages containing your keywords \
 				(other than messages in muted channels or messages with ignored phrases).
 
-				In this usage, all of the text after `add` will be treated as one keyword.
-
-				Keywords are case-insensitive.
+				You can react to a notification to act on it: 🔇 mutes the channel, 🚫 blocks the \
+				author, 🗑️ removes the keywords it was sent for, and ✅ dismisses it.
+
+				In this usage, all of the text after `add` will be treated as one keyword, apart \
+				from options starting with `--` at the start, which can be given in any order. \
+				To add a keyword that starts with `--`, or to make sure nothing in it is read as \
+				an option, put it in quotes, like `@{name} add \"[keyword]\"`; quoted keywords \
+				are always taken literally, and you can use `\\\"` for a quote in one.
+
+				Keywords are case-insensitive, and full-width letters and other compatibility \
+				characters match their normal forms. \
+				You can make a keyword case-sensitive with the `--case-sensitive` option, \
+				like `@{name} add --case-sensitive [keyword]`. \
+				You can also make keywords ignore accents or match look-alike characters with \
+				`@{name} set`; see `@{name} help set` for more information.
+
+				You can add a regular expression as a keyword with `@{name} add --regex /[regex]/`. \
+				Regex keywords can match anywhere in a message, so use `\\b` if you only want \
+				them to match whole words. \
+				See [the regex documentation](https://docs.rs/regex/1/regex/#syntax) for the \
+				supported syntax.
+
+				You can add a keyword with wildcards with `@{name} add --wildcard [keyword]`. \
+				Each `*` in a wildcard keyword matches any letters or numbers, so \
+				`deploy*` matches \"deployment\" and `*bot` matches \"chatbot\". \
+				Apart from that, wildcard keywords are matched just like normal keywords.
+
+				You can add a keyword that tolerates typos with `@{name} add --fuzzy [keyword]`. \
+				Each word of a fuzzy keyword can match a word with one edit (an added, removed, \
+				changed, or swapped letter) if it's 5 to 8 characters long, or two edits if it's \
+				longer. Words of 4 characters or less have to match exactly. \
+				Notifications for fuzzy keywords show the text that was matched.
+
+				You can add a keyword that also matches the same word with different endings with \
+				`@{name} add --stemmed [keyword]`, so `--stemmed build` matches \"builds\" and \
+				\"building\". Only endings are compared, so irregular forms like \"built\" \
+				aren't matched. \
+				Stemmed keywords are matched in English unless you choose another language with \
+				`@{name} set language`; see `@{name} help set` for more information.
+
+				You can add a keyword that combines several words or phrases with \
+				`@{name} add --expression [expression]`. Terms can be combined with `AND`, `OR`, \
+				and `NOT` (written in uppercase), and grouped with parentheses, so \
+				`outage AND (api OR gateway) NOT test` matches messages mentioning an outage and \
+				either the API or the gateway, but not tests. \
+				Each term is matched like a normal keyword, and words next to each other form a \
+				phrase; put a term in quotes to use `AND`, `OR`, or `NOT` in it. \
+				Notifications for expression keywords show which terms were matched.
+
+				You can add a keyword whose words can appear in any order, close to each other, \
+				with `@{name} add --near [term] ~[distance] [term]`. \
+				The two terms can be separated by at most `[distance]` other words, so \
+				`release ~3 notes` matches \"notes for the release\" as well as \
+				\"release notes\".
+
+				Keywords normally only match whole words. Keywords in languages written without \
+				spaces between words, like Chinese, Japanese, or Thai, match wherever Unicode \
+				word segmentation finds a word boundary instead. \
+				You can change this for one keyword with the `--substring` or `--words` option: \
+				`@{name} add --substring [keyword]` matches anywhere, even inside other words, \
+				and `@{name} add --words [keyword]` only matches whole words separated by spaces \
+				or punctuation. This works for every kind of keyword except regex and fuzzy \
+				keywords.
+
+				You can stop a keyword from matching in some parts of messages with the \
+				`--exclude=[parts]` option, where `[parts]` is a comma-separated list, without \
+				spaces, of `code` (inline code), `code-blocks`, `quotes`, `spoilers`, `links` \
+				(link addresses), or `all`. \
+				You can also exclude parts of messages for all of your keywords with \
+				`@{name} set exclude`; see `@{name} help set` for more information.
 
 				You can also add a keyword in just a specific channel or channels with \
 				`@{name} add \"[keyword]\" in [channels]`. \
 				You'll only be notified of keywords added this way when they appear in the \
 				specified channel(s) (not when they appear anywhere else). \
-				The keyword must be surrounded with quotes, and you can use `\\\"` to add a \
-				keyword with a quote in it. \
+				The keyword must be surrounded with quotes, and any options go before the quotes. \
 				`[channels]` may be channel mentions, channel names, or channel IDs. \
 				You can specify multiple channels, separated by spaces, to add the keyword in \
 				all of them at once.
@@ -207,7 +284,47 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 				`@{name} add \"optimize\" in javascript`
 
 				Add the keyword \"hello world\" in the current server:
-				`@{name} add hello world`",
+				`@{name} add hello world`
+
+				Add a regex keyword matching PR numbers like \"PR-1234\" in the current server:
+				`@{name} add --regex /\\bPR-\\d+\\b/`
+
+				Add the keyword \"テスト\" in the current server, matching even inside longer words \
+				like \"テストケース\":
+				`@{name} add --substring テスト`
+
+				Add a wildcard keyword matching \"deploy\", \"deploying\", \"deployment\", etc. in the \
+				current server:
+				`@{name} add --wildcard deploy*`
+
+				Add a fuzzy keyword matching \"kubernetes\" as well as typos like \"kubernets\" \
+				in the current server:
+				`@{name} add --fuzzy kubernetes`
+
+				Add a stemmed keyword matching \"build\", \"builds\", \"building\", etc. in the \
+				current server:
+				`@{name} add --stemmed build`
+
+				Add an expression keyword matching messages about outages of the API or gateway, \
+				but not tests, in the current server:
+				`@{name} add --expression outage AND (api OR gateway) NOT test`
+
+				Add a proximity keyword matching \"release notes\" and \"notes for the release\" \
+				in the current server:
+				`@{name} add --near release ~3 notes`
+
+				Add the keyword \"Go\" in the current server, without matching \"go\":
+				`@{name} add --case-sensitive Go`
+
+				Add the keyword \"panic\" in the current server, without matching it in code \
+				or code blocks:
+				`@{name} add --exclude=code,code-blocks panic`
+
+				Add the keyword \"near miss\" in the current server:
+				`@{name} add near miss`
+
+				Add the keyword \"--force\" in the current server:
+				`@{name} add \"--force\"`",
 				name = username
 			)),
 		},
@@ -218,14 +335,15 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 				Use `@{name} remove [keyword]` to remove a keyword that you previously added \
 				with `@{name} add` in the current server.
 
-				In this usage, all of the text after `remove` will be treated as one keyword.
-
-				Keywords are case-insensitive.
+				In this usage, all of the text after `remove` will be treated as one keyword, \
+				apart from options starting with `--` at the start, just like with \
+				`@{name} add`. A keyword is only removed if it's given the same options it was \
+				added with, in any order. `@{name} keywords` lists each keyword the way it's \
+				written with its options, so you can copy a keyword from there to remove it.
 
 				You can also remove a keyword that you added to a specific channel or channels \
 				with `@{name} remove \"[keyword]\" from [channels]`. \
-				The keyword must be surrounded with quotes, and you can use `\\\"` to remove a \
-				keyword with a quote in it. \
+				The keyword must be surrounded with quotes, and any options go before the quotes. \
 				`[channels]` may be channel mentions, channel names, or channel IDs. \
 				You can specify multiple channels, separated by spaces, to remove the keyword \
 				from all of them at once.
@@ -350,6 +468,15 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 
 				Phrases are case-insensitive.
 
+				You can ignore a regular expression with `@{name} ignore --regex /[regex]/`, \
+				a phrase with wildcards with `@{name} ignore --wildcard [phrase]`, \
+				a phrase with typos with `@{name} ignore --fuzzy [phrase]`, \
+				a word with any ending with `@{name} ignore --stemmed [phrase]`, \
+				a combination of phrases with `@{name} ignore --expression [expression]`, \
+				or words near each other with `@{name} ignore --near [term] ~[distance] [term]`; \
+				see `@{name} help add` for more information about these kinds of phrases. \
+				Put a phrase in quotes to make sure it's taken literally.
+
 				You can remove ignored phrases later with `@{name} unignore [phrase]`; see \
 				`@{name} help unignore` for more information.
 
@@ -369,10 +496,13 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 			name: "unignore",
 			short_desc: "Remove an ignored phrase in the current server",
 			long_desc: formatdoc!("
-				Use `@{name} ignore [phrase]` to remove a phrase you previously ignored in the \
+				Use `@{name} unignore [phrase]` to remove a phrase you previously ignored in the \
 				current server.
 
-				Phrases are case-insensitive.
+				Phrases are case-insensitive. A phrase is only removed if it's given the same \
+				option it was ignored with, like `--fuzzy`. `@{name} ignores` lists each phrase \
+				the way it's written with its option, so you can copy a phrase from there to \
+				remove it.
 
 				You can list your current keywords with `@{name} ignores`.",
 				name = username,
@@ -395,6 +525,9 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 				Using `keywords` in a server will show you only the keywords you've highlighted \
 				in that server, including all channel-specific keywords there.
 
+				Keywords are listed the way they're written with their options, like \
+				`--fuzzy kubernetes`, so you can copy them to remove them.
+
 				Using `keywords` in DMs with the bot will list keywords you've highlighted \
 				across all shared servers, including potentially deleted servers or servers this \
 				bot is no longer a member of.
@@ -519,6 +652,178 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 			),
 			examples: None,
 		},
+		CommandInfo {
+			name: "quiet",
+			short_desc: "Add quiet hours when you won't be notified",
+			long_desc: formatdoc!("
+				Use `@{name} quiet [days] [start]-[end]` to add quiet hours, when you \
+				won't be sent notifications.
+
+				`days` is a comma-separated list of days of the week, like `mon,tue`, or \
+				`weekdays`, `weekends`, or `daily`. `start` and `end` are times like `22:00`, \
+				in the time zone you chose with `@{name} set timezone` (UTC by default). \
+				Quiet hours that end before they start continue into the next day.
+
+				Notifications during quiet hours are sent together when they end, unless \
+				you use `@{name} set quiet-mode drop` to drop them instead.",
+				name = username
+			),
+			examples: Some(formatdoc!("
+				Don't send notifications overnight on weeknights:
+				`@{name} quiet mon,tue,wed,thu,sun 22:00-07:00`
+
+				Don't send notifications on weekends until 10:00:
+				`@{name} quiet weekends 00:00-10:00`",
+				name = username
+			)),
+		},
+		CommandInfo {
+			name: "unquiet",
+			short_desc: "Remove quiet hours",
+			long_desc: formatdoc!("
+				Use `@{name} unquiet [days] [start]-[end]` to remove quiet hours you \
+				added with `@{name} quiet`, or `@{name} unquiet all` to remove all of them.",
+				name = username
+			),
+			examples: Some(formatdoc!("
+				Remove quiet hours on Saturdays:
+				`@{name} unquiet sat 00:00-10:00`
+
+				Remove all of your quiet hours:
+				`@{name} unquiet all`",
+				name = username
+			)),
+		},
+		CommandInfo {
+			name: "quiet-hours",
+			short_desc: "List your quiet hours",
+			long_desc: formatdoc!("
+				Use `@{name} quiet-hours` to list your quiet hours, along with your time \
+				zone and whether notifications during them are dropped.",
+				name = username
+			),
+			examples: None,
+		},
+		CommandInfo {
+			name: "set",
+			short_desc: "Change how your keywords are matched and notified",
+			long_desc: formatdoc!("
+				Use `@{name} set [setting] [value]` to change one of your settings. \
+				Your settings apply to all of your keywords and ignored phrases.
+
+				The available settings are:
+				- `language`: the language your stemmed keywords are matched in \
+				(English by default); use `@{name} settings` to see the available languages
+				- `strip-accents`: when `on`, accents are ignored, so \"café\" and \"cafe\" \
+				match each other (`off` by default)
+				- `confusables`: when `on`, characters that look alike match each other, so \
+				\"paypal\" matches \"раypal\" written with Cyrillic letters (`off` by default)
+				- `exclude`: parts of messages your keywords never match in, as a comma-separated \
+				list of `code` (inline code), `code-blocks`, `quotes`, `spoilers`, `links` (link \
+				addresses), or `all` (`none` by default)
+				- `context`: the number of messages sent before a message to show in its \
+				notifications, along with the message it replied to, up to {max_context} \
+				(`off` by default); they're only shown if you can read the channel's history
+				- `delivery`: when you're notified; `immediate` sends a notification for each \
+				message (the default), `every [minutes]` collects notifications into a digest \
+				sent every {min_interval} to {max_interval} minutes, and `daily [HH:MM]` sends \
+				a digest once a day at that time
+				- `timezone`: the time zone daily digests and quiet hours are in, like \
+				`America/New_York` (`UTC` by default)
+				- `quiet-mode`: whether notifications during your quiet hours are sent when \
+				they end (`queue`, the default) or dropped (`drop`); see `@{name} help quiet`
+				- `activity`: what you can do in a server after a message, as a comma-separated \
+				list of `typing`, `messages` (in any channel), `voice` (joining, leaving, or \
+				muting in voice channels), `all`, or `none`, to skip being notified about it \
+				because you've probably seen it (`typing, messages` by default)
+				- `away-only`: when `on`, you're only notified while you're idle, on do not \
+				disturb, or offline, if this bot can see who's online (`off` by default)
+				- `cooldown`: the number of minutes to wait after a notification about a channel \
+				before you're notified about it again, or `off`
+				- `hourly-limit`: the most notifications you're sent each hour, or `off`; \
+				notifications held back by your cooldown or hourly limit are counted in your \
+				next notification, and either can be set to `default` to use the bot's default
+
+				These settings don't apply to regex keywords, which are matched against \
+				messages as they're written.
+
+				You can list your current settings with `@{name} settings`.",
+				name = username,
+				max_context = NOTIFICATION_CONTEXT_MAX_MESSAGES,
+				min_interval = DIGEST_MIN_INTERVAL,
+				max_interval = DIGEST_MAX_INTERVAL,
+			),
+			examples: Some(formatdoc!("
+				Match your stemmed keywords in French:
+				`@{name} set language french`
+
+				Ignore accents in your keywords:
+				`@{name} set strip-accents on`
+
+				Never match your keywords in code, code blocks, or quotes:
+				`@{name} set exclude code,code-blocks,quotes`
+
+				Show the three messages before each message you're notified about:
+				`@{name} set context 3`
+
+				Don't get notified about messages you might've seen while typing, chatting, or in voice:
+				`@{name} set activity all`
+
+				Get one digest of your notifications every morning at 9:00 in New York:
+				`@{name} set delivery daily 09:00`
+				`@{name} set timezone America/New_York`
+
+				Be notified about each channel at most once every 10 minutes:
+				`@{name} set cooldown 10`",
+				name = username
+			)),
+		},
+		CommandInfo {
+			name: "settings",
+			short_desc: "List your current settings",
+			long_desc: formatdoc!("
+				Use `@{name} settings` to list your current settings.
+
+				See `@{name} help set` for more information about each setting.",
+				name = username
+			),
+			examples: None,
+		},
+		CommandInfo {
+			name: "history",
+			short_desc: "List notifications you've been sent",
+			long_desc: formatdoc!("
+				Use `@{name} history` to list the notifications you've been sent, newest \
+				first, {page_size} to a page. Add a page number to see older notifications.
+
+				When used in a server, only notifications about messages in that server are \
+				listed. In DMs, notifications from every server are listed; use \
+				`server:[server ID]` to pick one server. You can also filter by keyword with \
+				`keyword:[keyword]` (quote keywords with spaces in them), and by date with \
+				`from:[YYYY-MM-DD]` and `to:[YYYY-MM-DD]`, in your time zone (see \
+				`@{name} help set`).{retention}",
+				name = username,
+				page_size = HISTORY_PAGE_SIZE,
+				retention = match settings().database.notification_history_days {
+					0 => String::new(),
+					days => format!(
+						"\n\nNotifications are removed from your history after {} days.",
+						days
+					),
+				},
+			),
+			examples: Some(formatdoc!("
+				List your notifications from the current server:
+				`@{name} history`
+
+				See the second page of your notifications from every server (in DMs):
+				`@{name} history 2`
+
+				List your notifications for \"release notes\" during June 2021:
+				`@{name} history keyword:\"release notes\" from:2021-06-01 to:2021-06-30`",
+				name = username
+			)),
+		},
 		CommandInfo {
 			name: "help",
 			short_desc: "Show this help message",
@@ -582,7 +887,7 @@ pub async fn help(ctx: &Context, message: &Message, args: &str) -> Result<()> {
 			.find(|info| info.name.eq_ignore_ascii_case(args))
 		{
 			Some(info) => info,
-			None => return question(ctx, &message).await,
+			None => return question(ctx, message).await,
 		};
 
 		message
@@ -0,0 +1,239 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Commands for adding, removing, and listing quiet hours.
+
+use anyhow::Result;
+use chrono::Weekday;
+use serenity::{client::Context, model::channel::Message};
+
+use super::util::{display_time_of_day, parse_time_of_day};
+
+use crate::{
+	bot::{
+		responses::insert_command_response,
+		util::{error, success},
+	},
+	db::{QuietHours, UserSettings},
+	monitoring::Timer,
+};
+
+/// Every day of the week, starting from Monday.
+const DAYS: [Weekday; 7] = [
+	Weekday::Mon,
+	Weekday::Tue,
+	Weekday::Wed,
+	Weekday::Thu,
+	Weekday::Fri,
+	Weekday::Sat,
+	Weekday::Sun,
+];
+
+/// Add quiet hours.
+///
+/// Usage: `@Highlights quiet <days> <HH:MM>-<HH:MM>`
+pub async fn quiet(ctx: &Context, message: &Message, args: &str) -> Result<()> {
+	let _timer = Timer::command("quiet");
+
+	require_nonempty_args!(args, ctx, message);
+
+	let windows = match parse_quiet_hours(message, args) {
+		Ok(windows) => windows,
+		Err(msg) => return error(ctx, message, msg).await,
+	};
+
+	for window in windows {
+		window.insert().await?;
+	}
+
+	success(ctx, message).await
+}
+
+/// Remove quiet hours.
+///
+/// Usage: `@Highlights unquiet <days> <HH:MM>-<HH:MM>` or `@Highlights unquiet all`
+pub async fn unquiet(
+	ctx: &Context,
+	message: &Message,
+	args: &str,
+) -> Result<()> {
+	let _timer = Timer::command("unquiet");
+
+	require_nonempty_args!(args, ctx, message);
+
+	if args.eq_ignore_ascii_case("all") {
+		let deleted =
+			QuietHours::delete_user_quiet_hours(message.author.id).await?;
+
+		return if deleted == 0 {
+			error(ctx, message, "You don't have any quiet hours!").await
+		} else {
+			success(ctx, message).await
+		};
+	}
+
+	let windows = match parse_quiet_hours(message, args) {
+		Ok(windows) => windows,
+		Err(msg) => return error(ctx, message, msg).await,
+	};
+
+	let mut deleted_any = false;
+
+	for window in windows {
+		deleted_any |= window.delete().await?;
+	}
+
+	if deleted_any {
+		success(ctx, message).await
+	} else {
+		error(ctx, message, "You don't have those quiet hours!").await
+	}
+}
+
+/// List quiet hours.
+///
+/// Usage: `@Highlights quiet-hours`
+pub async fn quiet_hours(
+	ctx: &Context,
+	message: &Message,
+	args: &str,
+) -> Result<()> {
+	let _timer = Timer::command("quiet-hours");
+
+	require_empty_args!(args, ctx, message);
+
+	let quiet_hours = QuietHours::user_quiet_hours(message.author.id).await?;
+
+	if quiet_hours.is_empty() {
+		return error(ctx, message, "You don't have any quiet hours!").await;
+	}
+
+	let settings = UserSettings::user_settings(message.author.id).await?;
+
+	let mut windows = Vec::<((u16, u16), Vec<Weekday>)>::new();
+
+	for window in quiet_hours {
+		match windows
+			.iter_mut()
+			.find(|(times, _)| *times == (window.start, window.end))
+		{
+			Some((_, days)) => days.push(window.day),
+			None => {
+				windows.push(((window.start, window.end), vec![window.day]))
+			}
+		}
+	}
+
+	let windows = windows
+		.into_iter()
+		.map(|((start, end), days)| {
+			let days = days
+				.iter()
+				.map(|day| format!("{:?}", day))
+				.collect::<Vec<_>>()
+				.join(", ");
+
+			format!(
+				"{} {}–{}",
+				days,
+				display_time_of_day(start),
+				display_time_of_day(end)
+			)
+		})
+		.collect::<Vec<_>>();
+
+	let msg = format!(
+		"{}'s quiet hours ({}, notifications are {}):\n  - {}",
+		message.author.name,
+		settings.timezone.as_deref().unwrap_or("UTC"),
+		if settings.drop_in_quiet_hours {
+			"dropped"
+		} else {
+			"sent when they end"
+		},
+		windows.join("\n  - ")
+	);
+
+	let response = message
+		.channel_id
+		.send_message(ctx, |m| {
+			m.content(msg).allowed_mentions(|m| m.empty_parse())
+		})
+		.await?;
+
+	insert_command_response(ctx, message.id, response.id).await;
+
+	Ok(())
+}
+
+/// Parses the arguments of `quiet` and `unquiet` into a window of quiet hours on each of the given
+/// days.
+///
+/// The arguments are a comma-separated list of days followed by a range of times, like
+/// `mon,tue 22:00-07:00`. `weekdays`, `weekends`, and `daily` can be used as days.
+fn parse_quiet_hours(
+	message: &Message,
+	args: &str,
+) -> Result<Vec<QuietHours>, &'static str> {
+	const USAGE: &str = "Give days and a range of times, like \
+		`weekdays 22:00-07:00` or `sat,sun 00:00-10:00`!";
+
+	let (days, times) = {
+		let mut iter = args.split_whitespace();
+
+		match (iter.next(), iter.next(), iter.next()) {
+			(Some(days), Some(times), None) => (days, times),
+			_ => return Err(USAGE),
+		}
+	};
+
+	let days = parse_days(days).ok_or(
+		"I don't know those days! Use names like `mon` or `monday`, \
+		or `weekdays`, `weekends`, or `daily`.",
+	)?;
+
+	let (start, end) = {
+		let mut iter = times.splitn(2, '-');
+
+		match (
+			iter.next().and_then(parse_time_of_day),
+			iter.next().and_then(parse_time_of_day),
+		) {
+			(Some(start), Some(end)) => (start, end),
+			_ => return Err(USAGE),
+		}
+	};
+
+	if start == end {
+		return Err("Quiet hours need to start and end at different times!");
+	}
+
+	Ok(days
+		.into_iter()
+		.map(|day| QuietHours {
+			user_id: message.author.id,
+			day,
+			start,
+			end,
+		})
+		.collect())
+}
+
+/// Parses a comma-separated list of days of the week.
+fn parse_days(days: &str) -> Option<Vec<Weekday>> {
+	let mut parsed = vec![];
+
+	for day in days.split(',') {
+		match &*day.to_lowercase() {
+			"daily" | "everyday" => parsed.extend_from_slice(&DAYS),
+			"weekdays" => parsed.extend_from_slice(&DAYS[..5]),
+			"weekends" => parsed.extend_from_slice(&DAYS[5..]),
+			day => parsed.push(day.parse().ok()?),
+		}
+	}
+
+	parsed.sort_by_key(Weekday::num_days_from_monday);
+	parsed.dedup();
+
+	Some(parsed)
+}
@@ -0,0 +1,420 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Commands for viewing and changing user settings.
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serenity::{client::Context, model::channel::Message};
+
+use super::util::{
+	display_flags, display_time_of_day, parse_flags, parse_time_of_day,
+};
+use crate::{
+	bot::{
+		highlighting::STEM_LANGUAGES,
+		responses::insert_command_response,
+		util::{error, parse_timezone, success},
+	},
+	db::{Delivery, UserSettings},
+	global::{
+		DIGEST_MAX_INTERVAL, DIGEST_MIN_INTERVAL,
+		NOTIFICATION_CONTEXT_MAX_MESSAGES,
+	},
+	monitoring::Timer,
+	settings::settings,
+};
+
+/// Pattern for batched delivery.
+///
+/// Matches text such as `every 30 minutes` or `every 2h`.
+static BATCHED_DELIVERY_REGEX: Lazy<Regex, fn() -> Regex> = Lazy::new(|| {
+	Regex::new(r"^every (\d+) ?(m|mins?|minutes?|h|hours?)?$").unwrap()
+});
+
+/// Change one of your settings.
+///
+/// Usage: `@Highlights set <setting> <value>`
+pub async fn set(ctx: &Context, message: &Message, args: &str) -> Result<()> {
+	let _timer = Timer::command("set");
+
+	require_nonempty_args!(args, ctx, message);
+
+	let (setting, value) = {
+		let mut iter = args.splitn(2, ' ');
+
+		let setting = iter.next().unwrap_or("").to_lowercase();
+		let value = iter.next().map(|s| s.trim()).unwrap_or("").to_lowercase();
+
+		(setting, value)
+	};
+
+	if value.is_empty() {
+		return error(
+			ctx,
+			message,
+			"You need to give a value for the setting!",
+		)
+		.await;
+	}
+
+	let mut settings = UserSettings::user_settings(message.author.id).await?;
+
+	match &*setting {
+		"language" => {
+			if !STEM_LANGUAGES.iter().any(|(name, _)| *name == value) {
+				return error(
+					ctx,
+					message,
+					format!(
+						"I can't stem that language! Available languages: {}",
+						language_names()
+					),
+				)
+				.await;
+			}
+
+			settings.stem_language = Some(value);
+		}
+		"strip-accents" => match parse_toggle(&value) {
+			Some(toggle) => settings.strip_accents = toggle,
+			None => return toggle_error(ctx, message).await,
+		},
+		"confusables" => match parse_toggle(&value) {
+			Some(toggle) => settings.match_confusables = toggle,
+			None => return toggle_error(ctx, message).await,
+		},
+		"context" => match parse_context_messages(&value) {
+			Some(count) => settings.context_messages = count,
+			None => {
+				return error(
+					ctx,
+					message,
+					format!(
+						"Context can only be `off` or a number of messages \
+						up to {}!",
+						NOTIFICATION_CONTEXT_MAX_MESSAGES
+					),
+				)
+				.await
+			}
+		},
+		"delivery" => match parse_delivery(&value) {
+			Some(delivery) => settings.delivery = delivery,
+			None => {
+				return error(
+					ctx,
+					message,
+					format!(
+						"Delivery can only be `immediate`, `every` a number of \
+						minutes from {} to {}, or `daily` at a time like \
+						`daily 09:00`!",
+						DIGEST_MIN_INTERVAL, DIGEST_MAX_INTERVAL
+					),
+				)
+				.await
+			}
+		},
+		"timezone" => match parse_timezone(&value) {
+			Some(timezone) => {
+				settings.timezone = Some(timezone.name().to_owned())
+			}
+			None => {
+				return error(
+					ctx,
+					message,
+					"I don't know that time zone! Use a name like \
+					`America/New_York` or `Europe/London`.",
+				)
+				.await
+			}
+		},
+		"activity" => match parse_flags(&value) {
+			Some(signals) => settings.activity_signals = signals,
+			None => {
+				return error(
+					ctx,
+					message,
+					"I don't know that activity! You can choose `typing`, \
+					`messages`, `voice`, `all`, or `none`.",
+				)
+				.await
+			}
+		},
+		"away-only" => match parse_toggle(&value) {
+			Some(toggle) => settings.only_when_away = toggle,
+			None => return toggle_error(ctx, message).await,
+		},
+		"quiet-mode" => match &*value {
+			"queue" => settings.drop_in_quiet_hours = false,
+			"drop" => settings.drop_in_quiet_hours = true,
+			_ => {
+				return error(
+					ctx,
+					message,
+					"Quiet mode can only be `queue` or `drop`!",
+				)
+				.await
+			}
+		},
+		"cooldown" => {
+			match parse_limit(&value) {
+				Some(minutes) => settings.cooldown_minutes = minutes,
+				None => return error(
+					ctx,
+					message,
+					"The cooldown can only be a number of minutes, `off`, or \
+					`default`!",
+				)
+				.await,
+			}
+		}
+		"hourly-limit" => {
+			match parse_limit(&value) {
+				Some(limit) => settings.hourly_limit = limit,
+				None => return error(
+					ctx,
+					message,
+					"The hourly limit can only be a number of notifications, \
+					`off`, or `default`!",
+				)
+				.await,
+			}
+		}
+		"exclude" => {
+			match parse_flags(&value) {
+				Some(exclusions) => settings.exclusions = exclusions,
+				None => return error(
+					ctx,
+					message,
+					"I don't know what to exclude! You can exclude `code`, \
+					`code-blocks`, `quotes`, `spoilers`, `links`, `all`, \
+					or `none`.",
+				)
+				.await,
+			}
+		}
+		_ => {
+			return error(
+				ctx,
+				message,
+				"I don't know that setting! Use `settings` to see them all.",
+			)
+			.await
+		}
+	}
+
+	settings.set().await?;
+
+	success(ctx, message).await
+}
+
+/// List your current settings.
+///
+/// Usage: `@Highlights settings`
+pub async fn list_settings(
+	ctx: &Context,
+	message: &Message,
+	args: &str,
+) -> Result<()> {
+	let _timer = Timer::command("settings");
+
+	require_empty_args!(args, ctx, message);
+
+	let settings = UserSettings::user_settings(message.author.id).await?;
+
+	let response = format!(
+		"{}'s settings:\n  \
+		– language: {} (available: {})\n  \
+		– strip-accents: {}\n  \
+		– confusables: {}\n  \
+		– exclude: {}\n  \
+		– context: {}\n  \
+		– delivery: {}\n  \
+		– timezone: {}\n  \
+		– quiet-mode: {}\n  \
+		– activity: {}\n  \
+		– away-only: {}\n  \
+		– cooldown: {}\n  \
+		– hourly-limit: {}",
+		message.author.name,
+		settings.stem_language.as_deref().unwrap_or("english"),
+		language_names(),
+		display_toggle(settings.strip_accents),
+		display_toggle(settings.match_confusables),
+		display_flags(settings.exclusions),
+		display_context_messages(settings.context_messages),
+		display_delivery(settings.delivery),
+		settings.timezone.as_deref().unwrap_or("UTC"),
+		if settings.drop_in_quiet_hours {
+			"drop"
+		} else {
+			"queue"
+		},
+		display_flags(settings.activity_signals),
+		display_away_only(settings.only_when_away),
+		display_cooldown(settings.cooldown_minutes),
+		display_hourly_limit(settings.hourly_limit),
+	);
+
+	let response = message
+		.channel_id
+		.send_message(ctx, |m| {
+			m.content(response).allowed_mentions(|m| m.empty_parse())
+		})
+		.await?;
+
+	insert_command_response(ctx, message.id, response.id).await;
+
+	Ok(())
+}
+
+/// Lists the names of the languages stemmed keywords can be matched in.
+fn language_names() -> String {
+	STEM_LANGUAGES
+		.iter()
+		.map(|(name, _)| *name)
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Parses the value of a setting that can be turned on or off.
+fn parse_toggle(value: &str) -> Option<bool> {
+	match value {
+		"on" | "yes" | "true" => Some(true),
+		"off" | "no" | "false" => Some(false),
+		_ => None,
+	}
+}
+
+/// Formats the value of a setting that can be turned on or off.
+fn display_toggle(toggle: bool) -> &'static str {
+	if toggle {
+		"on"
+	} else {
+		"off"
+	}
+}
+
+/// Parses the number of messages to show as context in notifications.
+///
+/// `off` is the same as 0.
+fn parse_context_messages(value: &str) -> Option<u8> {
+	match value {
+		"off" | "no" | "false" => Some(0),
+		_ => value
+			.parse()
+			.ok()
+			.filter(|&count| count <= NOTIFICATION_CONTEXT_MAX_MESSAGES),
+	}
+}
+
+/// Formats the number of messages to show as context in notifications.
+fn display_context_messages(count: u8) -> String {
+	match count {
+		0 => "off".to_owned(),
+		1 => "1 message".to_owned(),
+		_ => format!("{} messages", count),
+	}
+}
+
+/// Parses when notifications are sent: `immediate`, `every <minutes>`, or `daily <HH:MM>`.
+fn parse_delivery(value: &str) -> Option<Delivery> {
+	if value == "immediate" || value == "immediately" {
+		return Some(Delivery::Immediate);
+	}
+
+	if let Some(captures) = BATCHED_DELIVERY_REGEX.captures(value) {
+		let count = captures[1].parse::<u16>().ok()?;
+
+		let minutes = match captures.get(2).map(|unit| unit.as_str()) {
+			Some(unit) if unit.starts_with('h') => count.checked_mul(60)?,
+			_ => count,
+		};
+
+		return Some(Delivery::Batched { minutes }).filter(|_| {
+			(DIGEST_MIN_INTERVAL..=DIGEST_MAX_INTERVAL).contains(&minutes)
+		});
+	}
+
+	let time = value.strip_prefix("daily ")?.trim();
+
+	Some(Delivery::Daily {
+		minute: parse_time_of_day(time)?,
+	})
+}
+
+/// Formats when notifications are sent.
+fn display_delivery(delivery: Delivery) -> String {
+	match delivery {
+		Delivery::Immediate => "immediate".to_owned(),
+		Delivery::Batched { minutes: 1 } => "every minute".to_owned(),
+		Delivery::Batched { minutes } => format!("every {} minutes", minutes),
+		Delivery::Daily { minute } => {
+			format!("daily at {}", display_time_of_day(minute))
+		}
+	}
+}
+
+/// Formats whether notifications are only sent while away, noting when that can't be checked.
+fn display_away_only(only_when_away: bool) -> &'static str {
+	match (only_when_away, settings().bot.presences) {
+		(true, true) => "on",
+		(true, false) => "on (unavailable, this bot can't see who's online)",
+		(false, _) => "off",
+	}
+}
+
+/// Parses the value of a limit on notifications; a number, `off` (the same as 0), or `default`
+/// to use the hoster's default.
+fn parse_limit(value: &str) -> Option<Option<u16>> {
+	match value {
+		"default" => Some(None),
+		"off" | "no" | "false" => Some(Some(0)),
+		_ => value.parse().ok().map(Some),
+	}
+}
+
+/// Formats the minimum number of minutes between notifications about the same channel.
+fn display_cooldown(minutes: Option<u16>) -> String {
+	match minutes {
+		None => {
+			let default = settings().behavior.cooldown.as_secs();
+			let default = match default {
+				0 => "off".to_owned(),
+				_ if default % 60 == 0 => {
+					format!("{} minutes", default / 60)
+				}
+				_ => format!("{} seconds", default),
+			};
+			format!("default ({})", default)
+		}
+		Some(0) => "off".to_owned(),
+		Some(1) => "1 minute".to_owned(),
+		Some(minutes) => format!("{} minutes", minutes),
+	}
+}
+
+/// Formats the maximum number of notifications sent each hour.
+fn display_hourly_limit(limit: Option<u16>) -> String {
+	match limit {
+		None => match settings().behavior.max_notifications_per_hour {
+			0 => "default (off)".to_owned(),
+			default => format!("default ({} notifications)", default),
+		},
+		Some(0) => "off".to_owned(),
+		Some(1) => "1 notification".to_owned(),
+		Some(limit) => format!("{} notifications", limit),
+	}
+}
+
+/// Responds to an invalid value for a setting that can be turned on or off.
+async fn toggle_error(ctx: &Context, message: &Message) -> Result<()> {
+	error(
+		ctx,
+		message,
+		"That setting can only be turned `on` or `off`!",
+	)
+	.await
+}
@@ -4,6 +4,7 @@
 //! Miscellaneous utility functions and macros used by commands.
 
 use anyhow::{Context as _, Result};
+use chrono::{NaiveTime, Timelike};
 use serenity::{
 	client::Context,
 	model::{
@@ -13,7 +14,7 @@ use serenity::{
 	},
 };
 
-use crate::regex;
+use crate::{db::FlagSet, regex};
 use std::{collections::HashMap, iter::FromIterator};
 
 /// Requires the given message to have come from a guild channel.
@@ -218,6 +219,53 @@ pub fn get_ids_from_args(args: &str) -> Vec<Result<(ChannelId, &str), &str>> {
 		.collect()
 }
 
+/// Parses a time of day like `22:00` into the minute of the day.
+pub fn parse_time_of_day(time: &str) -> Option<u16> {
+	let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+
+	Some((time.hour() * 60 + time.minute()) as u16)
+}
+
+/// Formats a minute of the day as a time of day like `22:00`.
+pub fn display_time_of_day(minute: u16) -> String {
+	format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Parses comma-separated names of flags, like `code,quotes` for [`Exclusions`] or
+/// `typing,voice` for [`ActivitySignals`].
+///
+/// `all` and `none` can also be used. Returns `None` if any of the names isn't recognized.
+///
+/// [`Exclusions`]: crate::db::Exclusions
+/// [`ActivitySignals`]: crate::db::ActivitySignals
+pub fn parse_flags<T: FlagSet>(names: &str) -> Option<T> {
+	names
+		.split(',')
+		.map(|name| match name.trim().to_lowercase().as_str() {
+			"all" => Some(T::all()),
+			"none" => Some(T::none()),
+			name => T::NAMED
+				.iter()
+				.find(|(other, _)| *other == name)
+				.map(|&(_, flag)| flag),
+		})
+		.try_fold(T::none(), |flags, flag| Some(flags.union(flag?)))
+}
+
+/// Formats a set of flags as comma-separated names, or `none`.
+pub fn display_flags<T: FlagSet>(flags: T) -> String {
+	if flags.is_empty() {
+		"none".to_owned()
+	} else {
+		T::NAMED
+			.iter()
+			.filter(|&&(_, flag)| flags.contains(flag))
+			.map(|&(name, _)| name)
+			.collect::<Vec<_>>()
+			.join(", ")
+	}
+}
+
 /// Gets channels from the provided map by whitespace-separated arguments in the provided string.
 fn get_channels_from_args<'args, 'c>(
 	channels: &'c HashMap<ChannelId, GuildChannel>,
@@ -263,8 +311,7 @@ fn get_channel_from_arg<'arg, 'c>(
 	}
 
 	let mut iter = channels
-		.iter()
-		.map(|(_, channel)| channel)
+		.values()
 		.filter(|channel| channel.name.as_str().eq_ignore_ascii_case(arg));
 
 	if let Some(first) = iter.next() {
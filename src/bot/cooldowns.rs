@@ -0,0 +1,340 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Limiting how often users are notified, with a cooldown for each channel and a limit on
+//! notifications per hour.
+//!
+//! Notifications held back by either limit are counted, and the counts are shown in the user's
+//! next notification. This state is only kept in memory, so it's reset when the bot restarts, and
+//! users whose limits have all run out are occasionally forgotten.
+
+use once_cell::sync::Lazy;
+use serenity::model::id::{ChannelId, UserId};
+
+use std::{
+	collections::{HashMap, VecDeque},
+	mem,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use crate::{db::UserSettings, settings::settings};
+
+/// Cooldown state of every user that's been notified.
+static COOLDOWNS: Lazy<Mutex<HashMap<UserId, UserCooldowns>>> =
+	Lazy::new(Default::default);
+
+/// Number of notifications checked against cooldowns, used to occasionally forget users whose
+/// limits have run out.
+static CHECKED: AtomicUsize = AtomicUsize::new(0);
+
+/// How many notifications are checked between removals of users whose limits have run out.
+const PRUNE_INTERVAL: usize = 1000;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// How often a user can be notified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	/// Minimum duration between notifications about the same channel.
+	pub cooldown: Duration,
+	/// Maximum number of notifications each hour, or 0 for no limit.
+	pub hourly_limit: u32,
+}
+
+impl Limits {
+	/// Gets the limits of the user with the given settings, using the hoster's defaults for the
+	/// limits they haven't chosen.
+	pub fn of(user_settings: &UserSettings) -> Self {
+		let behavior = &settings().behavior;
+
+		Self {
+			cooldown: user_settings
+				.cooldown_minutes
+				.map_or(behavior.cooldown, |minutes| {
+					Duration::from_secs(u64::from(minutes) * 60)
+				}),
+			hourly_limit: user_settings
+				.hourly_limit
+				.map_or(behavior.max_notifications_per_hour, u32::from),
+		}
+	}
+}
+
+/// A notification allowed by a user's cooldowns, which has been recorded as sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allowed {
+	/// Numbers of notifications held back in each channel since the user was last notified.
+	pub suppressed: Vec<(ChannelId, u32)>,
+	channel_id: ChannelId,
+	at: Instant,
+}
+
+/// When a user was notified recently, and how many notifications they've missed since.
+#[derive(Debug, Default)]
+struct UserCooldowns {
+	/// When the user was last notified about each channel.
+	last_by_channel: HashMap<ChannelId, Instant>,
+	/// When the user was notified in the last hour, oldest first.
+	sent: VecDeque<Instant>,
+	/// Numbers of notifications held back in each channel since the user was last notified, in
+	/// the order the channels were first held back.
+	suppressed: Vec<(ChannelId, u32)>,
+	/// The user's cooldown when they were last checked, used to tell when their cooldowns have
+	/// run out without their settings.
+	cooldown: Duration,
+}
+
+impl UserCooldowns {
+	/// Checks if a notification about the given channel can be sent at `now`, recording it if so.
+	///
+	/// Returns the notifications held back since the last one if it can be sent. Otherwise, it's
+	/// counted as held back and `None` is returned.
+	fn try_notify(
+		&mut self,
+		channel_id: ChannelId,
+		limits: Limits,
+		now: Instant,
+	) -> Option<Allowed> {
+		while matches!(self.sent.front(), Some(&sent) if now - sent >= HOUR) {
+			self.sent.pop_front();
+		}
+
+		self.cooldown = limits.cooldown;
+
+		self.last_by_channel
+			.retain(|_, &mut last| now - last < limits.cooldown);
+
+		let cooling_down = self.last_by_channel.contains_key(&channel_id);
+
+		let over_limit = limits.hourly_limit != 0
+			&& self.sent.len() >= limits.hourly_limit as usize;
+
+		if cooling_down || over_limit {
+			match self.suppressed.iter_mut().find(|(id, _)| *id == channel_id) {
+				Some((_, count)) => *count += 1,
+				None => self.suppressed.push((channel_id, 1)),
+			}
+
+			return None;
+		}
+
+		self.last_by_channel.insert(channel_id, now);
+		self.sent.push_back(now);
+
+		Some(Allowed {
+			suppressed: mem::take(&mut self.suppressed),
+			channel_id,
+			at: now,
+		})
+	}
+
+	/// Undoes recording a notification that couldn't be sent, so it doesn't count towards the
+	/// limits and the notifications held back before it are shown in the next one.
+	fn restore(&mut self, allowed: Allowed) {
+		if let Some(i) = self.sent.iter().position(|&sent| sent == allowed.at) {
+			self.sent.remove(i);
+		}
+
+		if self.last_by_channel.get(&allowed.channel_id) == Some(&allowed.at) {
+			self.last_by_channel.remove(&allowed.channel_id);
+		}
+
+		let mut suppressed = allowed.suppressed;
+
+		for (channel_id, count) in mem::take(&mut self.suppressed) {
+			match suppressed.iter_mut().find(|(id, _)| *id == channel_id) {
+				Some((_, total)) => *total += count,
+				None => suppressed.push((channel_id, count)),
+			}
+		}
+
+		self.suppressed = suppressed;
+	}
+
+	/// Checks if none of this user's limits apply anymore at `now`, and there aren't any held
+	/// back notifications to show them, so forgetting them wouldn't change anything.
+	fn is_expired(&self, now: Instant) -> bool {
+		self.suppressed.is_empty()
+			&& self.sent.back().is_none_or(|&sent| now - sent >= HOUR)
+			&& self
+				.last_by_channel
+				.values()
+				.all(|&last| now - last >= self.cooldown)
+	}
+}
+
+/// Removes users whose limits have all run out at `now`.
+fn prune(cooldowns: &mut HashMap<UserId, UserCooldowns>, now: Instant) {
+	cooldowns.retain(|_, user_cooldowns| !user_cooldowns.is_expired(now));
+}
+
+/// Checks if the user with the given settings can be notified about a message in the given
+/// channel now, recording the notification if so.
+///
+/// Returns the numbers of notifications held back in each channel since the user was last
+/// notified if they can be, or `None` if this notification should be held back. If the
+/// notification can't be sent after all, it should be passed to [`restore`](restore).
+pub fn try_notify(
+	settings: &UserSettings,
+	channel_id: ChannelId,
+) -> Option<Allowed> {
+	let now = Instant::now();
+
+	let mut cooldowns = COOLDOWNS.lock().unwrap();
+
+	if CHECKED.fetch_add(1, Ordering::Relaxed) % PRUNE_INTERVAL == 0 {
+		prune(&mut cooldowns, now);
+	}
+
+	cooldowns.entry(settings.user_id).or_default().try_notify(
+		channel_id,
+		Limits::of(settings),
+		now,
+	)
+}
+
+/// Undoes recording a notification to the user with the given ID that couldn't be sent.
+pub fn restore(user_id: UserId, allowed: Allowed) {
+	COOLDOWNS
+		.lock()
+		.unwrap()
+		.entry(user_id)
+		.or_default()
+		.restore(allowed);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn minutes(minutes: u64) -> Duration {
+		Duration::from_secs(minutes * 60)
+	}
+
+	#[test]
+	fn channel_cooldown() {
+		let limits = Limits {
+			cooldown: minutes(5),
+			hourly_limit: 0,
+		};
+
+		let start = Instant::now();
+		let mut cooldowns = UserCooldowns::default();
+
+		let notify = |cooldowns: &mut UserCooldowns, channel, after| {
+			cooldowns
+				.try_notify(ChannelId(channel), limits, start + after)
+				.map(|allowed| allowed.suppressed)
+		};
+
+		assert_eq!(notify(&mut cooldowns, 1, minutes(0)), Some(vec![]));
+		assert_eq!(notify(&mut cooldowns, 1, minutes(1)), None);
+		assert_eq!(notify(&mut cooldowns, 1, minutes(2)), None);
+		assert_eq!(
+			notify(&mut cooldowns, 2, minutes(2)),
+			Some(vec![(ChannelId(1), 2)])
+		);
+		assert_eq!(notify(&mut cooldowns, 2, minutes(3)), None);
+		assert_eq!(
+			notify(&mut cooldowns, 1, minutes(5)),
+			Some(vec![(ChannelId(2), 1)])
+		);
+		assert_eq!(notify(&mut cooldowns, 2, minutes(7)), Some(vec![]));
+	}
+
+	#[test]
+	fn hourly_limit() {
+		let limits = Limits {
+			cooldown: Duration::from_secs(0),
+			hourly_limit: 2,
+		};
+
+		let start = Instant::now();
+		let mut cooldowns = UserCooldowns::default();
+
+		let notify = |cooldowns: &mut UserCooldowns, channel, after| {
+			cooldowns
+				.try_notify(ChannelId(channel), limits, start + after)
+				.map(|allowed| allowed.suppressed)
+		};
+
+		assert!(notify(&mut cooldowns, 1, minutes(0)).is_some());
+		assert!(notify(&mut cooldowns, 2, minutes(10)).is_some());
+		assert_eq!(notify(&mut cooldowns, 3, minutes(20)), None);
+		assert_eq!(notify(&mut cooldowns, 3, minutes(30)), None);
+		assert_eq!(notify(&mut cooldowns, 1, minutes(40)), None);
+		assert_eq!(
+			notify(&mut cooldowns, 2, minutes(60)),
+			Some(vec![(ChannelId(3), 2), (ChannelId(1), 1)])
+		);
+	}
+
+	#[test]
+	fn restore_failed_notification() {
+		let limits = Limits {
+			cooldown: minutes(5),
+			hourly_limit: 1,
+		};
+
+		let start = Instant::now();
+		let mut cooldowns = UserCooldowns::default();
+
+		cooldowns.try_notify(ChannelId(1), limits, start).unwrap();
+		assert_eq!(cooldowns.try_notify(ChannelId(2), limits, start), None);
+
+		let allowed = cooldowns
+			.try_notify(ChannelId(1), limits, start + HOUR)
+			.unwrap();
+		assert_eq!(allowed.suppressed, [(ChannelId(2), 1)]);
+
+		assert_eq!(
+			cooldowns.try_notify(ChannelId(3), limits, start + HOUR),
+			None
+		);
+
+		cooldowns.restore(allowed);
+
+		let allowed = cooldowns
+			.try_notify(ChannelId(1), limits, start + HOUR)
+			.unwrap();
+		assert_eq!(allowed.suppressed, [(ChannelId(2), 1), (ChannelId(3), 1)]);
+	}
+
+	#[test]
+	fn prune_expired_users() {
+		let limits = Limits {
+			cooldown: minutes(90),
+			hourly_limit: 1,
+		};
+
+		let start = Instant::now();
+		let mut cooldowns = HashMap::new();
+
+		let mut notify = |user, channel, after| {
+			cooldowns
+				.entry(UserId(user))
+				.or_insert_with(UserCooldowns::default)
+				.try_notify(ChannelId(channel), limits, start + after)
+		};
+
+		assert!(notify(1, 1, minutes(0)).is_some());
+		assert!(notify(2, 1, minutes(0)).is_some());
+		assert!(notify(2, 2, minutes(10)).is_none());
+		assert!(notify(3, 1, minutes(60)).is_some());
+
+		prune(&mut cooldowns, start + minutes(90));
+
+		assert!(!cooldowns.contains_key(&UserId(1)));
+		assert!(cooldowns.contains_key(&UserId(2)));
+		assert!(cooldowns.contains_key(&UserId(3)));
+
+		prune(&mut cooldowns, start + minutes(150));
+
+		assert!(cooldowns.contains_key(&UserId(2)));
+		assert!(!cooldowns.contains_key(&UserId(3)));
+	}
+}
@@ -0,0 +1,525 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Sending digests; notifications collected and sent together to users who chose not to be
+//! notified immediately.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serenity::{
+	builder::CreateMessage,
+	client::Context,
+	http::{error::ErrorResponse, HttpError},
+	model::id::{ChannelId, GuildId, MessageId, UserId},
+	Error as SerenityError,
+};
+use tokio::{task, time::interval};
+
+use std::{
+	collections::HashSet,
+	iter, mem,
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+	bot::{
+		highlighting::{describe_keyword, in_quiet_hours, send_dm, truncate},
+		util::{escape_markdown, user_timezone},
+	},
+	db::{Delivery, PendingNotification, UserSettings},
+	global::{
+		DIGEST_CHECK_INTERVAL, DIGEST_EMBED_MAX_LEN, EMBED_COLOR,
+		EMBED_FIELD_MAX_LEN, EMBED_MAX_FIELDS,
+	},
+	monitoring::Timer,
+};
+
+/// Maximum length of the name of an embed field, in characters.
+const EMBED_FIELD_NAME_MAX_LEN: usize = 256;
+
+/// Whether the digest cycle has been started, so it's only started once if the bot reconnects.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Starts checking for digests that are due to be sent every
+/// [`DIGEST_CHECK_INTERVAL`](DIGEST_CHECK_INTERVAL).
+pub fn start_digest_cycle(ctx: Context) {
+	if STARTED.swap(true, Ordering::SeqCst) {
+		return;
+	}
+
+	task::spawn(async move {
+		let mut interval = interval(DIGEST_CHECK_INTERVAL);
+
+		loop {
+			interval.tick().await;
+
+			send_due_digests(&ctx).await;
+		}
+	});
+}
+
+/// Sends a digest to every user with pending notifications whose digest is due, unless it's
+/// during their quiet hours.
+async fn send_due_digests(ctx: &Context) {
+	let users = match PendingNotification::pending_users().await {
+		Ok(users) => users,
+		Err(e) => {
+			log::error!(
+				"Failed to fetch users with pending notifications: {:?}",
+				e
+			);
+			return;
+		}
+	};
+
+	let now = Utc::now();
+
+	for (user_id, oldest) in users {
+		let settings = UserSettings::indexed_user_settings(user_id);
+
+		if !digest_due(settings.delivery, user_timezone(&settings), oldest, now)
+		{
+			continue;
+		}
+
+		match in_quiet_hours(&settings).await {
+			Ok(false) => {}
+			Ok(true) => continue,
+			Err(e) => {
+				log::error!(
+					"Failed to check quiet hours of {}: {:?}",
+					user_id,
+					e
+				);
+				continue;
+			}
+		}
+
+		let _timer = Timer::notification("digest");
+
+		if let Err(e) = send_digest(ctx, user_id).await {
+			log::error!("Failed to send digest to {}: {:?}", user_id, e);
+		}
+	}
+}
+
+/// Checks if a user's digest should be sent, given their delivery mode and time zone and when
+/// their oldest pending notification was created.
+///
+/// Pending notifications of users who have notifications delivered immediately were held back
+/// during their quiet hours, or before they chose to be notified immediately, so they're sent right
+/// away.
+fn digest_due(
+	delivery: Delivery,
+	timezone: Tz,
+	oldest: DateTime<Utc>,
+	now: DateTime<Utc>,
+) -> bool {
+	match delivery {
+		Delivery::Immediate => true,
+		Delivery::Batched { minutes } => {
+			now - oldest >= Duration::minutes(minutes.into())
+		}
+		Delivery::Daily { minute } => {
+			last_daily_time(minute, timezone, now).is_some_and(|t| oldest < t)
+		}
+	}
+}
+
+/// Finds the last time at or before `now` that was the given minute of the day in a time zone.
+///
+/// If that minute was skipped on a day because of daylight saving time, the time an hour later is
+/// used for that day.
+fn last_daily_time(
+	minute: u16,
+	timezone: Tz,
+	now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+	let time =
+		NaiveTime::from_hms_opt((minute / 60).into(), (minute % 60).into(), 0)?;
+
+	let today = now.with_timezone(&timezone).date().naive_local();
+
+	iter::successors(Some(today), |day| day.pred_opt())
+		.take(3)
+		.filter_map(|day| {
+			let scheduled = day.and_time(time);
+
+			timezone
+				.from_local_datetime(&scheduled)
+				.earliest()
+				.or_else(|| {
+					timezone
+						.from_local_datetime(&(scheduled + Duration::hours(1)))
+						.earliest()
+				})
+		})
+		.map(|time| time.with_timezone(&Utc))
+		.find(|&time| time <= now)
+}
+
+/// Sends all of a user's pending notifications in a digest, and removes them from the DB.
+///
+/// The digest is split into several messages if it doesn't fit in one embed, and the notifications
+/// on each page are removed once it's sent. If sending a page fails in a way that won't be fixed by
+/// trying again, like the user not accepting DMs, the rest of the notifications are dropped;
+/// otherwise they're kept to be sent in the next check.
+async fn send_digest(ctx: &Context, user_id: UserId) -> Result<()> {
+	let notifications =
+		PendingNotification::user_notifications(user_id).await?;
+
+	if notifications.is_empty() {
+		return Ok(());
+	}
+
+	let mut channels =
+		Vec::<(GuildId, ChannelId, Vec<(MessageId, String)>)>::new();
+
+	let mut messages = notifications.iter().peekable();
+
+	while let Some(first) = messages.next() {
+		let mut keywords =
+			vec![describe_keyword(&first.keyword, first.match_kind)];
+
+		while let Some(notification) =
+			messages.next_if(|n| n.message_id == first.message_id)
+		{
+			keywords.push(describe_keyword(
+				&notification.keyword,
+				notification.match_kind,
+			));
+		}
+
+		let line = (first.message_id, digest_line(first, &keywords));
+
+		match channels
+			.iter_mut()
+			.find(|(_, channel_id, _)| *channel_id == first.channel_id)
+		{
+			Some((_, _, lines)) => lines.push(line),
+			None => {
+				channels.push((first.guild_id, first.channel_id, vec![line]))
+			}
+		}
+	}
+
+	let mut named_channels = vec![];
+
+	for (guild_id, channel_id, lines) in channels {
+		let channel_name = ctx
+			.cache
+			.guild_channel_field(channel_id, |c| c.name.clone())
+			.await
+			.unwrap_or_else(|| channel_id.to_string());
+
+		let guild_name = ctx
+			.cache
+			.guild_field(guild_id, |g| g.name.clone())
+			.await
+			.unwrap_or_else(|| guild_id.to_string());
+
+		named_channels
+			.push((format!("#{} ({})", channel_name, guild_name), lines));
+	}
+
+	let pages = paginate(digest_fields(named_channels));
+
+	let page_count = pages.len();
+
+	let mut unsent = notifications;
+
+	for (i, fields) in pages.into_iter().enumerate() {
+		let message_ids = fields
+			.iter()
+			.flat_map(|field| field.message_ids.iter().copied())
+			.collect::<HashSet<_>>();
+
+		let title = if page_count == 1 {
+			"Keyword digest".to_owned()
+		} else {
+			format!("Keyword digest ({}/{})", i + 1, page_count)
+		};
+
+		let mut message = CreateMessage::default();
+
+		message.embed(|e| {
+			e.title(title)
+				.fields(
+					fields
+						.into_iter()
+						.map(|field| (field.name, field.value, false)),
+				)
+				.timestamp(&Utc::now())
+				.color(EMBED_COLOR)
+		});
+
+		match send_dm(ctx, user_id, message).await {
+			Ok(Some(_)) => {}
+			Ok(None) => break,
+			Err(e) if is_permanent(&e) => {
+				log::error!(
+					"Giving up on digest for {} after {} of {} pages: {:?}",
+					user_id,
+					i,
+					page_count,
+					e
+				);
+				break;
+			}
+			Err(e) => return Err(e),
+		}
+
+		let (sent, rest) = unsent
+			.into_iter()
+			.partition(|n| message_ids.contains(&n.message_id));
+
+		unsent = rest;
+
+		PendingNotification::delete_sent(sent).await?;
+	}
+
+	// anything left can't be sent to the user, so it's dropped
+	PendingNotification::delete_sent(unsent).await
+}
+
+/// Checks if an error sending a message won't go away by trying again, like the user not accepting
+/// DMs from the bot.
+fn is_permanent(error: &anyhow::Error) -> bool {
+	match error.downcast_ref::<SerenityError>() {
+		Some(SerenityError::Http(err)) => matches!(
+			&**err,
+			HttpError::UnsuccessfulRequest(ErrorResponse { status_code, .. })
+				if status_code.is_client_error() && status_code.as_u16() != 429
+		),
+		_ => false,
+	}
+}
+
+/// Formats the line of a digest about one message, given the descriptions of the keywords it
+/// matched.
+fn digest_line(
+	notification: &PendingNotification,
+	keywords: &[String],
+) -> String {
+	let content = if notification.content.is_empty() {
+		"*(no text)*".to_owned()
+	} else {
+		escape_markdown(&notification.content)
+	};
+
+	let line = format!(
+		"**{}**: {} ([Link]({}))\n↳ {}",
+		escape_markdown(&notification.author_name),
+		content,
+		message_link(
+			notification.guild_id,
+			notification.channel_id,
+			notification.message_id
+		),
+		escape_markdown(&keywords.join(", ")),
+	);
+
+	truncate(&line, EMBED_FIELD_MAX_LEN)
+}
+
+/// Creates a link to jump to a message.
+fn message_link(
+	guild_id: GuildId,
+	channel_id: ChannelId,
+	message_id: MessageId,
+) -> String {
+	format!(
+		"https://discord.com/channels/{}/{}/{}",
+		guild_id, channel_id, message_id
+	)
+}
+
+/// An embed field of a digest, with the IDs of the messages it's about.
+#[derive(Debug, Clone)]
+struct DigestField {
+	name: String,
+	value: String,
+	message_ids: Vec<MessageId>,
+}
+
+/// Builds the embed fields of a digest from the names of channels and the lines about the
+/// messages in them, along with the ID of the message each line is about.
+///
+/// The lines of a channel are split into several fields if they don't fit in one.
+fn digest_fields(
+	channels: Vec<(String, Vec<(MessageId, String)>)>,
+) -> Vec<DigestField> {
+	let mut fields = vec![];
+
+	for (name, lines) in channels {
+		let name =
+			truncate(&name, EMBED_FIELD_NAME_MAX_LEN - " (continued)".len());
+
+		let mut value = String::new();
+		let mut message_ids = vec![];
+		let mut continued = false;
+
+		for (message_id, line) in lines {
+			if !value.is_empty()
+				&& value.chars().count() + line.chars().count() + 1
+					> EMBED_FIELD_MAX_LEN
+			{
+				fields.push(DigestField {
+					name: field_name(&name, continued),
+					value: mem::take(&mut value),
+					message_ids: mem::take(&mut message_ids),
+				});
+				continued = true;
+			}
+
+			if !value.is_empty() {
+				value.push('\n');
+			}
+
+			value.push_str(&line);
+			message_ids.push(message_id);
+		}
+
+		fields.push(DigestField {
+			name: field_name(&name, continued),
+			value,
+			message_ids,
+		});
+	}
+
+	fields
+}
+
+/// Names a field of a digest, marking fields that continue the last field's channel.
+fn field_name(name: &str, continued: bool) -> String {
+	if continued {
+		format!("{} (continued)", name)
+	} else {
+		name.to_owned()
+	}
+}
+
+/// Splits the fields of a digest into pages that each fit in one embed.
+fn paginate(fields: Vec<DigestField>) -> Vec<Vec<DigestField>> {
+	let mut pages = vec![];
+	let mut page = vec![];
+	let mut page_len = 0;
+
+	for field in fields {
+		let len = field.name.chars().count() + field.value.chars().count();
+
+		if !page.is_empty()
+			&& (page.len() == EMBED_MAX_FIELDS
+				|| page_len + len > DIGEST_EMBED_MAX_LEN)
+		{
+			pages.push(mem::take(&mut page));
+			page_len = 0;
+		}
+
+		page.push(field);
+		page_len += len;
+	}
+
+	if !page.is_empty() {
+		pages.push(page);
+	}
+
+	pages
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn utc(hour: u32, minute: u32) -> DateTime<Utc> {
+		Utc.ymd(2021, 6, 15).and_hms(hour, minute, 0)
+	}
+
+	#[test]
+	fn batched_digests() {
+		let delivery = Delivery::Batched { minutes: 30 };
+
+		assert!(!digest_due(delivery, Tz::UTC, utc(12, 0), utc(12, 29)));
+		assert!(digest_due(delivery, Tz::UTC, utc(12, 0), utc(12, 30)));
+		assert!(digest_due(
+			Delivery::Immediate,
+			Tz::UTC,
+			utc(12, 0),
+			utc(12, 0)
+		));
+	}
+
+	#[test]
+	fn daily_digests() {
+		let delivery = Delivery::Daily { minute: 9 * 60 };
+
+		assert!(!digest_due(delivery, Tz::UTC, utc(9, 30), utc(23, 0)));
+		assert!(digest_due(delivery, Tz::UTC, utc(8, 30), utc(9, 0)));
+
+		let yesterday = Utc.ymd(2021, 6, 14).and_hms(20, 0, 0);
+		assert!(digest_due(delivery, Tz::UTC, yesterday, utc(9, 1)));
+		assert!(!digest_due(delivery, Tz::UTC, yesterday, utc(8, 59)));
+
+		// 09:00 in New York is 13:00 UTC during daylight saving time
+		let new_york = Tz::America__New_York;
+		assert!(!digest_due(delivery, new_york, utc(10, 0), utc(12, 59)));
+		assert!(digest_due(delivery, new_york, utc(10, 0), utc(13, 0)));
+	}
+
+	#[test]
+	fn daily_digest_in_skipped_time() {
+		// 02:30 didn't happen in New York on March 14, 2021
+		let now = Utc.ymd(2021, 3, 14).and_hms(12, 0, 0);
+
+		assert_eq!(
+			last_daily_time(2 * 60 + 30, Tz::America__New_York, now),
+			Some(Utc.ymd(2021, 3, 14).and_hms(7, 30, 0))
+		);
+	}
+
+	fn field(name: &str, value: String) -> DigestField {
+		DigestField {
+			name: name.to_owned(),
+			value,
+			message_ids: vec![],
+		}
+	}
+
+	#[test]
+	fn fields_and_pages() {
+		let line = "x".repeat(400);
+
+		let fields = digest_fields(vec![
+			(
+				"#a".to_owned(),
+				vec![
+					(MessageId(1), line.clone()),
+					(MessageId(2), line.clone()),
+					(MessageId(3), line.clone()),
+				],
+			),
+			("#b".to_owned(), vec![(MessageId(4), "short".to_owned())]),
+		]);
+
+		let names = fields
+			.iter()
+			.map(|field| field.name.as_str())
+			.collect::<Vec<_>>();
+
+		assert_eq!(names, ["#a", "#a (continued)", "#b"]);
+		assert_eq!(fields[0].value, format!("{}\n{}", line, line));
+		assert_eq!(fields[0].message_ids, [MessageId(1), MessageId(2)]);
+		assert_eq!(fields[1].message_ids, [MessageId(3)]);
+
+		let many = iter::repeat_n(field("#c", "y".to_owned()), 30).collect();
+		let pages = paginate(many);
+		assert_eq!(
+			pages.iter().map(Vec::len).collect::<Vec<_>>(),
+			[EMBED_MAX_FIELDS, 5]
+		);
+
+		let long = iter::repeat_n(field("#d", "z".repeat(1000)), 6).collect();
+		assert_eq!(paginate(long).len(), 2);
+	}
+}
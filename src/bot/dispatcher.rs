@@ -0,0 +1,224 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Sending, editing, deleting, and reacting to notification messages with limited concurrency.
+//!
+//! At most [`NOTIFICATION_CONCURRENCY`](NOTIFICATION_CONCURRENCY) requests are made at once. A
+//! request to a route that's out of requests for its rate limit waits for the rate limit to reset
+//! before taking a place, so it doesn't hold up requests to other routes. The number of requests
+//! waiting is recorded as the `dispatch_queue` metric.
+//!
+//! The ID of the DM channel with each user is cached, so it only needs to be created once.
+
+use once_cell::sync::Lazy;
+use serenity::{
+	builder::{CreateMessage, EditMessage},
+	client::Context,
+	http::{error::ErrorResponse, routing::Route, HttpError, LightMethod},
+	model::{
+		channel::{Message, ReactionType},
+		id::{ChannelId, MessageId, UserId},
+	},
+	Error as SerenityError, Result,
+};
+use tokio::{sync::Semaphore, time::sleep};
+
+use std::{
+	collections::HashMap,
+	future::Future,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Mutex,
+	},
+	time::SystemTime,
+};
+
+use crate::{global::NOTIFICATION_CONCURRENCY, monitoring::set_dispatch_queue};
+
+/// Places for requests that are being made.
+static PERMITS: Lazy<Semaphore> =
+	Lazy::new(|| Semaphore::new(NOTIFICATION_CONCURRENCY));
+
+/// Number of requests waiting for a place or a rate limit.
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+/// ID of the DM channel with each user that's been sent a DM.
+static DM_CHANNELS: Lazy<Mutex<HashMap<UserId, ChannelId>>> =
+	Lazy::new(Default::default);
+
+/// Marker of a request waiting to be made, counted in the queue depth while it exists.
+struct Queued;
+
+impl Queued {
+	fn new() -> Self {
+		let depth = QUEUED.fetch_add(1, Ordering::Relaxed) + 1;
+		set_dispatch_queue(depth);
+		Queued
+	}
+}
+
+impl Drop for Queued {
+	fn drop(&mut self) {
+		let depth = QUEUED.fetch_sub(1, Ordering::Relaxed) - 1;
+		set_dispatch_queue(depth);
+	}
+}
+
+/// Sends a message to the user with the given ID in a DM channel.
+pub async fn send(
+	ctx: &Context,
+	user_id: UserId,
+	message: CreateMessage<'static>,
+) -> Result<Message> {
+	let channel_id = dm_channel(ctx, user_id).await?;
+
+	let result = dispatch(
+		ctx,
+		Route::ChannelsIdMessages(channel_id.0),
+		channel_id.send_message(ctx, |m| {
+			*m = message;
+			m
+		}),
+	)
+	.await;
+
+	forget_unknown_channel(user_id, result)
+}
+
+/// Edits a message sent to the user with the given ID in a DM channel.
+pub async fn edit(
+	ctx: &Context,
+	user_id: UserId,
+	message_id: MessageId,
+	edit: EditMessage,
+) -> Result<Message> {
+	let channel_id = dm_channel(ctx, user_id).await?;
+
+	let result = dispatch(
+		ctx,
+		Route::ChannelsIdMessagesId(LightMethod::Patch, channel_id.0),
+		channel_id.edit_message(ctx, message_id, |m| {
+			*m = edit;
+			m
+		}),
+	)
+	.await;
+
+	forget_unknown_channel(user_id, result)
+}
+
+/// Deletes a message sent to the user with the given ID in a DM channel.
+pub async fn delete(
+	ctx: &Context,
+	user_id: UserId,
+	message_id: MessageId,
+) -> Result<()> {
+	let channel_id = dm_channel(ctx, user_id).await?;
+
+	let result = dispatch(
+		ctx,
+		Route::ChannelsIdMessagesId(LightMethod::Delete, message_id.0),
+		channel_id.delete_message(ctx, message_id),
+	)
+	.await;
+
+	forget_unknown_channel(user_id, result)
+}
+
+/// Reacts to a message sent to the user with the given ID in a DM channel.
+pub async fn react(
+	ctx: &Context,
+	user_id: UserId,
+	message_id: MessageId,
+	reaction: ReactionType,
+) -> Result<()> {
+	let channel_id = dm_channel(ctx, user_id).await?;
+
+	let result = dispatch(
+		ctx,
+		Route::ChannelsIdMessagesIdReactionsUserIdType(channel_id.0),
+		channel_id.create_reaction(ctx, message_id, reaction),
+	)
+	.await;
+
+	forget_unknown_channel(user_id, result)
+}
+
+/// Gets the ID of the DM channel with the user with the given ID, creating it if it isn't cached.
+async fn dm_channel(ctx: &Context, user_id: UserId) -> Result<ChannelId> {
+	if let Some(&channel_id) = DM_CHANNELS.lock().unwrap().get(&user_id) {
+		return Ok(channel_id);
+	}
+
+	let channel =
+		dispatch(ctx, Route::UsersMeChannels, user_id.create_dm_channel(ctx))
+			.await?;
+
+	DM_CHANNELS.lock().unwrap().insert(user_id, channel.id);
+
+	Ok(channel.id)
+}
+
+/// Removes the cached DM channel of the user with the given ID if `result` shows it doesn't exist
+/// anymore, so it's created again next time.
+fn forget_unknown_channel<T>(user_id: UserId, result: Result<T>) -> Result<T> {
+	if let Err(SerenityError::Http(err)) = &result {
+		if let HttpError::UnsuccessfulRequest(ErrorResponse {
+			status_code,
+			..
+		}) = &**err
+		{
+			if status_code.as_u16() == 404 {
+				DM_CHANNELS.lock().unwrap().remove(&user_id);
+			}
+		}
+	}
+
+	result
+}
+
+/// Makes a request to the given route once there's a place for it and the route's rate limit
+/// allows it.
+async fn dispatch<T>(
+	ctx: &Context,
+	route: Route,
+	request: impl Future<Output = T>,
+) -> T {
+	let queued = Queued::new();
+
+	wait_for_rate_limit(ctx, route).await;
+
+	let _permit = PERMITS
+		.acquire()
+		.await
+		.expect("Notification dispatcher was closed");
+
+	drop(queued);
+
+	request.await
+}
+
+/// Waits until the rate limit of the given route resets, if it has no requests remaining.
+async fn wait_for_rate_limit(ctx: &Context, route: Route) {
+	let ratelimit = match ctx.http.ratelimiter.routes().read().await.get(&route)
+	{
+		Some(ratelimit) => Arc::clone(ratelimit),
+		None => return,
+	};
+
+	let delay = {
+		let ratelimit = ratelimit.lock().await;
+
+		if ratelimit.remaining() > 0 {
+			return;
+		}
+
+		ratelimit
+			.reset()
+			.and_then(|reset| reset.duration_since(SystemTime::now()).ok())
+	};
+
+	if let Some(delay) = delay {
+		sleep(delay).await;
+	}
+}
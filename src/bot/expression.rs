@@ -0,0 +1,408 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Parsing and evaluation of expression keywords, like `outage AND (api OR gateway) NOT test`.
+
+use std::{iter::Peekable, vec};
+
+use crate::global::{
+	EXPRESSION_KEYWORD_MAX_DEPTH, EXPRESSION_KEYWORD_MAX_TERMS,
+};
+
+/// A parsed expression keyword or ignored phrase.
+///
+/// Each term is a word or phrase matched like a literal keyword, and terms are combined with
+/// `AND`, `OR`, and `NOT`. `a NOT b` is short for `a AND NOT b`. Operators must be written in
+/// uppercase, and parentheses can be used for grouping. Consecutive words without an operator
+/// between them form a single phrase, and terms can be quoted to include operators or parentheses
+/// in them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+	Term(String),
+	Not(Box<Expression>),
+	And(Box<Expression>, Box<Expression>),
+	Or(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+	/// Parses an expression.
+	///
+	/// Returns a message explaining the problem if it isn't valid.
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let tokens = tokenize(text)?;
+
+		let mut parser = Parser {
+			tokens: tokens.into_iter().peekable(),
+			depth: 0,
+			terms: 0,
+		};
+
+		let expression = parser.or()?;
+
+		match parser.tokens.next() {
+			None => {}
+			Some(Token::RightParen) => {
+				return Err("That expression has an unmatched `)`!".into())
+			}
+			Some(_) => {
+				return Err(
+					"That expression has a term in the wrong place!".into()
+				)
+			}
+		}
+
+		Ok(expression)
+	}
+
+	/// Evaluates the expression, using `matches` to check if each term matches.
+	pub fn evaluate(&self, matches: &mut impl FnMut(&str) -> bool) -> bool {
+		match self {
+			Expression::Term(term) => matches(term),
+			Expression::Not(inner) => !inner.evaluate(matches),
+			Expression::And(left, right) => {
+				left.evaluate(matches) && right.evaluate(matches)
+			}
+			Expression::Or(left, right) => {
+				left.evaluate(matches) || right.evaluate(matches)
+			}
+		}
+	}
+
+	/// Iterates over all of the terms in the expression, in the order they're written.
+	pub fn terms(&self) -> impl Iterator<Item = &str> {
+		let mut terms = vec![];
+		self.collect_terms(false, &mut terms);
+		terms.into_iter().map(|(term, _)| term)
+	}
+
+	/// Finds the terms that match and contributed to the expression matching; that is, terms that
+	/// match and aren't negated.
+	pub fn fired_terms(
+		&self,
+		mut matches: impl FnMut(&str) -> bool,
+	) -> Vec<&str> {
+		let mut terms = vec![];
+		self.collect_terms(false, &mut terms);
+
+		let mut fired = vec![];
+
+		for (term, negated) in terms {
+			if !negated && !fired.contains(&term) && matches(term) {
+				fired.push(term);
+			}
+		}
+
+		fired
+	}
+
+	/// Collects the terms of this expression, and whether each one is negated.
+	fn collect_terms<'s>(
+		&'s self,
+		negated: bool,
+		terms: &mut Vec<(&'s str, bool)>,
+	) {
+		match self {
+			Expression::Term(term) => terms.push((term, negated)),
+			Expression::Not(inner) => inner.collect_terms(!negated, terms),
+			Expression::And(left, right) | Expression::Or(left, right) => {
+				left.collect_terms(negated, terms);
+				right.collect_terms(negated, terms);
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+	LeftParen,
+	RightParen,
+	And,
+	Or,
+	Not,
+	Word(String),
+	Quoted(String),
+}
+
+/// Splits an expression into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+	let mut tokens = vec![];
+	let mut chars = text.char_indices().peekable();
+
+	while let Some(&(start, c)) = chars.peek() {
+		match c {
+			c if c.is_whitespace() => {
+				chars.next();
+			}
+			'(' => {
+				chars.next();
+				tokens.push(Token::LeftParen);
+			}
+			')' => {
+				chars.next();
+				tokens.push(Token::RightParen);
+			}
+			'"' => {
+				chars.next();
+
+				let end = loop {
+					match chars.next() {
+						Some((i, '"')) => break i,
+						Some(_) => {}
+						None => {
+							return Err(
+								"That expression has an unmatched `\"`!".into(),
+							)
+						}
+					}
+				};
+
+				let phrase = text[start + 1..end].trim();
+
+				if phrase.is_empty() {
+					return Err(
+						"That expression has an empty quoted term!".into()
+					);
+				}
+
+				tokens.push(Token::Quoted(phrase.to_owned()));
+			}
+			_ => {
+				let mut end = text.len();
+
+				while let Some(&(i, c)) = chars.peek() {
+					if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+						end = i;
+						break;
+					}
+					chars.next();
+				}
+
+				let token = match &text[start..end] {
+					"AND" => Token::And,
+					"OR" => Token::Or,
+					"NOT" => Token::Not,
+					word => Token::Word(word.to_owned()),
+				};
+
+				tokens.push(token);
+			}
+		}
+	}
+
+	Ok(tokens)
+}
+
+/// Recursive descent parser for expressions.
+///
+/// `OR` has the lowest precedence, then `AND` (including the `AND` implied by `a NOT b`), then
+/// `NOT`.
+///
+/// The number of terms and the nesting depth are limited while parsing, so long or deeply nested
+/// expressions are rejected before they can overflow the stack.
+struct Parser {
+	tokens: Peekable<vec::IntoIter<Token>>,
+	/// How many parentheses and `NOT`s the parser is currently inside of.
+	depth: usize,
+	/// How many terms have been parsed so far.
+	terms: usize,
+}
+
+impl Parser {
+	/// Parses something nested inside parentheses or a `NOT`.
+	fn nested(
+		&mut self,
+		parse: impl FnOnce(&mut Self) -> Result<Expression, String>,
+	) -> Result<Expression, String> {
+		if self.depth >= EXPRESSION_KEYWORD_MAX_DEPTH {
+			return Err(format!(
+				"Expressions can't be nested more than {} levels deep!",
+				EXPRESSION_KEYWORD_MAX_DEPTH
+			));
+		}
+
+		self.depth += 1;
+		let expression = parse(self);
+		self.depth -= 1;
+
+		expression
+	}
+
+	/// Counts a parsed term.
+	fn add_term(&mut self, term: String) -> Result<Expression, String> {
+		self.terms += 1;
+
+		if self.terms > EXPRESSION_KEYWORD_MAX_TERMS {
+			return Err(format!(
+				"Expressions can't have more than {} terms!",
+				EXPRESSION_KEYWORD_MAX_TERMS
+			));
+		}
+
+		Ok(Expression::Term(term))
+	}
+
+	fn or(&mut self) -> Result<Expression, String> {
+		let mut expression = self.and()?;
+
+		while self.tokens.next_if_eq(&Token::Or).is_some() {
+			expression =
+				Expression::Or(Box::new(expression), Box::new(self.and()?));
+		}
+
+		Ok(expression)
+	}
+
+	fn and(&mut self) -> Result<Expression, String> {
+		let mut expression = self.not()?;
+
+		loop {
+			let right = match self.tokens.peek() {
+				Some(Token::And) => {
+					self.tokens.next();
+					self.not()?
+				}
+				Some(Token::Not) => self.not()?,
+				_ => break,
+			};
+
+			expression = Expression::And(Box::new(expression), Box::new(right));
+		}
+
+		Ok(expression)
+	}
+
+	fn not(&mut self) -> Result<Expression, String> {
+		if self.tokens.next_if_eq(&Token::Not).is_some() {
+			let inner = self.nested(Self::not)?;
+			Ok(Expression::Not(Box::new(inner)))
+		} else {
+			self.term()
+		}
+	}
+
+	fn term(&mut self) -> Result<Expression, String> {
+		match self.tokens.next() {
+			Some(Token::LeftParen) => {
+				let expression = self.nested(Self::or)?;
+
+				match self.tokens.next() {
+					Some(Token::RightParen) => Ok(expression),
+					_ => Err("That expression has an unmatched `(`!".into()),
+				}
+			}
+			Some(Token::Quoted(phrase)) => self.add_term(phrase),
+			Some(Token::Word(word)) => {
+				let mut phrase = word;
+
+				while let Some(Token::Word(word)) =
+					self.tokens.next_if(|token| matches!(token, Token::Word(_)))
+				{
+					phrase.push(' ');
+					phrase.push_str(&word);
+				}
+
+				self.add_term(phrase)
+			}
+			_ => Err("That expression is missing a term!".into()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn term(text: &str) -> Box<Expression> {
+		Box::new(Expression::Term(text.to_owned()))
+	}
+
+	#[test]
+	fn parse_precedence() {
+		assert_eq!(
+			Expression::parse("outage AND (api OR gateway) NOT test"),
+			Ok(Expression::And(
+				Box::new(Expression::And(
+					term("outage"),
+					Box::new(Expression::Or(term("api"), term("gateway"))),
+				)),
+				Box::new(Expression::Not(term("test"))),
+			))
+		);
+
+		assert_eq!(
+			Expression::parse("a AND b OR c"),
+			Ok(Expression::Or(
+				Box::new(Expression::And(term("a"), term("b"))),
+				term("c"),
+			))
+		);
+	}
+
+	#[test]
+	fn parse_phrases() {
+		assert_eq!(
+			Expression::parse("hello world OR \"rock and roll\""),
+			Ok(Expression::Or(term("hello world"), term("rock and roll")))
+		);
+
+		assert_eq!(
+			Expression::parse("\"AND\" OR and"),
+			Ok(Expression::Or(term("AND"), term("and")))
+		);
+
+		assert_eq!(
+			Expression::parse("\"AND\" OR"),
+			Err("That expression is missing a term!".to_owned())
+		);
+	}
+
+	#[test]
+	fn parse_errors() {
+		assert!(Expression::parse("(a OR b").is_err());
+		assert!(Expression::parse("a OR b)").is_err());
+		assert!(Expression::parse("a AND").is_err());
+		assert!(Expression::parse("\"a").is_err());
+		assert!(Expression::parse("a \"\"").is_err());
+		assert!(Expression::parse("").is_err());
+	}
+
+	#[test]
+	fn parse_limits() {
+		let nested =
+			|depth| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+
+		assert!(
+			Expression::parse(&nested(EXPRESSION_KEYWORD_MAX_DEPTH)).is_ok()
+		);
+		assert!(Expression::parse(&nested(EXPRESSION_KEYWORD_MAX_DEPTH + 1))
+			.is_err());
+		assert!(Expression::parse(&nested(100_000)).is_err());
+		assert!(Expression::parse(&"NOT ".repeat(100_000)).is_err());
+
+		let terms = |count| vec!["a"; count].join(" OR ");
+
+		assert!(Expression::parse(&terms(EXPRESSION_KEYWORD_MAX_TERMS)).is_ok());
+		assert!(Expression::parse(&terms(EXPRESSION_KEYWORD_MAX_TERMS + 1))
+			.is_err());
+		assert!(Expression::parse(&terms(100_000)).is_err());
+	}
+
+	#[test]
+	fn evaluate_and_fired_terms() {
+		let expression =
+			Expression::parse("outage AND (api OR gateway) NOT test").unwrap();
+
+		let matches = |words: &'static [&'static str]| {
+			move |term: &str| words.contains(&term)
+		};
+
+		assert!(expression.evaluate(&mut matches(&["outage", "api"])));
+		assert!(!expression.evaluate(&mut matches(&["outage"])));
+		assert!(!expression.evaluate(&mut matches(&["outage", "api", "test"])));
+
+		assert_eq!(
+			expression.fired_terms(matches(&["outage", "gateway"])),
+			vec!["outage", "gateway"]
+		);
+	}
+}
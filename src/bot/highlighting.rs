@@ -3,29 +3,101 @@
 
 //! Functions for sending, editing, and deleting notifications.
 
+use aho_corasick::AhoCorasick;
 use anyhow::{anyhow, Context as _, Result};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use rust_stemmers::{Algorithm, Stemmer};
 use serenity::{
 	builder::{CreateEmbed, CreateMessage, EditMessage},
 	client::Context,
 	http::{error::ErrorResponse, HttpError},
 	model::{
-		channel::Message,
+		channel::{Channel, GuildChannel, Message},
 		id::{ChannelId, GuildId, MessageId, UserId},
+		user::OnlineStatus,
 	},
 	Error as SerenityError,
 };
 
-use std::{ops::Range, time::Duration};
+use std::{
+	borrow::Cow,
+	collections::{HashMap, HashSet},
+	iter, mem,
+	ops::Range,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, RwLock,
+	},
+	time::{Duration, Instant},
+};
 
 use crate::{
-	bot::util::{optional_result, user_can_read_channel},
-	db::{Ignore, Keyword, Notification, UserState, UserStateKind},
-	global::{EMBED_COLOR, ERROR_COLOR, NOTIFICATION_RETRIES},
-	log_discord_error, regex,
+	bot::{
+		actions, activity, cooldowns, dispatcher,
+		expression::Expression,
+		normalization::{NormalizeOptions, NormalizedText},
+		scheduler,
+		util::{
+			escape_markdown, optional_result, user_can_read_channel,
+			user_permissions_in, user_timezone,
+		},
+	},
+	db::{
+		ActivitySignals, Delivery, Exclusions, Ignore, Keyword, KeywordKind,
+		MatchKind, Notification, PendingNotification, QuietHours,
+		ScheduledNotification, Segmentation, UserSettings, UserState,
+		UserStateKind,
+	},
+	global::{
+		COMPILED_KEYWORD_CACHE_SIZE, CONTEXT_MESSAGE_MAX_LEN, EMBED_COLOR,
+		EMBED_FIELD_MAX_LEN, ERROR_COLOR, NOTIFICATION_CONTENT_MAX_LEN,
+		NOTIFICATION_EXCERPT_CONTEXT, REGEX_KEYWORD_SIZE_LIMIT,
+	},
 	settings::settings,
 };
 use indoc::indoc;
-use tokio::{select, time::sleep};
+use tokio::select;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Languages that stemmed keywords can be matched in, by name.
+pub const STEM_LANGUAGES: &[(&str, Algorithm)] = &[
+	("arabic", Algorithm::Arabic),
+	("danish", Algorithm::Danish),
+	("dutch", Algorithm::Dutch),
+	("english", Algorithm::English),
+	("finnish", Algorithm::Finnish),
+	("french", Algorithm::French),
+	("german", Algorithm::German),
+	("greek", Algorithm::Greek),
+	("hungarian", Algorithm::Hungarian),
+	("italian", Algorithm::Italian),
+	("norwegian", Algorithm::Norwegian),
+	("portuguese", Algorithm::Portuguese),
+	("romanian", Algorithm::Romanian),
+	("russian", Algorithm::Russian),
+	("spanish", Algorithm::Spanish),
+	("swedish", Algorithm::Swedish),
+	("tamil", Algorithm::Tamil),
+	("turkish", Algorithm::Turkish),
+];
+
+/// Gets the language the stemmed keywords of the user with the given settings are matched in.
+///
+/// Defaults to English if the user hasn't chosen a language, or chose one that's no longer
+/// supported.
+pub fn stem_language(settings: &UserSettings) -> Algorithm {
+	settings
+		.stem_language
+		.as_deref()
+		.and_then(|name| {
+			STEM_LANGUAGES
+				.iter()
+				.find(|(language_name, _)| *language_name == name)
+		})
+		.map_or(Algorithm::English, |&(_, language)| language)
+}
 
 /// Checks if the provided keyword should be highlighted anywhere in the given message.
 ///
@@ -34,12 +106,17 @@ use tokio::{select, time::sleep};
 /// is similarly searched for in the message content. If it is found, the permissions of the user
 /// are checked to ensure they can read the message. If they can read the message, `Ok(true)`
 /// is returned.
+///
+/// `matcher` holds the message content normalized with the user's options, and `language` is the
+/// language of the user's stemmed keywords and ignored phrases, from
+/// [`stem_language`](stem_language).
 pub async fn should_notify_keyword(
 	ctx: &Context,
 	message: &Message,
-	content: &str,
+	matcher: &KeywordMatcher,
 	keyword: &Keyword,
 	ignores: &[Ignore],
+	language: Algorithm,
 ) -> Result<bool> {
 	if message
 		.mentions
@@ -50,27 +127,30 @@ pub async fn should_notify_keyword(
 	}
 
 	for ignore in ignores {
-		if keyword_matches(&ignore.phrase, content) {
+		if matcher.matches(
+			&ignore.phrase,
+			ignore.match_kind,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			language,
+		) {
 			return Ok(false);
 		}
 	}
 
-	if !keyword_matches(&keyword.keyword, content) {
+	if !matcher.matches(
+		&keyword.keyword,
+		keyword.match_kind,
+		keyword.segmentation,
+		keyword.case_sensitive,
+		keyword.exclusions,
+		language,
+	) {
 		return Ok(false);
 	}
 
-	let channel = match ctx.cache.guild_channel(message.channel_id).await {
-		Some(c) => c,
-		None => match ctx.http.get_channel(message.channel_id.0).await? {
-			serenity::model::channel::Channel::Guild(c) => c,
-			_ => {
-				return Err(anyhow!(
-					"Channel {} wasn't a guild channel",
-					message.channel_id
-				))
-			}
-		},
-	};
+	let channel = guild_channel(ctx, message.channel_id).await?;
 
 	match user_can_read_channel(ctx, &channel, keyword.user_id).await {
 		Ok(Some(true)) => Ok(true),
@@ -79,105 +159,591 @@ pub async fn should_notify_keyword(
 	}
 }
 
-/// Sends a notification about a highlighted keyword.
+/// Gets a guild channel from the cache, or fetches it if it isn't cached.
+async fn guild_channel(
+	ctx: &Context,
+	channel_id: ChannelId,
+) -> Result<GuildChannel> {
+	match ctx.cache.guild_channel(channel_id).await {
+		Some(c) => Ok(c),
+		None => match ctx.http.get_channel(channel_id.0).await? {
+			Channel::Guild(c) => Ok(c),
+			_ => Err(anyhow!("Channel {} wasn't a guild channel", channel_id)),
+		},
+	}
+}
+
+/// Checks if the user with the given ID is online, rather than idle, on do not disturb, or offline.
+///
+/// Presences are only cached if the hoster enabled the presence intent; without it, users are never
+/// considered online, so users who chose to only be notified while away are always notified.
+async fn user_online(
+	ctx: &Context,
+	guild_id: GuildId,
+	user_id: UserId,
+) -> bool {
+	if !settings().bot.presences {
+		return false;
+	}
+
+	let status = ctx
+		.cache
+		.guild_field(guild_id, |g| {
+			g.presences.get(&user_id).map(|presence| presence.status)
+		})
+		.await
+		.flatten();
+
+	status == Some(OnlineStatus::Online)
+}
+
+/// Messages around a highlighted message, shown in its notification if the user chose to see
+/// them.
+#[derive(Debug, Default)]
+struct MessageContext {
+	/// The message the highlighted message replied to, if it's in the same channel.
+	replied_to: Option<Message>,
+	/// Messages sent just before the highlighted message, oldest first.
+	previous: Vec<Message>,
+}
+
+/// Fetches the context of a message to show in a notification for the user with the given ID.
 ///
-/// This will first wait for the configured patience duration for a message or reaction from the
-/// user of the keyword. If they don't send a message or reaction in that time, then an embed is
-/// created to notify them and sent in a DM channel.
+/// `count` is the number of messages before the message to fetch, from the user's settings.
+/// Nothing is fetched if it's 0, or if the user can't read the channel's message history.
+async fn fetch_message_context(
+	ctx: &Context,
+	message: &Message,
+	user_id: UserId,
+	count: u8,
+) -> Result<MessageContext> {
+	if count == 0 {
+		return Ok(MessageContext::default());
+	}
+
+	let channel = guild_channel(ctx, message.channel_id).await?;
+
+	match user_permissions_in(ctx, &channel, user_id).await? {
+		Some(permissions) if permissions.read_message_history() => {}
+		_ => return Ok(MessageContext::default()),
+	}
+
+	let mut previous = message
+		.channel_id
+		.messages(ctx, |r| r.before(message.id).limit(count.into()))
+		.await
+		.context("Failed to fetch context messages")?;
+
+	previous.reverse();
+
+	let replied_to = message
+		.referenced_message
+		.as_deref()
+		.filter(|reply| {
+			reply.channel_id == message.channel_id
+				&& !previous.iter().any(|m| m.id == reply.id)
+		})
+		.cloned();
+
+	Ok(MessageContext {
+		replied_to,
+		previous,
+	})
+}
+
+/// Sends a notification about the highlighted keywords of one user in a message.
 ///
-/// If sending the notification fails because of an internal server error, it is retried up to five
-/// times with a delay of two seconds.
+/// The notification is first stored as a [`ScheduledNotification`](ScheduledNotification), so it
+/// can be [resumed](scheduler) if the bot restarts before it's sent. Then this will wait for the
+/// configured patience duration for a message or reaction from the user of the keywords in the
+/// same channel. If they don't send a message or reaction in that time, and weren't
+/// [active](activity) elsewhere in the server in the ways they chose, then the notification is
+/// sent with [`send_keyword_notification`](send_keyword_notification).
 ///
-/// If sending the notification fails with `"Cannot send messages to this user"`, a corresponding
-/// [`UserState`](UserState) is created.
+/// If sending the notification fails, it's retried later by the [`scheduler`](scheduler).
 ///
-/// Any other errors are logged as normal.
-pub async fn notify_keyword(
+/// `edited` is whether the keywords only matched the message after it was edited, which is noted
+/// in the notification.
+pub async fn notify_keywords(
 	ctx: Context,
 	message: Message,
-	keyword: Keyword,
+	keywords: Vec<Keyword>,
 	ignores: Vec<Ignore>,
+	user_settings: UserSettings,
 	guild_id: GuildId,
+	edited: bool,
 ) {
-	let user_id = keyword.user_id;
+	let user_id = user_settings.user_id;
 	let channel_id = message.channel_id;
+	let patience = settings().behavior.patience;
+
+	let scheduled = ScheduledNotification {
+		user_id,
+		guild_id,
+		channel_id,
+		message_id: message.id,
+		due_at: Utc::now()
+			+ chrono::Duration::from_std(patience)
+				.expect("Patience duration out of range"),
+		attempts: 0,
+		dead: false,
+		edited,
+	};
+
+	if let Err(e) = scheduled.insert().await {
+		log_discord_error!(in channel_id, by user_id, e);
+	}
+
+	if seen_during_patience(
+		&ctx,
+		channel_id,
+		user_id,
+		guild_id,
+		user_settings.activity_signals,
+		patience,
+	)
+	.await
+	{
+		scheduler::finish(scheduled, Ok(())).await;
+		return;
+	}
+
+	let result = async {
+		let message = match fetch_message(&ctx, channel_id, message.id).await? {
+			Some(m) => m,
+			None => return Ok(()),
+		};
+
+		send_keyword_notification(
+			&ctx,
+			&message,
+			keywords,
+			&ignores,
+			&user_settings,
+			&scheduled,
+		)
+		.await
+	}
+	.await;
+
+	scheduler::finish(scheduled, result).await;
+}
+
+/// Waits for up to `patience` for signs that the user with the given ID saw a message in the given
+/// channel.
+///
+/// Returns `true` if they sent a message or added a reaction in the channel, or were
+/// [active](activity) elsewhere in the server in any of the given ways, during that time.
+pub async fn seen_during_patience(
+	ctx: &Context,
+	channel_id: ChannelId,
+	user_id: UserId,
+	guild_id: GuildId,
+	signals: ActivitySignals,
+	patience: Duration,
+) -> bool {
+	let started = Instant::now();
 
 	let reply_or_reaction;
 
-	let reply = message
-		.channel_id
-		.await_reply(&ctx)
+	let reply = channel_id
+		.await_reply(ctx)
 		.author_id(user_id)
-		.timeout(settings().behavior.patience);
+		.timeout(patience);
 
-	let reaction = message.channel_id.await_reaction(&ctx).author_id(user_id);
+	let reaction = channel_id.await_reaction(ctx).author_id(user_id);
 
 	select! {
 		reaction = reaction => reply_or_reaction = reaction.map(|_| ()),
 		reply = reply => reply_or_reaction = reply.map(|_| ()),
 	}
 
-	if reply_or_reaction.is_none() {
-		let result: Result<()> = async {
-			let message = match optional_result(
-				ctx.http
-					.get_message(message.channel_id.0, message.id.0)
-					.await,
-			)
-			.context("Failed to fetch original messsage")?
-			{
-				Some(m) => m,
-				None => return Ok(()),
-			};
+	reply_or_reaction.is_some()
+		|| activity::active_since(user_id, guild_id, signals, started)
+}
 
-			if !should_notify_keyword(
-				&ctx,
-				&message,
-				&message.content.to_lowercase(),
-				&keyword,
-				&ignores,
-			)
-			.await?
-			{
-				return Ok(());
+/// Fetches the current version of a message, or `None` if it was deleted.
+pub async fn fetch_message(
+	ctx: &Context,
+	channel_id: ChannelId,
+	message_id: MessageId,
+) -> Result<Option<Message>> {
+	optional_result(ctx.http.get_message(channel_id.0, message_id.0).await)
+		.context("Failed to fetch original messsage")
+}
+
+/// Sends a notification about the keywords of one user that match a message.
+///
+/// The keywords are checked again against the message's content, and one embed listing every
+/// keyword that still matches is created to notify them and sent in a DM channel. If the user
+/// collects their notifications into digests, or it's during their quiet hours, the keywords that
+/// still match are stored as [`PendingNotification`](PendingNotification)s instead, unless they
+/// chose to drop notifications during quiet hours. Users who chose to only be notified while away
+/// aren't notified if they're [online](user_online). On the first attempt to send a notification,
+/// which is described by `scheduled`, notifications held back by the user's
+/// [`cooldowns`](cooldowns) aren't sent, but are counted in their next notification; retries aren't
+/// held back. If the notification isn't sent, the user's cooldowns are restored.
+///
+/// If sending the notification fails, the error is returned, and the
+/// [`scheduler`](scheduler) decides whether to retry it.
+///
+/// If sending the notification fails with `"Cannot send messages to this user"`, a corresponding
+/// [`UserState`](UserState) is created.
+pub async fn send_keyword_notification(
+	ctx: &Context,
+	message: &Message,
+	keywords: Vec<Keyword>,
+	ignores: &[Ignore],
+	user_settings: &UserSettings,
+	scheduled: &ScheduledNotification,
+) -> Result<()> {
+	let user_id = user_settings.user_id;
+	let guild_id = scheduled.guild_id;
+	let channel_id = message.channel_id;
+
+	let matcher = KeywordMatcher::new(
+		NormalizedText::new(
+			&message.content,
+			NormalizeOptions::of(user_settings),
+		),
+		keywords
+			.iter()
+			.map(MatcherKeyword::from)
+			.chain(ignores.iter().map(MatcherKeyword::from)),
+	);
+
+	let mut matched = vec![];
+
+	for keyword in keywords {
+		if should_notify_keyword(
+			ctx,
+			message,
+			&matcher,
+			&keyword,
+			ignores,
+			stem_language(user_settings),
+		)
+		.await?
+		{
+			matched.push(keyword);
+		}
+	}
+
+	if matched.is_empty() {
+		return Ok(());
+	}
+
+	let quiet = in_quiet_hours(user_settings).await?;
+
+	if quiet && user_settings.drop_in_quiet_hours {
+		return Ok(());
+	}
+
+	if quiet || user_settings.delivery != Delivery::Immediate {
+		return queue_notifications(message, &matched, guild_id).await;
+	}
+
+	if user_settings.only_when_away && user_online(ctx, guild_id, user_id).await
+	{
+		return Ok(());
+	}
+
+	let allowed = if scheduled.attempts == 0 {
+		match cooldowns::try_notify(user_settings, channel_id) {
+			Some(allowed) => Some(allowed),
+			None => return Ok(()),
+		}
+	} else {
+		None
+	};
+
+	let suppressed = allowed
+		.as_ref()
+		.map_or(&[][..], |allowed| &allowed.suppressed);
+
+	let result = async {
+		let contents = matched
+			.iter()
+			.map(|keyword| {
+				matcher
+					.keyword_content(keyword.case_sensitive, keyword.exclusions)
+			})
+			.collect::<Vec<_>>();
+
+		let keyword_matches = matched
+			.iter()
+			.zip(&contents)
+			.map(|(keyword, content)| KeywordMatch {
+				content,
+				keyword: &keyword.keyword,
+				match_kind: keyword.match_kind,
+				segmentation: keyword.segmentation,
+				language: stem_language(user_settings),
+			})
+			.collect::<Vec<_>>();
+
+		let context = match fetch_message_context(
+			ctx,
+			message,
+			user_id,
+			user_settings.context_messages,
+		)
+		.await
+		{
+			Ok(context) => context,
+			Err(e) => {
+				log_discord_error!(in channel_id, by user_id, e);
+				MessageContext::default()
 			}
+		};
 
-			let message_to_send = build_notification_message(
-				&ctx,
-				&message,
-				&keyword.keyword,
-				channel_id,
-				guild_id,
-			)
-			.await?;
+		let message_to_send = build_notification_message(
+			ctx,
+			message,
+			&keyword_matches,
+			&context,
+			suppressed,
+			guild_id,
+			scheduled.edited,
+		)
+		.await?;
 
-			send_notification_message(
-				&ctx,
-				user_id,
-				message.id,
-				message_to_send,
-				keyword.keyword,
-			)
-			.await
+		send_notification_message(
+			ctx,
+			message,
+			guild_id,
+			message_to_send,
+			user_id,
+			&matched,
+		)
+		.await
+	}
+	.await;
+
+	if result.is_err() {
+		if let Some(allowed) = allowed {
+			cooldowns::restore(user_id, allowed);
 		}
-		.await;
+	}
+
+	result
+}
+
+/// Describes a keyword and how it's matched, like `Regex keyword /foo/`.
+pub fn describe_keyword(keyword: &str, match_kind: MatchKind) -> String {
+	match match_kind {
+		MatchKind::Literal => format!("Keyword \"{}\"", keyword),
+		MatchKind::Regex => format!("Regex keyword /{}/", keyword),
+		MatchKind::Wildcard => format!("Wildcard keyword \"{}\"", keyword),
+		MatchKind::Fuzzy => format!("Fuzzy keyword \"{}\"", keyword),
+		MatchKind::Stemmed => format!("Stemmed keyword \"{}\"", keyword),
+		MatchKind::Expression => format!("Expression keyword \"{}\"", keyword),
+		MatchKind::Proximity => format!("Proximity keyword \"{}\"", keyword),
+	}
+}
+
+/// Checks if it's currently during the [`QuietHours`](QuietHours) of the user with the given
+/// settings, in their time zone.
+pub async fn in_quiet_hours(user_settings: &UserSettings) -> Result<bool> {
+	let quiet_hours =
+		QuietHours::user_quiet_hours(user_settings.user_id).await?;
+
+	if quiet_hours.is_empty() {
+		return Ok(false);
+	}
+
+	let now = Utc::now()
+		.with_timezone(&user_timezone(user_settings))
+		.naive_local();
+
+	Ok(quiet_hours.iter().any(|window| window.contains(now)))
+}
+
+/// Stores keywords that matched a message to be sent in the user's next digest.
+async fn queue_notifications(
+	message: &Message,
+	keywords: &[Keyword],
+	guild_id: GuildId,
+) -> Result<()> {
+	let created_at = Utc::now();
+
+	let content = truncate(
+		&message
+			.content
+			.split_whitespace()
+			.collect::<Vec<_>>()
+			.join(" "),
+		CONTEXT_MESSAGE_MAX_LEN,
+	);
+
+	let notifications = keywords
+		.iter()
+		.map(|keyword| PendingNotification {
+			user_id: keyword.user_id,
+			guild_id,
+			channel_id: message.channel_id,
+			message_id: message.id,
+			author_name: message.author.name.clone(),
+			content: content.clone(),
+			keyword: keyword.keyword.clone(),
+			match_kind: keyword.match_kind,
+			created_at,
+		})
+		.collect();
+
+	PendingNotification::insert_all(notifications).await
+}
+
+/// A keyword that matched a message, with what's needed to show where it matched in a
+/// notification.
+struct KeywordMatch<'a> {
+	/// The message content, normalized as it was when the keyword matched it, from
+	/// [`keyword_content`](keyword_content).
+	content: &'a NormalizedText,
+	keyword: &'a str,
+	match_kind: MatchKind,
+	segmentation: Segmentation,
+	/// The language of stemmed keywords, from [`stem_language`](stem_language).
+	language: Algorithm,
+}
+
+impl KeywordMatch<'_> {
+	/// Describes the keyword for the title of a notification, like `Regex keyword /foo/`.
+	fn description(&self) -> String {
+		describe_keyword(self.keyword, self.match_kind)
+	}
+
+	/// Describes how the keyword matched the original message content, if that's not obvious, as
+	/// the name and value of an embed field.
+	///
+	/// This is the text a fuzzy keyword matched, or the terms that made an expression keyword
+	/// match.
+	fn details(&self, original: &str) -> Option<(&'static str, String)> {
+		let content = self.content;
+
+		match self.match_kind {
+			MatchKind::Fuzzy => {
+				let keyword = content.normalize_keyword(self.keyword);
+				let range = fuzzy_match(&keyword, content)?;
+				let matched = &original[content.original_range(range)];
+
+				Some(("Fuzzy match", format!("\"{}\"", matched)))
+			}
+			MatchKind::Expression => {
+				let expression = cached_expression(self.keyword)?;
+
+				let fired = expression
+					.fired_terms(|term| {
+						term_matches(term, self.segmentation, content)
+					})
+					.into_iter()
+					.map(|term| format!("\"{}\"", term))
+					.collect::<Vec<_>>();
 
-		if let Err(error) = result {
-			log_discord_error!(in channel_id, by user_id, error);
+				if fired.is_empty() {
+					None
+				} else {
+					Some(("Matched terms", fired.join(", ")))
+				}
+			}
+			_ => None,
 		}
 	}
+
+	/// Finds the ranges of the original content the keyword matched.
+	///
+	/// For expression keywords, these are the occurrences of the terms that contributed to the
+	/// match, and for proximity keywords, the occurrences of both terms.
+	fn ranges(&self) -> Vec<Range<usize>> {
+		let content = self.content;
+		let segmentation = self.segmentation;
+		let normalized = content.normalize_keyword(self.keyword);
+
+		let ranges: Vec<_> = match self.match_kind {
+			MatchKind::Literal => {
+				keyword_occurrences(&normalized, segmentation, content)
+					.collect()
+			}
+			MatchKind::Regex => {
+				let pattern = if content.is_case_sensitive() {
+					cached_regex(&format!("(?-i){}", self.keyword))
+				} else {
+					cached_regex(self.keyword)
+				};
+
+				// regexes are matched against the original content already
+				return pattern
+					.map(|regex| regex_occurrences(&regex, content).collect())
+					.unwrap_or_default();
+			}
+			MatchKind::Wildcard => {
+				wildcard_occurrences(&normalized, segmentation, content)
+					.collect()
+			}
+			MatchKind::Fuzzy => {
+				fuzzy_match(&normalized, content).into_iter().collect()
+			}
+			MatchKind::Stemmed => stemmed_occurrences(
+				&normalized,
+				segmentation,
+				content,
+				self.language,
+			)
+			.collect(),
+			MatchKind::Expression => match cached_expression(self.keyword) {
+				Some(expression) => expression
+					.fired_terms(|term| {
+						term_matches(term, segmentation, content)
+					})
+					.into_iter()
+					.flat_map(|term| {
+						let term = content.normalize_keyword(term);
+						keyword_occurrences(&term, segmentation, content)
+							.collect::<Vec<_>>()
+					})
+					.collect(),
+				None => vec![],
+			},
+			MatchKind::Proximity => match parse_proximity_keyword(self.keyword)
+			{
+				Some((first, _, second)) => [first, second]
+					.iter()
+					.flat_map(|term| {
+						let term = content.normalize_keyword(term);
+						keyword_occurrences(&term, segmentation, content)
+							.collect::<Vec<_>>()
+					})
+					.collect(),
+				None => vec![],
+			},
+		};
+
+		ranges
+			.into_iter()
+			.map(|range| content.original_range(range))
+			.collect()
+	}
 }
 
 async fn build_notification_message(
 	ctx: &Context,
 	message: &Message,
-	keyword: &str,
-	channel_id: ChannelId,
+	keyword_matches: &[KeywordMatch<'_>],
+	context: &MessageContext,
+	suppressed: &[(ChannelId, u32)],
 	guild_id: GuildId,
+	edited: bool,
 ) -> Result<CreateMessage<'static>> {
-	let embed =
-		build_notification_embed(ctx, message, keyword, channel_id, guild_id)
-			.await?;
+	let embed = build_notification_embed(
+		ctx,
+		message,
+		keyword_matches,
+		context,
+		suppressed,
+		guild_id,
+		edited,
+	)
+	.await?;
 
 	let mut msg = CreateMessage::default();
 
@@ -192,13 +758,20 @@ async fn build_notification_message(
 async fn build_notification_edit(
 	ctx: &Context,
 	message: &Message,
-	keyword: &str,
-	channel_id: ChannelId,
+	keyword_matches: &[KeywordMatch<'_>],
+	context: &MessageContext,
 	guild_id: GuildId,
 ) -> Result<EditMessage> {
-	let embed =
-		build_notification_embed(ctx, message, keyword, channel_id, guild_id)
-			.await?;
+	let embed = build_notification_embed(
+		ctx,
+		message,
+		keyword_matches,
+		context,
+		&[],
+		guild_id,
+		true,
+	)
+	.await?;
 
 	let mut msg = EditMessage::default();
 
@@ -210,21 +783,29 @@ async fn build_notification_edit(
 	Ok(msg)
 }
 
+/// Builds the embed of a notification about one or more keywords of the same user matching a
+/// message.
+///
+/// `suppressed` is the numbers of notifications held back by the user's cooldowns in each channel
+/// since they were last notified, from [`cooldowns::try_notify`](cooldowns::try_notify). If
+/// `edited` is true, the title notes that the message was edited.
 async fn build_notification_embed(
 	ctx: &Context,
 	message: &Message,
-	keyword: &str,
-	channel_id: ChannelId,
+	keyword_matches: &[KeywordMatch<'_>],
+	context: &MessageContext,
+	suppressed: &[(ChannelId, u32)],
 	guild_id: GuildId,
+	edited: bool,
 ) -> Result<CreateEmbed> {
 	let message_link = format!(
 		"[(Link)](https://discord.com/channels/{}/{}/{})",
-		guild_id, channel_id, message.id
+		guild_id, message.channel_id, message.id
 	);
 
 	let channel_name = ctx
 		.cache
-		.guild_channel_field(channel_id, |c| c.name.clone())
+		.guild_channel_field(message.channel_id, |c| c.name.clone())
 		.await
 		.context("Couldn't get channel for keyword")?;
 	let (guild_name, guild_icon) = ctx
@@ -232,16 +813,33 @@ async fn build_notification_embed(
 		.guild_field(guild_id, |g| (g.name.clone(), g.icon_url()))
 		.await
 		.context("Couldn't get guild for keyword")?;
-	let title = format!(
-		"Keyword \"{}\" seen in #{} ({})",
-		keyword, channel_name, guild_name
-	);
+	let mut title = match keyword_matches {
+		[keyword_match] => format!(
+			"{} seen in #{} ({})",
+			keyword_match.description(),
+			channel_name,
+			guild_name
+		),
+		_ => format!(
+			"{} keywords seen in #{} ({})",
+			keyword_matches.len(),
+			channel_name,
+			guild_name
+		),
+	};
+	if edited {
+		title.push_str(" (edited)");
+	}
 	let channel_mention = format!("<#{}>", message.channel_id);
 
+	let ranges = keyword_matches
+		.iter()
+		.flat_map(|keyword_match| keyword_match.ranges());
+
 	let mut embed = CreateEmbed::default();
 
 	embed
-		.description(&message.content)
+		.description(highlighted_content(&message.content, ranges))
 		.timestamp(&message.timestamp)
 		.author(|a| {
 			a.name(title);
@@ -263,104 +861,241 @@ async fn build_notification_embed(
 		})
 		.color(EMBED_COLOR);
 
-	Ok(embed)
-}
-
-async fn send_notification_message(
-	ctx: &Context,
-	user_id: UserId,
-	message_id: MessageId,
-	message_to_send: CreateMessage<'static>,
-	keyword: String,
-) -> Result<()> {
-	let dm_channel = user_id
-		.create_dm_channel(&ctx)
-		.await
-		.context("Failed to create DM channel to notify user")?;
+	match keyword_matches {
+		[keyword_match] => {
+			if let Some((name, details)) =
+				keyword_match.details(&message.content)
+			{
+				embed.field(name, details, true);
+			}
+		}
+		_ => {
+			let lines = keyword_matches
+				.iter()
+				.map(|keyword_match| {
+					let line = match keyword_match.details(&message.content) {
+						Some((name, details)) => format!(
+							"{} ({}: {})",
+							keyword_match.description(),
+							name.to_lowercase(),
+							details
+						),
+						None => keyword_match.description(),
+					};
 
-	let mut result = Ok(());
+					format!("- {}", escape_markdown(&line))
+				})
+				.collect::<Vec<_>>()
+				.join("\n");
+
+			embed.field(
+				"Keywords",
+				truncate(&lines, EMBED_FIELD_MAX_LEN),
+				false,
+			);
+		}
+	}
 
-	for _ in 0..NOTIFICATION_RETRIES {
-		let mut message_to_send = message_to_send.clone();
+	fn context_line(message: &Message) -> (&str, &str) {
+		(&message.author.name, &message.content)
+	}
 
-		match dm_channel
-			.send_message(&ctx, |_| &mut message_to_send)
-			.await
-		{
-			Ok(sent_message) => {
-				result = Ok(());
-				UserState::clear(user_id).await?;
-				let notification = Notification {
-					original_message: message_id,
-					notification_message: sent_message.id,
-					keyword,
-					user_id,
-				};
-				notification.insert().await?;
-				break;
-			}
+	if let Some(reply) = &context.replied_to {
+		embed.field(
+			"Replying to",
+			context_lines(iter::once(context_line(reply))),
+			false,
+		);
+	}
 
-			Err(SerenityError::Http(err)) => match &*err {
-				HttpError::UnsuccessfulRequest(ErrorResponse {
-					status_code,
-					..
-				}) if status_code.is_server_error() => {
-					result = Err(SerenityError::Http(err).into());
-				}
+	if !context.previous.is_empty() {
+		embed.field(
+			"Context",
+			context_lines(context.previous.iter().map(context_line)),
+			false,
+		);
+	}
 
-				HttpError::UnsuccessfulRequest(ErrorResponse {
-					error, ..
-				}) if error.message == "Cannot send messages to this user" => {
-					let user_state = UserState {
-						user_id,
-						state: UserStateKind::CannotDm,
-					};
+	if !suppressed.is_empty() {
+		let lines = suppressed
+			.iter()
+			.map(|(channel_id, count)| {
+				format!("+{} more in <#{}>", count, channel_id)
+			})
+			.collect::<Vec<_>>()
+			.join("\n");
+
+		embed.field(
+			"Held back by your cooldowns",
+			truncate(&lines, EMBED_FIELD_MAX_LEN),
+			false,
+		);
+	}
 
-					user_state.set().await?;
+	Ok(embed)
+}
 
-					result = Ok(());
-					break;
-				}
+/// Formats messages shown as context in a notification, given as pairs of their authors' names
+/// and their content, oldest first.
+///
+/// Each message is shortened to [`CONTEXT_MESSAGE_MAX_LEN`](CONTEXT_MESSAGE_MAX_LEN) characters and
+/// put on one line, and the oldest messages are left out if they don't all fit in an embed field.
+fn context_lines<'a>(
+	messages: impl Iterator<Item = (&'a str, &'a str)>,
+) -> String {
+	let mut lines = messages
+		.map(|(author, content)| {
+			let content =
+				content.split_whitespace().collect::<Vec<_>>().join(" ");
+
+			let content = if content.is_empty() {
+				"*(no text)*".to_owned()
+			} else if content.chars().count() > CONTEXT_MESSAGE_MAX_LEN {
+				let shortened = content
+					.chars()
+					.take(CONTEXT_MESSAGE_MAX_LEN - 1)
+					.collect::<String>();
+				format!("{}…", escape_markdown(&shortened))
+			} else {
+				escape_markdown(&content)
+			};
 
-				_ => return Err(SerenityError::Http(err).into()),
-			},
+			format!("**{}**: {}", escape_markdown(author), content)
+		})
+		.collect::<Vec<_>>();
 
-			Err(err) => {
-				Err(err).context("Failed to send notification message")?
-			}
-		}
+	let len = |lines: &[String]| {
+		lines
+			.iter()
+			.map(|line| line.chars().count() + 1)
+			.sum::<usize>()
+	};
 
-		sleep(Duration::from_secs(2)).await;
+	while lines.len() > 1 && len(&lines) > EMBED_FIELD_MAX_LEN {
+		lines.remove(0);
 	}
 
-	result
+	truncate(&lines.join("\n"), EMBED_FIELD_MAX_LEN)
 }
 
-pub async fn delete_sent_notifications(
-	ctx: &Context,
-	channel_id: ChannelId,
-	notifications: &[Notification],
-) {
+/// Shortens text to at most `max_len` characters, ending it with an ellipsis if it was cut off.
+pub fn truncate(text: &str, max_len: usize) -> String {
+	if text.chars().count() > max_len {
+		text.chars()
+			.take(max_len - 1)
+			.chain(iter::once('…'))
+			.collect()
+	} else {
+		text.to_owned()
+	}
+}
+
+/// Sends a notification message about the given keywords matching a message, storing a
+/// [`Notification`](Notification) for each keyword and adding [`actions`](actions) to it.
+async fn send_notification_message(
+	ctx: &Context,
+	message: &Message,
+	guild_id: GuildId,
+	message_to_send: CreateMessage<'static>,
+	user_id: UserId,
+	keywords: &[Keyword],
+) -> Result<()> {
+	let sent_message = match send_dm(ctx, user_id, message_to_send).await? {
+		Some(m) => m,
+		None => return Ok(()),
+	};
+
+	for keyword in keywords {
+		let notification = Notification {
+			original_message: message.id,
+			notification_message: sent_message.id,
+			keyword: keyword.keyword.clone(),
+			user_id,
+			match_kind: keyword.match_kind,
+			segmentation: keyword.segmentation,
+			case_sensitive: keyword.case_sensitive,
+			exclusions: keyword.exclusions,
+			guild_id: Some(guild_id),
+			channel_id: Some(message.channel_id),
+			author_id: Some(message.author.id),
+			channel_keyword: matches!(keyword.kind, KeywordKind::Channel(_)),
+			sent_at: Some(sent_message.timestamp),
+		};
+		notification.insert().await?;
+	}
+
+	actions::add_actions(ctx, user_id, sent_message.id);
+
+	Ok(())
+}
+
+/// Sends a message to the user with the given ID in a DM channel, through the
+/// [`dispatcher`](dispatcher).
+///
+/// Failures aren't retried here; scheduled notifications are retried by the
+/// [`scheduler`](super::scheduler) instead.
+///
+/// If sending the message fails with `"Cannot send messages to this user"`, a corresponding
+/// [`UserState`](UserState) is created and `Ok(None)` is returned. Otherwise, the user's state is
+/// cleared once the message is sent.
+pub async fn send_dm(
+	ctx: &Context,
+	user_id: UserId,
+	message_to_send: CreateMessage<'static>,
+) -> Result<Option<Message>> {
+	match dispatcher::send(ctx, user_id, message_to_send).await {
+		Ok(sent_message) => {
+			UserState::clear(user_id).await?;
+			Ok(Some(sent_message))
+		}
+
+		Err(SerenityError::Http(err)) => match &*err {
+			HttpError::UnsuccessfulRequest(ErrorResponse { error, .. })
+				if error.message == "Cannot send messages to this user" =>
+			{
+				let user_state = UserState {
+					user_id,
+					state: UserStateKind::CannotDm,
+				};
+
+				user_state.set().await?;
+
+				Ok(None)
+			}
+
+			_ => Err(SerenityError::Http(err).into()),
+		},
+
+		Err(err) => Err(err).context("Failed to send notification message"),
+	}
+}
+
+pub async fn delete_sent_notifications(
+	ctx: &Context,
+	channel_id: ChannelId,
+	notifications: &[Notification],
+) {
+	let mut deleted = HashSet::new();
+
 	for notification in notifications {
+		// one notification message can be stored once for each of its keywords
+		if !deleted.insert(notification.notification_message) {
+			continue;
+		}
+
 		let user_id = notification.user_id;
 		let message_id = notification.notification_message;
 
-		let result: Result<()> = async {
-			let dm_channel = user_id.create_dm_channel(ctx).await?;
+		let mut edit = EditMessage::default();
 
-			dm_channel
-				.edit_message(ctx, message_id, |m| {
-					m.embed(|e| {
-						e.description("*Original message deleted*")
-							.color(ERROR_COLOR)
-					})
-				})
-				.await
-				.context("Failed to edit notification message")?;
+		edit.embed(|e| {
+			e.description("*Original message deleted*")
+				.color(ERROR_COLOR)
+		});
 
-			Ok(())
-		}
-		.await;
+		let result = dispatcher::edit(ctx, user_id, message_id, edit)
+			.await
+			.context("Failed to edit notification message");
 
 		if let Err(e) = result {
 			log_discord_error!(in channel_id, deleted notification.original_message, e);
@@ -368,6 +1103,10 @@ pub async fn delete_sent_notifications(
 	}
 }
 
+/// Updates the notifications that were sent because of a message that was edited.
+///
+/// Each notification is edited to list the keywords that still match the message's new content,
+/// and deleted if none of them match anymore.
 pub async fn update_sent_notifications(
 	ctx: &Context,
 	channel_id: ChannelId,
@@ -377,37 +1116,101 @@ pub async fn update_sent_notifications(
 ) {
 	let mut to_delete = vec![];
 
-	let lowercase_content = message.content.to_lowercase();
+	let mut notifications_by_message = Vec::<Vec<Notification>>::new();
 
 	for notification in notifications {
-		if !keyword_matches(&notification.keyword, &lowercase_content) {
-			to_delete.push(notification);
+		match notifications_by_message.iter_mut().find(|group| {
+			group[0].notification_message == notification.notification_message
+		}) {
+			Some(group) => group.push(notification),
+			None => notifications_by_message.push(vec![notification]),
+		}
+	}
+
+	for notifications in notifications_by_message {
+		let user_id = notifications[0].user_id;
+		let notification_message = notifications[0].notification_message;
+
+		let settings = UserSettings::indexed_user_settings(user_id);
+
+		let matcher = KeywordMatcher::new(
+			NormalizedText::new(
+				&message.content,
+				NormalizeOptions::of(&settings),
+			),
+			notifications.iter().map(MatcherKeyword::from),
+		);
+
+		let language = stem_language(&settings);
+
+		let matched = notifications
+			.iter()
+			.filter(|notification| {
+				matcher.matches(
+					&notification.keyword,
+					notification.match_kind,
+					notification.segmentation,
+					notification.case_sensitive,
+					notification.exclusions,
+					language,
+				)
+			})
+			.collect::<Vec<_>>();
+
+		if matched.is_empty() {
+			// deleting one of them deletes the rest, since they share a notification message
+			to_delete.extend(notifications.into_iter().take(1));
 			continue;
 		}
 
 		let result: Result<()> = async {
+			let contents = matched
+				.iter()
+				.map(|notification| {
+					matcher.keyword_content(
+						notification.case_sensitive,
+						notification.exclusions,
+					)
+				})
+				.collect::<Vec<_>>();
+
+			let keyword_matches = matched
+				.iter()
+				.zip(&contents)
+				.map(|(notification, content)| KeywordMatch {
+					content,
+					keyword: &notification.keyword,
+					match_kind: notification.match_kind,
+					segmentation: notification.segmentation,
+					language,
+				})
+				.collect::<Vec<_>>();
+
+			let context = fetch_message_context(
+				ctx,
+				&message,
+				user_id,
+				settings.context_messages,
+			)
+			.await?;
+
 			let message_to_send = build_notification_edit(
 				ctx,
 				&message,
-				&notification.keyword,
-				channel_id,
+				&keyword_matches,
+				&context,
 				guild_id,
 			)
 			.await?;
 
-			let user_id = notification.user_id;
-			let message_id = notification.notification_message;
-
-			let dm_channel = user_id.create_dm_channel(ctx).await.context(
-				"Failed to create DM channel to update notifications",
-			)?;
-
-			dm_channel
-				.edit_message(ctx, message_id, |m| {
-					*m = message_to_send;
-					m
-				})
-				.await?;
+			dispatcher::edit(
+				ctx,
+				user_id,
+				notification_message,
+				message_to_send,
+			)
+			.await
+			.context("Failed to update notification message")?;
 
 			Ok(())
 		}
@@ -427,55 +1230,1106 @@ pub async fn update_sent_notifications(
 	}
 }
 
-/// Finds a match of the keyword in the message content.
-fn keyword_matches(keyword: &str, content: &str) -> bool {
-	fn overlaps_with_mention(range: Range<usize>, content: &str) -> bool {
-		regex!(r"<(@!?|&|#|a?:[a-zA-Z0-9_]*:)[0-9]+>")
-			.find_iter(content)
-			.any(|mention| {
-				let mention = mention.range();
-
-				range.start <= mention.end && range.end >= mention.start
+/// A keyword or ignored phrase to look for with a [`KeywordMatcher`](KeywordMatcher).
+#[derive(Debug, Clone, Copy)]
+pub struct MatcherKeyword<'a> {
+	pub keyword: &'a str,
+	pub match_kind: MatchKind,
+	pub case_sensitive: bool,
+	pub exclusions: Exclusions,
+}
+
+impl<'a> MatcherKeyword<'a> {
+	/// A literal keyword that isn't case-sensitive and doesn't exclude any more of the content.
+	pub fn literal(keyword: &'a str) -> Self {
+		Self {
+			keyword,
+			match_kind: MatchKind::Literal,
+			case_sensitive: false,
+			exclusions: Exclusions::none(),
+		}
+	}
+}
+
+impl<'a> From<&'a Keyword> for MatcherKeyword<'a> {
+	fn from(keyword: &'a Keyword) -> Self {
+		Self {
+			keyword: &keyword.keyword,
+			match_kind: keyword.match_kind,
+			case_sensitive: keyword.case_sensitive,
+			exclusions: keyword.exclusions,
+		}
+	}
+}
+
+impl<'a> From<&'a Ignore> for MatcherKeyword<'a> {
+	fn from(ignore: &'a Ignore) -> Self {
+		Self {
+			match_kind: ignore.match_kind,
+			..Self::literal(&ignore.phrase)
+		}
+	}
+}
+
+impl<'a> From<&'a Notification> for MatcherKeyword<'a> {
+	fn from(notification: &'a Notification) -> Self {
+		Self {
+			keyword: &notification.keyword,
+			match_kind: notification.match_kind,
+			case_sensitive: notification.case_sensitive,
+			exclusions: notification.exclusions,
+		}
+	}
+}
+
+/// Matcher for many keywords and ignored phrases against the same message content.
+///
+/// Keywords given to [`new`](KeywordMatcher::new) are grouped by whether they're case-sensitive
+/// and which parts of the content they exclude, and the content is normalized once for each group.
+/// Occurrences of every literal keyword in a group are found in one pass over its content with an
+/// Aho-Corasick automaton, and then checked with the same boundary rules as
+/// [`keyword_matches`](keyword_matches), so keywords that don't appear at all are ruled out without
+/// scanning the content again. Other keywords are matched individually with
+/// [`content_matches`](content_matches).
+pub struct KeywordMatcher {
+	content: NormalizedText,
+	/// The keywords of each combination of case sensitivity and exclusions.
+	groups: HashMap<(bool, Exclusions), MatcherGroup>,
+}
+
+/// Keywords that are matched against the same content by a [`KeywordMatcher`](KeywordMatcher).
+struct MatcherGroup {
+	/// The content, if it's normalized differently from the matcher's content.
+	content: Option<NormalizedText>,
+	/// The index into `patterns` of each literal keyword, as written.
+	indices: HashMap<String, usize>,
+	/// Each distinct literal keyword, normalized like the content.
+	patterns: Vec<String>,
+	/// The occurrences of each pattern in the normalized content, which may overlap.
+	occurrences: Vec<Vec<Range<usize>>>,
+}
+
+impl KeywordMatcher {
+	/// Normalizes the content for each group of the given keywords or ignored phrases, and finds
+	/// the occurrences of the literal ones.
+	pub fn new<'a>(
+		content: NormalizedText,
+		keywords: impl IntoIterator<Item = MatcherKeyword<'a>>,
+	) -> Self {
+		let mut literals_by_group = HashMap::<_, Vec<_>>::new();
+
+		for keyword in keywords {
+			let literals = literals_by_group
+				.entry((keyword.case_sensitive, keyword.exclusions))
+				.or_default();
+
+			if keyword.match_kind == MatchKind::Literal {
+				literals.push(keyword.keyword);
+			}
+		}
+
+		let groups = literals_by_group
+			.into_iter()
+			.map(|((case_sensitive, exclusions), literals)| {
+				let group_content =
+					match keyword_content(&content, case_sensitive, exclusions)
+					{
+						Cow::Borrowed(_) => None,
+						Cow::Owned(group_content) => Some(group_content),
+					};
+
+				let group =
+					MatcherGroup::new(group_content, &content, literals);
+
+				((case_sensitive, exclusions), group)
 			})
+			.collect();
+
+		Self { content, groups }
 	}
 
-	if regex!(r"\s").is_match(keyword) {
-		// if the keyword has a space, only matches of whole phrases should be considered
-		content
-			.match_indices(keyword)
-			.filter(|(i, phrase)| {
-				if *i != 0 || i + phrase.len() < content.len() {
-					let start = i.saturating_sub(1);
-					let end = usize::min(i + phrase.len() + 1, content.len());
-					content
-						.get(start..end)
-						.map(|around| regex!(r"^.\b.*\b.$").is_match(around))
-						.unwrap_or(true)
+	/// Gets the content to match keywords with the given case sensitivity and exclusions against,
+	/// like [`keyword_content`](keyword_content).
+	///
+	/// The content is only normalized again if no such keywords were given to
+	/// [`new`](KeywordMatcher::new).
+	pub fn keyword_content(
+		&self,
+		case_sensitive: bool,
+		exclusions: Exclusions,
+	) -> Cow<'_, NormalizedText> {
+		match self.groups.get(&(case_sensitive, exclusions)) {
+			Some(group) => Cow::Borrowed(group.content(&self.content)),
+			None => keyword_content(&self.content, case_sensitive, exclusions),
+		}
+	}
+
+	/// Checks if a keyword or ignored phrase of the given kind appears in the message content.
+	///
+	/// The result is the same as [`content_matches`](content_matches), but uses the content
+	/// normalized for the keyword's group and the occurrences found by the automaton for literal
+	/// keywords that were given to [`new`](KeywordMatcher::new).
+	pub fn matches(
+		&self,
+		keyword: &str,
+		match_kind: MatchKind,
+		segmentation: Segmentation,
+		case_sensitive: bool,
+		exclusions: Exclusions,
+		language: Algorithm,
+	) -> bool {
+		let group = match self.groups.get(&(case_sensitive, exclusions)) {
+			Some(group) => group,
+			None => {
+				return content_matches(
+					keyword,
+					match_kind,
+					segmentation,
+					case_sensitive,
+					exclusions,
+					&self.content,
+					language,
+				)
+			}
+		};
+
+		let content = group.content(&self.content);
+
+		let index = match group.indices.get(keyword) {
+			Some(&index) if match_kind == MatchKind::Literal => index,
+			_ => {
+				return normalized_content_matches(
+					keyword,
+					match_kind,
+					segmentation,
+					case_sensitive,
+					content,
+					language,
+				)
+			}
+		};
+
+		let occurrences = &group.occurrences[index];
+
+		if occurrences.is_empty() {
+			return false;
+		}
+
+		boundary_matches(
+			&group.patterns[index],
+			occurrences.iter().cloned(),
+			None,
+			segmentation,
+			content,
+		)
+	}
+}
+
+impl MatcherGroup {
+	/// Finds the occurrences of the given literal keywords or ignored phrases in the group's
+	/// content, which is `base` unless it's normalized differently.
+	fn new(
+		content: Option<NormalizedText>,
+		base: &NormalizedText,
+		literals: Vec<&str>,
+	) -> Self {
+		let normalized = content.as_ref().unwrap_or(base);
+
+		let mut indices = HashMap::new();
+		let mut pattern_indices = HashMap::new();
+		let mut patterns = vec![];
+
+		for literal in literals {
+			if indices.contains_key(literal) {
+				continue;
+			}
+
+			let pattern = normalized.normalize_keyword(literal);
+
+			if pattern.is_empty() {
+				continue;
+			}
+
+			let index = *pattern_indices.entry(pattern).or_insert_with_key(
+				|pattern: &String| {
+					patterns.push(pattern.clone());
+					patterns.len() - 1
+				},
+			);
+
+			indices.insert(literal.to_owned(), index);
+		}
+
+		let mut occurrences = vec![vec![]; patterns.len()];
+
+		if !patterns.is_empty() {
+			let automaton = AhoCorasick::new(&patterns);
+
+			for m in automaton.find_overlapping_iter(normalized.as_str()) {
+				occurrences[m.pattern()].push(m.start()..m.end());
+			}
+		}
+
+		Self {
+			content,
+			indices,
+			patterns,
+			occurrences,
+		}
+	}
+
+	/// Gets the group's content, given the content of its matcher.
+	fn content<'a>(&'a self, base: &'a NormalizedText) -> &'a NormalizedText {
+		self.content.as_ref().unwrap_or(base)
+	}
+}
+
+/// Compiles a regex keyword or ignored phrase.
+///
+/// Regex keywords are matched case-insensitively unless the pattern turns that off, and their compiled size is limited to
+/// [`REGEX_KEYWORD_SIZE_LIMIT`](REGEX_KEYWORD_SIZE_LIMIT) so one keyword can't make checking every
+/// message expensive.
+pub fn compile_regex_keyword(pattern: &str) -> Result<Regex, regex::Error> {
+	RegexBuilder::new(pattern)
+		.case_insensitive(true)
+		.size_limit(REGEX_KEYWORD_SIZE_LIMIT)
+		.dfa_size_limit(REGEX_KEYWORD_SIZE_LIMIT)
+		.build()
+}
+
+/// Cache of compiled keywords, which drops the least recently used one when it's full.
+///
+/// Keywords stay cached after they're removed, until they're pushed out by others, so the
+/// capacity bounds how much memory removed keywords can hold on to.
+struct CompiledCache<T> {
+	capacity: usize,
+	/// Incremented every time an entry is used, to order entries by when they were last used.
+	clock: AtomicU64,
+	entries: RwLock<HashMap<String, CompiledEntry<T>>>,
+}
+
+struct CompiledEntry<T> {
+	value: Arc<T>,
+	last_used: AtomicU64,
+}
+
+impl<T> CompiledCache<T> {
+	fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			clock: AtomicU64::new(0),
+			entries: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// Gets the cached value for the given source, or compiles and caches it if it's not cached.
+	///
+	/// Errors from compiling aren't cached.
+	fn get_or_compile<E>(
+		&self,
+		source: &str,
+		compile: impl FnOnce(&str) -> Result<T, E>,
+	) -> Result<Arc<T>, E> {
+		if let Some(entry) = self.entries.read().unwrap().get(source) {
+			entry.last_used.store(self.tick(), Ordering::Relaxed);
+			return Ok(Arc::clone(&entry.value));
+		}
+
+		let value = Arc::new(compile(source)?);
+
+		let mut entries = self.entries.write().unwrap();
+
+		if entries.len() >= self.capacity && !entries.contains_key(source) {
+			let least_recent = entries
+				.iter()
+				.min_by_key(|(_, entry)| {
+					entry.last_used.load(Ordering::Relaxed)
+				})
+				.map(|(source, _)| source.clone());
+
+			if let Some(least_recent) = least_recent {
+				entries.remove(&least_recent);
+			}
+		}
+
+		entries.insert(
+			source.to_owned(),
+			CompiledEntry {
+				value: Arc::clone(&value),
+				last_used: AtomicU64::new(self.tick()),
+			},
+		);
+
+		Ok(value)
+	}
+
+	fn tick(&self) -> u64 {
+		self.clock.fetch_add(1, Ordering::Relaxed)
+	}
+}
+
+/// Gets the compiled form of a regex keyword, compiling it the first time it's used.
+///
+/// Returns `None` if the pattern doesn't compile, which should only happen if the pattern was
+/// stored by a version of highlights with different limits.
+fn cached_regex(pattern: &str) -> Option<Arc<Regex>> {
+	static CACHE: Lazy<CompiledCache<Regex>> =
+		Lazy::new(|| CompiledCache::new(COMPILED_KEYWORD_CACHE_SIZE));
+
+	match CACHE.get_or_compile(pattern, compile_regex_keyword) {
+		Ok(regex) => Some(regex),
+		Err(e) => {
+			log::warn!("Invalid stored regex keyword {:?}: {}", pattern, e);
+			None
+		}
+	}
+}
+
+/// Gets the parsed form of an expression keyword, parsing it the first time it's used.
+///
+/// Returns `None` if the expression doesn't parse, which should only happen if it was stored by a
+/// version of highlights with different limits.
+fn cached_expression(keyword: &str) -> Option<Arc<Expression>> {
+	static CACHE: Lazy<CompiledCache<Expression>> =
+		Lazy::new(|| CompiledCache::new(COMPILED_KEYWORD_CACHE_SIZE));
+
+	match CACHE.get_or_compile(keyword, Expression::parse) {
+		Ok(expression) => Some(expression),
+		Err(e) => {
+			log::warn!(
+				"Invalid stored expression keyword {:?}: {}",
+				keyword,
+				e
+			);
+			None
+		}
+	}
+}
+
+/// Checks if a keyword or ignored phrase of the given kind appears in the message content.
+///
+/// The keyword is normalized the same way as the content first, except for regex keywords, which
+/// are matched as written against the original content, and expression and proximity keywords,
+/// whose terms are normalized separately. `segmentation` is used for every kind of keyword except
+/// regex and fuzzy keywords, and `language` is only used for stemmed keywords.
+///
+/// If `case_sensitive` is true, the content is normalized again without case folding, or regex
+/// keywords are matched case-sensitively. Parts of the content in `exclusions` are excluded, along
+/// with any the content already excludes.
+fn content_matches(
+	keyword: &str,
+	match_kind: MatchKind,
+	segmentation: Segmentation,
+	case_sensitive: bool,
+	exclusions: Exclusions,
+	content: &NormalizedText,
+	language: Algorithm,
+) -> bool {
+	// regexes are matched against the original content, so its case doesn't need to be kept
+	let content = keyword_content(
+		content,
+		case_sensitive && match_kind != MatchKind::Regex,
+		exclusions,
+	);
+
+	normalized_content_matches(
+		keyword,
+		match_kind,
+		segmentation,
+		case_sensitive,
+		&content,
+		language,
+	)
+}
+
+/// Checks if a keyword or ignored phrase of the given kind appears in message content that's
+/// already normalized for it with [`keyword_content`](keyword_content), like
+/// [`content_matches`](content_matches).
+fn normalized_content_matches(
+	keyword: &str,
+	match_kind: MatchKind,
+	segmentation: Segmentation,
+	case_sensitive: bool,
+	content: &NormalizedText,
+	language: Algorithm,
+) -> bool {
+	if match_kind == MatchKind::Regex {
+		return if case_sensitive {
+			regex_matches(&format!("(?-i){}", keyword), content)
+		} else {
+			regex_matches(keyword, content)
+		};
+	}
+
+	if match_kind == MatchKind::Expression {
+		return expression_matches(keyword, segmentation, content);
+	}
+
+	if match_kind == MatchKind::Proximity {
+		return proximity_matches(keyword, segmentation, content);
+	}
+
+	let keyword = content.normalize_keyword(keyword);
+
+	match match_kind {
+		MatchKind::Literal => keyword_matches(&keyword, segmentation, content),
+		MatchKind::Regex | MatchKind::Expression | MatchKind::Proximity => {
+			unreachable!(
+				"Regex, expression, and proximity keywords aren't normalized"
+			)
+		}
+		MatchKind::Wildcard => {
+			wildcard_matches(&keyword, segmentation, content)
+		}
+		MatchKind::Fuzzy => fuzzy_match(&keyword, content).is_some(),
+		MatchKind::Stemmed => {
+			stemmed_matches(&keyword, segmentation, content, language)
+		}
+	}
+}
+
+/// Gets the message content to match a keyword against, depending on whether the keyword is
+/// case-sensitive and which parts of the content it excludes.
+fn keyword_content(
+	content: &NormalizedText,
+	case_sensitive: bool,
+	exclusions: Exclusions,
+) -> Cow<'_, NormalizedText> {
+	let mut content = Cow::Borrowed(content);
+
+	if case_sensitive {
+		content = Cow::Owned(content.preserving_case());
+	}
+
+	if !content.excludes(exclusions) {
+		content = Cow::Owned(content.with_exclusions(exclusions));
+	}
+
+	content
+}
+
+/// Formats message content for a notification, with the given ranges of it in bold.
+///
+/// Markdown in the content is escaped, so the emphasis is shown in the right places. If the content
+/// is too long for an embed, only the text around the first range is shown, with ellipses where
+/// text was left out.
+fn highlighted_content(
+	content: &str,
+	ranges: impl IntoIterator<Item = Range<usize>>,
+) -> String {
+	let mut ranges = ranges.into_iter().collect::<Vec<_>>();
+	ranges.sort_by_key(|range| range.start);
+
+	let ranges = ranges.into_iter().fold(
+		Vec::<Range<usize>>::new(),
+		|mut merged, range| {
+			match merged.last_mut() {
+				Some(last) if range.start <= last.end => {
+					last.end = last.end.max(range.end);
+				}
+				_ => merged.push(range),
+			}
+			merged
+		},
+	);
+
+	let whole = emphasize(content, 0..content.len(), &ranges);
+
+	if whole.chars().count() <= NOTIFICATION_CONTENT_MAX_LEN {
+		return whole;
+	}
+
+	let first = ranges.first().cloned().unwrap_or(0..0);
+	let mut context = NOTIFICATION_EXCERPT_CONTEXT;
+
+	loop {
+		let window = excerpt_window(content, first.clone(), context);
+
+		let mut excerpt = emphasize(content, window.clone(), &ranges);
+
+		if window.start > 0 {
+			excerpt.insert(0, '…');
+		}
+
+		if window.end < content.len() {
+			excerpt.push('…');
+		}
+
+		let len = excerpt.chars().count();
+
+		if len <= NOTIFICATION_CONTENT_MAX_LEN {
+			return excerpt;
+		}
+
+		if context == 0 {
+			// even the match alone is too long, so it has to be cut off
+			return excerpt
+				.chars()
+				.take(NOTIFICATION_CONTENT_MAX_LEN - 1)
+				.chain(iter::once('…'))
+				.collect();
+		}
+
+		context /= 2;
+	}
+}
+
+/// Escapes the given window of the content, putting the parts of it in `ranges` in bold.
+///
+/// `ranges` must be sorted and not overlap.
+fn emphasize(
+	content: &str,
+	window: Range<usize>,
+	ranges: &[Range<usize>],
+) -> String {
+	let mut emphasized = String::with_capacity(window.len() * 2);
+	let mut last = window.start;
+
+	for range in ranges {
+		let start = range.start.max(window.start);
+		let end = range.end.min(window.end);
+
+		if start >= end {
+			continue;
+		}
+
+		// bold text can't start or end with whitespace
+		let text = &content[start..end];
+		let start = start + (text.len() - text.trim_start().len());
+		let end = end - (text.len() - text.trim_end().len());
+
+		if start >= end {
+			continue;
+		}
+
+		emphasized.push_str(&escape_markdown(&content[last..start]));
+		emphasized.push_str("**");
+		emphasized.push_str(&escape_markdown(&content[start..end]));
+		emphasized.push_str("**");
+
+		last = end;
+	}
+
+	emphasized.push_str(&escape_markdown(&content[last..window.end]));
+
+	emphasized
+}
+
+/// Finds the part of the content to show around a match, with up to `context` characters on each
+/// side, not cutting off words where possible.
+fn excerpt_window(
+	content: &str,
+	range: Range<usize>,
+	context: usize,
+) -> Range<usize> {
+	let start = content[..range.start]
+		.char_indices()
+		.rev()
+		.take(context)
+		.last()
+		.map_or(range.start, |(i, _)| i);
+
+	let start = if start == 0 {
+		0
+	} else {
+		content[start..range.start]
+			.find(char::is_whitespace)
+			.map_or(start, |i| start + i)
+	};
+
+	let before = &content[start..range.start];
+	let start = start + (before.len() - before.trim_start().len());
+
+	let end = content[range.end..]
+		.char_indices()
+		.nth(context)
+		.map_or(content.len(), |(i, _)| range.end + i);
+
+	let end = if end == content.len() {
+		end
+	} else {
+		content[range.end..end]
+			.rfind(char::is_whitespace)
+			.map_or(end, |i| range.end + i)
+	};
+
+	let after = &content[range.end..end];
+	let end = end - (after.len() - after.trim_end().len());
+
+	start..end
+}
+
+/// Finds a match of the regex keyword in the message content.
+///
+/// Regexes are matched against the original content, since they can't be normalized like the
+/// content is, so accents and look-alike characters aren't ignored.
+fn regex_matches(pattern: &str, content: &NormalizedText) -> bool {
+	cached_regex(pattern).is_some_and(|regex| {
+		regex_occurrences(&regex, content).next().is_some()
+	})
+}
+
+/// Finds the occurrences of the regex in the original message content, like
+/// [`regex_matches`](regex_matches).
+fn regex_occurrences<'a>(
+	regex: &'a Regex,
+	content: &'a NormalizedText,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+	regex
+		.find_iter(content.original())
+		.map(|m| m.range())
+		.filter(move |range| {
+			!content.original_overlaps_with_excluded(range.clone())
+		})
+}
+
+/// Evaluates the expression keyword against the message content.
+///
+/// Each term of the expression is normalized and matched like a literal keyword.
+fn expression_matches(
+	keyword: &str,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+) -> bool {
+	cached_expression(keyword).is_some_and(|expression| {
+		expression
+			.evaluate(&mut |term| term_matches(term, segmentation, content))
+	})
+}
+
+/// Checks if one term of an expression keyword appears in the message content.
+fn term_matches(
+	term: &str,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+) -> bool {
+	keyword_matches(&content.normalize_keyword(term), segmentation, content)
+}
+
+/// Finds a match of the keyword in the message content.
+fn keyword_matches(
+	keyword: &str,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+) -> bool {
+	keyword_occurrences(keyword, segmentation, content)
+		.next()
+		.is_some()
+}
+
+/// Finds the occurrences of the keyword in the message content that are on appropriate
+/// boundaries, as ranges of the normalized content.
+fn keyword_occurrences<'a>(
+	keyword: &'a str,
+	segmentation: Segmentation,
+	content: &'a NormalizedText,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+	let occurrences = content
+		.as_str()
+		.match_indices(keyword)
+		.map(move |(i, _)| i..i + keyword.len());
+
+	boundary_occurrences(keyword, occurrences, None, segmentation, content)
+}
+
+/// Finds a match of the proximity keyword in the message content.
+///
+/// Each of the two terms is normalized and matched like a literal keyword, and the keyword
+/// matches if an occurrence of each term has at most the given number of words between them, in
+/// either order.
+fn proximity_matches(
+	keyword: &str,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+) -> bool {
+	let (first, distance, second) = match parse_proximity_keyword(keyword) {
+		Some(parsed) => parsed,
+		None => {
+			log::warn!("Invalid stored proximity keyword {:?}", keyword);
+			return false;
+		}
+	};
+
+	let (first, second) = (
+		content.normalize_keyword(first),
+		content.normalize_keyword(second),
+	);
+
+	let words = regex!(r"\w+")
+		.find_iter(content.as_str())
+		.map(|m| m.range())
+		.collect::<Vec<_>>();
+
+	// the indices of the first and last words each occurrence overlaps with
+	let word_span = |range: Range<usize>| {
+		let first_word = words.partition_point(|word| word.end <= range.start);
+		let last_word = words
+			.partition_point(|word| word.start < range.end)
+			.saturating_sub(1);
+		(range, first_word, last_word)
+	};
+
+	let first_spans = keyword_occurrences(&first, segmentation, content)
+		.map(word_span)
+		.collect::<Vec<_>>();
+
+	if first_spans.is_empty() {
+		return false;
+	}
+
+	let mut second_spans =
+		keyword_occurrences(&second, segmentation, content).map(word_span);
+
+	second_spans.any(|(second_range, second_first, second_last)| {
+		first_spans
+			.iter()
+			.any(|(first_range, first_first, first_last)| {
+				if first_range.end <= second_range.start {
+					second_first.saturating_sub(first_last + 1) <= distance
+				} else if second_range.end <= first_range.start {
+					first_first.saturating_sub(second_last + 1) <= distance
 				} else {
-					true
+					false
 				}
 			})
-			.map(|(index, _)| index..index + keyword.len())
-			.any(|range| !overlaps_with_mention(range, content))
-	} else if regex!(r"\W").is_match(keyword) {
+	})
+}
+
+/// Splits a proximity keyword, like `release ~3 notes`, into its two terms and the maximum number
+/// of words between them.
+///
+/// Returns `None` if the keyword isn't a proximity keyword.
+pub fn parse_proximity_keyword(keyword: &str) -> Option<(&str, usize, &str)> {
+	let captures = regex!(r"^(.+?) ~(\d+) (.+)$").captures(keyword)?;
+
+	let distance = captures[2].parse().ok()?;
+
+	Some((
+		captures.get(1).unwrap().as_str().trim(),
+		distance,
+		captures.get(3).unwrap().as_str().trim(),
+	))
+}
+
+/// Finds a match of the wildcard keyword in the message content.
+///
+/// Each `*` in the keyword matches any number of word characters, including none. Otherwise, the
+/// keyword is matched like a literal keyword with its `*`s removed.
+fn wildcard_matches(
+	keyword: &str,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+) -> bool {
+	wildcard_occurrences(keyword, segmentation, content)
+		.next()
+		.is_some()
+}
+
+/// Finds the occurrences of the wildcard keyword in the message content, like
+/// [`wildcard_matches`](wildcard_matches).
+fn wildcard_occurrences<'a>(
+	keyword: &str,
+	segmentation: Segmentation,
+	content: &'a NormalizedText,
+) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+	// the keyword is already normalized like the content, so case doesn't need to be ignored
+	let pattern = format!(
+		"(?-i){}",
+		keyword
+			.split('*')
+			.map(regex::escape)
+			.collect::<Vec<_>>()
+			.join(r"\w*")
+	);
+
+	let (unanchored, anchored) = match (
+		cached_regex(&pattern),
+		cached_regex(&format!("^(?:{})$", pattern)),
+	) {
+		(Some(unanchored), Some(anchored)) => (unanchored, anchored),
+		_ => return Box::new(iter::empty()),
+	};
+
+	let occurrences = unanchored
+		.find_iter(content.as_str())
+		.map(|m| m.range())
+		.collect::<Vec<_>>();
+
+	boundary_occurrences(
+		&keyword.replace('*', ""),
+		occurrences.into_iter(),
+		Some(Box::new(move |frag| anchored.is_match(frag))),
+		segmentation,
+		content,
+	)
+}
+
+/// Finds a match of the stemmed keyword in the message content.
+///
+/// Alphanumeric keywords match any word with the same stem in the given language, found by
+/// stripping suffixes, so `build` matches "builds" and "building". Irregular forms like "built"
+/// aren't suffixes of the stem, and aren't matched. Other keywords, and keywords matched as substrings or in scripts without spaces
+/// between words, are matched like literal keywords.
+fn stemmed_matches(
+	keyword: &str,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+	language: Algorithm,
+) -> bool {
+	stemmed_occurrences(keyword, segmentation, content, language)
+		.next()
+		.is_some()
+}
+
+/// Finds the occurrences of the stemmed keyword in the message content, like
+/// [`stemmed_matches`](stemmed_matches).
+fn stemmed_occurrences<'a>(
+	keyword: &'a str,
+	segmentation: Segmentation,
+	content: &'a NormalizedText,
+	language: Algorithm,
+) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+	let stemmer = Stemmer::create(language);
+	let stem = stemmer.stem(keyword).into_owned();
+
+	let occurrences = content
+		.as_str()
+		.match_indices(keyword)
+		.map(move |(i, _)| i..i + keyword.len());
+
+	boundary_occurrences(
+		keyword,
+		occurrences,
+		Some(Box::new(move |frag| {
+			frag == keyword || stemmer.stem(frag) == stem
+		})),
+		segmentation,
+		content,
+	)
+}
+
+/// Finds a fuzzy match of the keyword in the message content.
+///
+/// The keyword and content are split into words of alphanumeric characters, and the keyword
+/// matches a run of consecutive words in the content if each word is within
+/// [`fuzzy_max_distance`](fuzzy_max_distance) edits of the corresponding word of the keyword.
+///
+/// Returns the range of the normalized content that matched, from the start of its first word to
+/// the end of its last word.
+fn fuzzy_match(
+	keyword: &str,
+	content: &NormalizedText,
+) -> Option<Range<usize>> {
+	let keyword_words = regex!(r"\w+")
+		.find_iter(keyword)
+		.map(|m| m.as_str())
+		.collect::<Vec<_>>();
+
+	if keyword_words.is_empty() {
+		return None;
+	}
+
+	let text = content.as_str();
+
+	let content_words = regex!(r"\w+")
+		.find_iter(text)
+		.map(|m| m.range())
+		.collect::<Vec<_>>();
+
+	content_words
+		.windows(keyword_words.len())
+		.filter(|window| {
+			window.iter().zip(&keyword_words).all(|(range, word)| {
+				within_edit_distance(
+					word,
+					&text[range.clone()],
+					fuzzy_max_distance(word),
+				)
+			})
+		})
+		.map(|window| window[0].start..window[window.len() - 1].end)
+		.find(|range| !content.overlaps_with_excluded(range.clone()))
+}
+
+/// Determines how many edits are allowed for a word of a fuzzy keyword to still match.
+///
+/// Short words have to match exactly, since nearly every short word is only an edit or two away
+/// from some other word.
+fn fuzzy_max_distance(word: &str) -> usize {
+	match word.chars().count() {
+		0..=4 => 0,
+		5..=8 => 1,
+		_ => 2,
+	}
+}
+
+/// Checks if the edit distance between two strings is at most `max`.
+///
+/// Edits are insertions, deletions, or substitutions of one character, or swaps of two adjacent
+/// characters (optimal string alignment distance).
+fn within_edit_distance(a: &str, b: &str, max: usize) -> bool {
+	let a = a.chars().collect::<Vec<_>>();
+	let b = b.chars().collect::<Vec<_>>();
+
+	if a.len().abs_diff(b.len()) > max {
+		return false;
+	}
+
+	// distances from prefixes of `a` to each prefix of `b`, for the last two rows and this one
+	let mut before_last = vec![0; b.len() + 1];
+	let mut last = (0..=b.len()).collect::<Vec<_>>();
+	let mut current = vec![0; b.len() + 1];
+
+	for i in 1..=a.len() {
+		current[0] = i;
+
+		for j in 1..=b.len() {
+			let substitution = last[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+
+			current[j] = substitution.min(last[j] + 1).min(current[j - 1] + 1);
+
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				current[j] = current[j].min(before_last[j - 2] + 1);
+			}
+		}
+
+		mem::swap(&mut before_last, &mut last);
+		mem::swap(&mut last, &mut current);
+	}
+
+	last[b.len()] <= max
+}
+
+/// Checks if a fragment of the content matches a keyword that can match text other than itself, for
+/// [`boundary_occurrences`](boundary_occurrences).
+type FragmentMatcher<'a> = Box<dyn Fn(&str) -> bool + 'a>;
+
+/// Checks if any of the occurrences of a keyword in the content are on appropriate boundaries.
+///
+/// See [`boundary_occurrences`](boundary_occurrences) for the boundaries that are required.
+fn boundary_matches(
+	text: &str,
+	occurrences: impl Iterator<Item = Range<usize>>,
+	matches_fragment: Option<FragmentMatcher<'_>>,
+	segmentation: Segmentation,
+	content: &NormalizedText,
+) -> bool {
+	boundary_occurrences(
+		text,
+		occurrences,
+		matches_fragment,
+		segmentation,
+		content,
+	)
+	.next()
+	.is_some()
+}
+
+/// Filters the occurrences of a keyword in the content to those on appropriate boundaries.
+///
+/// If `segmentation` is [`Substring`](Segmentation::Substring), any occurrence is allowed. If it's
+/// [`Auto`](Segmentation::Auto) and `text` contains characters from scripts that are written
+/// without spaces between words, an occurrence must start and end on Unicode word boundaries.
+///
+/// Otherwise, `text` is the text of the keyword, and determines the boundaries that are required:
+/// - If it contains whitespace, an occurrence must start and end on word boundaries.
+/// - If it contains other non-alphanumeric characters, any occurrence is allowed.
+/// - Otherwise, an occurrence must be a whole fragment of the content, split on non-alphanumeric
+///   characters. If `matches_fragment` is given, the fragments that satisfy it are used instead of
+///   `occurrences`, for keywords that match text other than themselves.
+///
+/// Occurrences that overlap with mentions are never included.
+fn boundary_occurrences<'a>(
+	text: &str,
+	occurrences: impl Iterator<Item = Range<usize>> + 'a,
+	matches_fragment: Option<FragmentMatcher<'a>>,
+	segmentation: Segmentation,
+	content: &'a NormalizedText,
+) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+	let normalized = content.as_str();
+	let not_mention = move |range: &Range<usize>| {
+		!content.overlaps_with_excluded(range.clone())
+	};
+
+	let unspaced = regex!(
+		r"[\p{Han}\p{Hiragana}\p{Katakana}\p{Thai}\p{Lao}\p{Khmer}\p{Myanmar}\p{Tibetan}]"
+	);
+
+	if segmentation == Segmentation::Substring {
+		Box::new(occurrences.filter(not_mention))
+	} else if segmentation == Segmentation::Auto && unspaced.is_match(text) {
+		// words in these scripts aren't separated, so rely on Unicode word segmentation instead
+		let boundaries = normalized
+			.split_word_bound_indices()
+			.map(|(i, _)| i)
+			.chain(iter::once(normalized.len()))
+			.collect::<Vec<_>>();
+
+		Box::new(
+			occurrences
+				.filter(move |range| {
+					boundaries.binary_search(&range.start).is_ok()
+						&& boundaries.binary_search(&range.end).is_ok()
+				})
+				.filter(not_mention),
+		)
+	} else if regex!(r"\s").is_match(text) {
+		// if the keyword has a space, only matches of whole phrases should be considered
+		Box::new(
+			occurrences
+				.filter(move |range| {
+					if range.start != 0 || range.end < normalized.len() {
+						let start = range.start.saturating_sub(1);
+						let end = usize::min(range.end + 1, normalized.len());
+						normalized
+							.get(start..end)
+							.map(|around| {
+								regex!(r"^.\b.*\b.$").is_match(around)
+							})
+							.unwrap_or(true)
+					} else {
+						true
+					}
+				})
+				.filter(not_mention),
+		)
+	} else if regex!(r"\W").is_match(text) {
 		// if the keyword contains non-alphanumeric characters, it could appear anywhere
-		content
-			.match_indices(keyword)
-			.map(|(i, _)| i..i + keyword.len())
-			.any(|range| !overlaps_with_mention(range, content))
+		Box::new(occurrences.filter(not_mention))
 	} else {
 		// otherwise, it is only alphanumeric and could appear between non-alphanumeric text
-		regex!(r"\W+")
-			.split(content)
-			.filter(|&frag| keyword == frag)
-			.map(|substring| {
-				let substring_start = substring.as_ptr() as usize;
-				let content_start = content.as_ptr() as usize;
-				let substring_index = substring_start - content_start;
-
-				substring_index..substring_index + keyword.len()
-			})
-			.any(|range| !overlaps_with_mention(range, content))
+		let matches_fragment = match matches_fragment {
+			Some(matches_fragment) => matches_fragment,
+			None => {
+				let is_word = |c: char| {
+					regex!(r"^\w$").is_match(c.encode_utf8(&mut [0; 4]))
+				};
+
+				// the occurrences are of the keyword itself, so keep the ones that are whole fragments
+				let whole = move |range: &Range<usize>| {
+					let before = normalized[..range.start].chars().next_back();
+					let after = normalized[range.end..].chars().next();
+
+					!before.is_some_and(is_word) && !after.is_some_and(is_word)
+				};
+
+				return Box::new(occurrences.filter(whole).filter(not_mention));
+			}
+		};
+
+		Box::new(
+			regex!(r"\W+")
+				.split(normalized)
+				.filter(move |&frag| matches_fragment(frag))
+				.map(move |substring| {
+					let substring_start = substring.as_ptr() as usize;
+					let content_start = normalized.as_ptr() as usize;
+					let substring_index = substring_start - content_start;
+
+					substring_index..substring_index + substring.len()
+				})
+				.filter(not_mention),
+		)
 	}
 }
 
@@ -515,25 +2369,891 @@ pub async fn check_notify_user_state(
 mod tests {
 	use super::*;
 
+	fn text(content: &str) -> NormalizedText {
+		NormalizedText::new(content, NormalizeOptions::default())
+	}
+
 	#[test]
 	fn keyword_match_basic() {
-		assert!(keyword_matches("bar", "foo bar baz"));
+		assert!(keyword_matches(
+			"bar",
+			Segmentation::Auto,
+			&text("foo bar baz")
+		));
 	}
 
 	#[test]
 	fn keyword_match_phrase() {
-		assert!(keyword_matches("foo bar", "baz foo bar."));
+		assert!(keyword_matches(
+			"foo bar",
+			Segmentation::Auto,
+			&text("baz foo bar.")
+		));
 	}
 
 	#[test]
 	fn keyword_match_complex() {
-		assert!(keyword_matches("$bar", "foo$bar%baz"));
+		assert!(keyword_matches(
+			"$bar",
+			Segmentation::Auto,
+			&text("foo$bar%baz")
+		));
 	}
 
 	#[test]
 	fn keyword_match_unicode() {
-		assert!(keyword_matches("ဥပမာ", "စမ်းသပ်မှု—ဥပမာ—ကျေးဇူးပြု၍ လျစ်လျူရှုပါ"));
+		assert!(keyword_matches(
+			"ဥပမာ",
+			Segmentation::Auto,
+			&text("စမ်းသပ်မှု—ဥပမာ—ကျေးဇူးပြု၍ လျစ်လျူရှုပါ")
+		));
+
+		assert!(keyword_matches(
+			"ဥပမာ",
+			Segmentation::Auto,
+			&text("စမ်းသပ်မှုဥပမာ")
+		));
+
+		assert!(!keyword_matches(
+			"ဥပမာ",
+			Segmentation::Words,
+			&text("စမ်းသပ်မှုဥပမာ")
+		));
+	}
+
+	#[test]
+	fn keyword_match_cjk() {
+		assert!(keyword_matches(
+			"北京",
+			Segmentation::Auto,
+			&text("我喜欢吃北京烤鸭")
+		));
+
+		assert!(keyword_matches(
+			"東京",
+			Segmentation::Auto,
+			&text("明日東京に行きます")
+		));
+
+		assert!(!keyword_matches(
+			"東京",
+			Segmentation::Words,
+			&text("明日東京に行きます")
+		));
+
+		assert!(!keyword_matches(
+			"テスト",
+			Segmentation::Auto,
+			&text("テストケースを書く")
+		));
+
+		assert!(keyword_matches(
+			"テスト",
+			Segmentation::Substring,
+			&text("テストケースを書く")
+		));
+	}
+
+	#[test]
+	fn keyword_match_thai() {
+		assert!(keyword_matches(
+			"ไทย",
+			Segmentation::Auto,
+			&text("ภาษาไทยง่ายนิดเดียว")
+		));
+
+		assert!(!keyword_matches(
+			"ไทย",
+			Segmentation::Words,
+			&text("ภาษาไทยง่ายนิดเดียว")
+		));
+	}
+
+	#[test]
+	fn keyword_match_substring() {
+		assert!(keyword_matches(
+			"deploy",
+			Segmentation::Substring,
+			&text("redeployment")
+		));
+
+		assert!(!keyword_matches(
+			"deploy",
+			Segmentation::Auto,
+			&text("redeployment")
+		));
+	}
+
+	#[test]
+	fn wildcard_match_basic() {
+		assert!(wildcard_matches(
+			"deploy*",
+			Segmentation::Auto,
+			&text("starting the deployment now")
+		));
+
+		assert!(wildcard_matches(
+			"*bot",
+			Segmentation::Auto,
+			&text("ask the chatbot")
+		));
+
+		assert!(wildcard_matches(
+			"deploy*",
+			Segmentation::Auto,
+			&text("deploy it")
+		));
+
+		assert!(!wildcard_matches(
+			"deploy*",
+			Segmentation::Auto,
+			&text("redeployment")
+		));
+	}
+
+	#[test]
+	fn wildcard_match_phrase() {
+		assert!(wildcard_matches(
+			"deploy* prod*",
+			Segmentation::Auto,
+			&text("now deploying production.")
+		));
+
+		assert!(!wildcard_matches(
+			"deploy* prod",
+			Segmentation::Auto,
+			&text("now deploying products")
+		));
+	}
+
+	#[test]
+	fn wildcard_match_complex() {
+		assert!(wildcard_matches(
+			"$bar*",
+			Segmentation::Auto,
+			&text("foo$barbaz")
+		));
+	}
+
+	#[test]
+	fn fuzzy_match_typos() {
+		assert!(
+			fuzzy_match("kubernetes", &text("is kubernets down?")).is_some()
+		);
+
+		assert!(fuzzy_match("postgres", &text("postgress is slow")).is_some());
+
+		assert!(fuzzy_match("postgres", &text("postgers")).is_some());
+
+		assert!(fuzzy_match("postgres", &text("progress")).is_none());
+
+		assert!(fuzzy_match("rust", &text("rest")).is_none());
+	}
+
+	#[test]
+	fn fuzzy_match_phrase() {
+		let content = "ask about the kubernets cluser";
+
+		assert_eq!(
+			fuzzy_match("kubernetes cluster", &text(content))
+				.map(|r| &content[r]),
+			Some("kubernets cluser")
+		);
+
+		assert!(fuzzy_match(
+			"kubernetes cluster",
+			&text("kubernets and cluster")
+		)
+		.is_none());
+	}
+
+	#[test]
+	fn stemmed_match() {
+		let english = Algorithm::English;
+
+		assert!(stemmed_matches(
+			"build",
+			Segmentation::Auto,
+			&text("the builds are green"),
+			english
+		));
+
+		assert!(stemmed_matches(
+			"build",
+			Segmentation::Auto,
+			&text("building now"),
+			english
+		));
+
+		assert!(stemmed_matches(
+			"building",
+			Segmentation::Auto,
+			&text("can you build it?"),
+			english
+		));
+
+		assert!(!stemmed_matches(
+			"build",
+			Segmentation::Auto,
+			&text("rebuilding now"),
+			english
+		));
+
+		assert!(!stemmed_matches(
+			"build",
+			Segmentation::Auto,
+			&text("it's built"),
+			english
+		));
+
+		assert!(stemmed_matches(
+			"maison",
+			Segmentation::Auto,
+			&text("toutes les maisons"),
+			Algorithm::French
+		));
+	}
+
+	#[test]
+	fn normalized_match() {
+		let english = Algorithm::English;
+
+		assert!(content_matches(
+			"hello",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&text("ＨＥＬＬＯ there"),
+			english
+		));
+
+		let strip_accents = NormalizeOptions {
+			strip_accents: true,
+			..Default::default()
+		};
+
+		assert!(content_matches(
+			"café",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&NormalizedText::new("a cafe nearby", strip_accents),
+			english
+		));
+
+		assert!(!content_matches(
+			"café",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&text("a cafe nearby"),
+			english
+		));
+
+		let confusables = NormalizeOptions {
+			confusables: true,
+			..Default::default()
+		};
+
+		assert!(content_matches(
+			"paypal",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&NormalizedText::new("log in to раypal now", confusables),
+			english
+		));
+
+		assert!(!content_matches(
+			"123",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&text("ﬁle <@123>"),
+			english
+		));
+	}
+
+	#[test]
+	fn expression_match() {
+		let expression = "outage AND (api OR gateway) NOT test";
+
+		assert!(expression_matches(
+			expression,
+			Segmentation::Auto,
+			&text("Outage in the API right now")
+		));
+
+		assert!(!expression_matches(
+			expression,
+			Segmentation::Auto,
+			&text("outage in the api test environment")
+		));
+
+		assert!(!expression_matches(
+			expression,
+			Segmentation::Auto,
+			&text("no outage here")
+		));
+
+		assert!(expression_matches(
+			"\"hello world\" OR greetings",
+			Segmentation::Auto,
+			&text("well, hello world!")
+		));
+	}
+
+	#[test]
+	fn proximity_match() {
+		assert!(proximity_matches(
+			"release ~3 notes",
+			Segmentation::Auto,
+			&text("notes for the release")
+		));
+
+		assert!(proximity_matches(
+			"release ~0 notes",
+			Segmentation::Auto,
+			&text("the release notes are out")
+		));
+
+		assert!(!proximity_matches(
+			"release ~1 notes",
+			Segmentation::Auto,
+			&text("notes for the release")
+		));
+
+		assert!(!proximity_matches(
+			"release ~3 notes",
+			Segmentation::Auto,
+			&text("releases and footnotes")
+		));
+
+		assert!(proximity_matches(
+			"api ~2 outage",
+			Segmentation::Auto,
+			&text("API gateway outage")
+		));
+
+		assert!(proximity_matches(
+			"release ~0 notes",
+			Segmentation::Substring,
+			&text("see releasenotes.md")
+		));
+
+		assert!(!proximity_matches(
+			"123 ~3 notes",
+			Segmentation::Auto,
+			&text("<@123> notes")
+		));
+	}
+
+	#[test]
+	fn case_sensitive_match() {
+		let english = Algorithm::English;
+
+		assert!(content_matches(
+			"Go",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			true,
+			Exclusions::none(),
+			&text("written in Go"),
+			english
+		));
+
+		assert!(!content_matches(
+			"Go",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			true,
+			Exclusions::none(),
+			&text("time to go home"),
+			english
+		));
+
+		assert!(content_matches(
+			"Go",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&text("time to go home"),
+			english
+		));
+
+		assert!(!content_matches(
+			r"PR-\d+",
+			MatchKind::Regex,
+			Segmentation::Auto,
+			true,
+			Exclusions::none(),
+			&text("see pr-1234 for details"),
+			english
+		));
+
+		assert!(content_matches(
+			"K8*",
+			MatchKind::Wildcard,
+			Segmentation::Auto,
+			true,
+			Exclusions::none(),
+			&text("the K8s cluster"),
+			english
+		));
+	}
 
-		assert!(!keyword_matches("ဥပမာ", "စမ်းသပ်မှုဥပမာ"));
+	#[test]
+	fn excluded_match() {
+		let english = Algorithm::English;
+
+		let content = text("run `cargo build` then build");
+
+		assert!(content_matches(
+			"cargo",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			&content,
+			english
+		));
+
+		assert!(!content_matches(
+			"cargo",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::INLINE_CODE,
+			&content,
+			english
+		));
+
+		assert!(content_matches(
+			"build",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::INLINE_CODE,
+			&content,
+			english
+		));
+
+		let matcher = KeywordMatcher::new(
+			NormalizedText::new(
+				"||spoiler||\n> quoted",
+				NormalizeOptions {
+					exclusions: Exclusions::SPOILERS,
+					..Default::default()
+				},
+			),
+			[
+				MatcherKeyword::literal("spoiler"),
+				MatcherKeyword::literal("quoted"),
+				MatcherKeyword {
+					exclusions: Exclusions::QUOTES,
+					..MatcherKeyword::literal("quoted")
+				},
+			],
+		);
+
+		assert!(!matcher.matches(
+			"spoiler",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			english
+		));
+
+		assert!(matcher.matches(
+			"quoted",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			english
+		));
+
+		assert!(!matcher.matches(
+			"quoted",
+			MatchKind::Literal,
+			Segmentation::Auto,
+			false,
+			Exclusions::QUOTES,
+			english
+		));
+
+		assert!(!matcher.matches(
+			"spo*",
+			MatchKind::Wildcard,
+			Segmentation::Auto,
+			false,
+			Exclusions::none(),
+			english
+		));
+	}
+
+	#[test]
+	fn highlighted_ranges() {
+		let ranges = |keyword, match_kind, original| {
+			let content = text(original);
+
+			KeywordMatch {
+				content: &content,
+				keyword,
+				match_kind,
+				segmentation: Segmentation::Auto,
+				language: Algorithm::English,
+			}
+			.ranges()
+			.into_iter()
+			.map(|range| &original[range])
+			.collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			ranges("hello", MatchKind::Literal, "Hello, hello!"),
+			["Hello", "hello"]
+		);
+
+		assert_eq!(
+			ranges("deploy*", MatchKind::Wildcard, "the deployment"),
+			["deployment"]
+		);
+
+		assert_eq!(
+			ranges(
+				"outage AND api NOT test",
+				MatchKind::Expression,
+				"api outage"
+			),
+			["outage", "api"]
+		);
+
+		assert_eq!(
+			ranges("kubernetes", MatchKind::Fuzzy, "a kubernets cluster"),
+			["kubernets"]
+		);
+	}
+
+	#[test]
+	fn keyword_match_details() {
+		let original = "the kubernets api is down";
+		let content = text(original);
+
+		let keyword_match = |keyword, match_kind| KeywordMatch {
+			content: &content,
+			keyword,
+			match_kind,
+			segmentation: Segmentation::Auto,
+			language: Algorithm::English,
+		};
+
+		let fuzzy = keyword_match("kubernetes", MatchKind::Fuzzy);
+
+		assert_eq!(fuzzy.description(), "Fuzzy keyword \"kubernetes\"");
+		assert_eq!(
+			fuzzy.details(original),
+			Some(("Fuzzy match", "\"kubernets\"".to_owned()))
+		);
+
+		let expression =
+			keyword_match("down AND (api OR gateway)", MatchKind::Expression);
+
+		assert_eq!(
+			expression.details(original),
+			Some(("Matched terms", "\"down\", \"api\"".to_owned()))
+		);
+
+		assert_eq!(
+			keyword_match("api", MatchKind::Literal).details(original),
+			None
+		);
+	}
+
+	#[test]
+	fn highlighted_content_escaping() {
+		assert_eq!(
+			highlighted_content("a *big* deploy <@123>", iter::once(8..14)),
+			r"a \*big\* **deploy** <@123>"
+		);
+
+		assert_eq!(
+			highlighted_content("deploy deploy", [0..6, 3..13]),
+			"**deploy deploy**"
+		);
+
+		assert_eq!(
+			highlighted_content("see || spoiler ||", iter::once(3..17)),
+			r"see **\|\| spoiler \|\|**"
+		);
+	}
+
+	#[test]
+	fn highlighted_content_excerpt() {
+		let before = "word ".repeat(1000);
+		let after = " text".repeat(1000);
+		let content = format!("{}keyword{}", before, after);
+
+		let excerpt = highlighted_content(
+			&content,
+			iter::once(before.len()..before.len() + 7),
+		);
+
+		assert!(excerpt.chars().count() <= NOTIFICATION_CONTENT_MAX_LEN);
+		assert!(excerpt.starts_with("…word "));
+		assert!(excerpt.ends_with(" text…"));
+		assert!(excerpt.contains("word **keyword** text"));
+
+		let long_match = "x".repeat(5000);
+		let excerpt = highlighted_content(&long_match, iter::once(0..5000));
+
+		assert_eq!(excerpt.chars().count(), NOTIFICATION_CONTENT_MAX_LEN);
+	}
+
+	#[test]
+	fn context_lines_are_trimmed() {
+		assert_eq!(
+			context_lines(
+				vec![("alice", "does this\nbreak *it*?"), ("bob_", "")]
+					.into_iter()
+			),
+			"**alice**: does this break \\*it\\*?\n**bob\\_**: *(no text)*"
+		);
+
+		let long = "word ".repeat(100);
+		let lines =
+			context_lines(iter::repeat_n(("someone", long.as_str()), 10));
+
+		assert!(lines.chars().count() <= EMBED_FIELD_MAX_LEN);
+		assert!(lines.lines().count() < 10);
+		assert!(lines.lines().all(|line| line.ends_with('…')));
+	}
+
+	#[test]
+	fn matcher_agrees_with_content_matches() {
+		let keywords = [
+			"hello",
+			"hello world",
+			"world",
+			"c++",
+			"テスト",
+			"123",
+			"missing",
+			"ＨＥＬＬＯ",
+			"Hello",
+		];
+
+		let contents = [
+			"hello world!",
+			"oh, hello there",
+			"worldly hellos",
+			"i love c++ and テストケース",
+			"<@123> hi",
+			"ＨＥＬＬＯ　ＷＯＲＬＤ",
+			"Hello, hello",
+		];
+
+		let segmentations = [
+			Segmentation::Auto,
+			Segmentation::Words,
+			Segmentation::Substring,
+		];
+
+		for content in contents {
+			let matcher = KeywordMatcher::new(
+				text(content),
+				keywords.iter().flat_map(|&keyword| {
+					[
+						MatcherKeyword::literal(keyword),
+						MatcherKeyword {
+							case_sensitive: true,
+							..MatcherKeyword::literal(keyword)
+						},
+					]
+				}),
+			);
+
+			for keyword in keywords {
+				for segmentation in segmentations {
+					for case_sensitive in [false, true] {
+						assert_eq!(
+							matcher.matches(
+								keyword,
+								MatchKind::Literal,
+								segmentation,
+								case_sensitive,
+								Exclusions::none(),
+								Algorithm::English
+							),
+							content_matches(
+								keyword,
+								MatchKind::Literal,
+								segmentation,
+								case_sensitive,
+								Exclusions::none(),
+								&text(content),
+								Algorithm::English
+							),
+							"{:?} in {:?} ({:?}, case-sensitive: {})",
+							keyword,
+							content,
+							segmentation,
+							case_sensitive,
+						);
+					}
+				}
+			}
+		}
+	}
+
+	/// Compares matching many keywords one at a time with matching them with a
+	/// [`KeywordMatcher`](KeywordMatcher).
+	///
+	/// Run with `cargo test --release bench_matcher -- --ignored --nocapture`.
+	#[test]
+	#[ignore]
+	fn bench_matcher() {
+		use std::time::Instant;
+
+		const KEYWORDS: usize = 2000;
+		const ITERATIONS: usize = 50;
+
+		let keywords = (0..KEYWORDS)
+			.map(|i| format!("keyword{}", i))
+			.collect::<Vec<_>>();
+
+		let content = (0..200)
+			.map(|i| format!("some words keyword{} and more", i * 37))
+			.collect::<Vec<_>>()
+			.join(" ");
+
+		let content = text(&content);
+
+		let start = Instant::now();
+		let mut individual = 0;
+		for _ in 0..ITERATIONS {
+			individual = keywords
+				.iter()
+				.filter(|keyword| {
+					content_matches(
+						keyword,
+						MatchKind::Literal,
+						Segmentation::Auto,
+						false,
+						Exclusions::none(),
+						&content,
+						Algorithm::English,
+					)
+				})
+				.count();
+		}
+		let individual_time = start.elapsed() / ITERATIONS as u32;
+
+		let start = Instant::now();
+		let mut automaton = 0;
+		for _ in 0..ITERATIONS {
+			let matcher = KeywordMatcher::new(
+				content.clone(),
+				keywords
+					.iter()
+					.map(|keyword| MatcherKeyword::literal(keyword)),
+			);
+			automaton = keywords
+				.iter()
+				.filter(|keyword| {
+					matcher.matches(
+						keyword,
+						MatchKind::Literal,
+						Segmentation::Auto,
+						false,
+						Exclusions::none(),
+						Algorithm::English,
+					)
+				})
+				.count();
+		}
+		let automaton_time = start.elapsed() / ITERATIONS as u32;
+
+		assert_eq!(individual, automaton);
+
+		println!(
+			"{} keywords, {} bytes of content: \
+			individual: {:?}, automaton: {:?}",
+			KEYWORDS,
+			content.as_str().len(),
+			individual_time,
+			automaton_time,
+		);
+	}
+
+	#[test]
+	fn regex_match() {
+		assert!(regex_matches(r"PR-\d+", &text("see pr-1234 for details")));
+
+		assert!(regex_matches(
+			r"(deploy|rollback) (prod|staging)",
+			&text("rollback prod now")
+		));
+
+		assert!(!regex_matches(r"PR-\d+", &text("see pr-abc")));
+	}
+
+	#[test]
+	fn regex_match_skips_folding() {
+		let content = NormalizedText::new(
+			"Café",
+			NormalizeOptions {
+				strip_accents: true,
+				confusables: true,
+				..Default::default()
+			},
+		);
+
+		assert!(regex_matches("café", &content));
+		assert!(!regex_matches("cafe", &content));
+	}
+
+	#[test]
+	fn compiled_cache_drops_least_recently_used() {
+		let cache = CompiledCache::new(2);
+		let compile = |source: &str| Ok::<_, ()>(source.to_owned());
+
+		let first = cache.get_or_compile("a", compile).unwrap();
+		cache.get_or_compile("b", compile).unwrap();
+		assert!(Arc::ptr_eq(
+			&first,
+			&cache.get_or_compile("a", compile).unwrap()
+		));
+
+		cache.get_or_compile("c", compile).unwrap();
+
+		let entries = cache.entries.read().unwrap();
+		assert_eq!(entries.len(), 2);
+		assert!(entries.contains_key("a"));
+		assert!(!entries.contains_key("b"));
+		assert!(entries.contains_key("c"));
+	}
+
+	#[test]
+	fn compiled_cache_skips_errors() {
+		let cache = CompiledCache::<String>::new(2);
+
+		assert!(cache.get_or_compile("a", |_| Err(())).is_err());
+		assert!(cache.entries.read().unwrap().is_empty());
+	}
+
+	#[test]
+	fn regex_match_ignores_mentions() {
+		assert!(!regex_matches(
+			r"\d{18}",
+			&text("hey <@!123456789012345678>")
+		));
 	}
 }
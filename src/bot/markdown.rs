@@ -0,0 +1,122 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Finding the parts of a message written with Discord's markdown that users can choose not to
+//! match keywords in.
+
+use std::ops::Range;
+
+use crate::db::Exclusions;
+
+/// Finds the byte ranges of the parts of the text in any of the given exclusions.
+///
+/// Code blocks and inline code are found first, since nothing inside them is formatted; spoilers,
+/// quotes, and links that start inside code aren't included.
+pub fn excluded_spans(text: &str, exclusions: Exclusions) -> Vec<Range<usize>> {
+	if exclusions.is_empty() {
+		return vec![];
+	}
+
+	let code = regex!(r"(?s)```.+?```|``.+?``|`[^`]+`")
+		.find_iter(text)
+		.map(|m| {
+			let exclusion = if m.as_str().starts_with("```") {
+				Exclusions::CODE_BLOCKS
+			} else {
+				Exclusions::INLINE_CODE
+			};
+
+			(exclusion, m.range())
+		})
+		.collect::<Vec<_>>();
+
+	let in_code = |range: &Range<usize>| {
+		code.iter().any(|(_, code)| {
+			code.start <= range.start && range.start < code.end
+		})
+	};
+
+	let spoilers = regex!(r"(?s)\|\|.+?\|\|")
+		.find_iter(text)
+		.map(|m| (Exclusions::SPOILERS, m.range()));
+
+	let quotes = regex!(r"(?m)^>>> (?s:.*)|^> .*$")
+		.find_iter(text)
+		.map(|m| (Exclusions::QUOTES, m.range()));
+
+	let links = regex!(r"https?://[^\s<>)]+")
+		.find_iter(text)
+		.map(|m| (Exclusions::LINKS, m.range()));
+
+	let formatted = spoilers
+		.chain(quotes)
+		.chain(links)
+		.filter(|(_, range)| !in_code(range));
+
+	code.iter()
+		.cloned()
+		.chain(formatted)
+		.filter(|&(exclusion, _)| exclusions.contains(exclusion))
+		.map(|(_, range)| range)
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn excluded(text: &str, exclusions: Exclusions) -> Vec<&str> {
+		excluded_spans(text, exclusions)
+			.into_iter()
+			.map(|range| &text[range])
+			.collect()
+	}
+
+	#[test]
+	fn code() {
+		let text = "run `cargo build` or\n```\ncargo test\n```";
+
+		assert_eq!(excluded(text, Exclusions::INLINE_CODE), ["`cargo build`"]);
+
+		assert_eq!(
+			excluded(text, Exclusions::CODE_BLOCKS),
+			["```\ncargo test\n```"]
+		);
+
+		assert_eq!(
+			excluded("``a ` b``", Exclusions::INLINE_CODE),
+			["``a ` b``"]
+		);
+
+		assert!(excluded(text, Exclusions::SPOILERS).is_empty());
+	}
+
+	#[test]
+	fn quotes() {
+		let text = "> quoted\nnot quoted\n>>> quoted\nto the end";
+
+		assert_eq!(
+			excluded(text, Exclusions::QUOTES),
+			["> quoted", ">>> quoted\nto the end"]
+		);
+
+		assert!(excluded("a > b", Exclusions::QUOTES).is_empty());
+	}
+
+	#[test]
+	fn spoilers_and_links() {
+		let text =
+			"||the ending|| at [docs](https://example.com/a) <https://b.com>";
+
+		assert_eq!(excluded(text, Exclusions::SPOILERS), ["||the ending||"]);
+
+		assert_eq!(
+			excluded(text, Exclusions::LINKS),
+			["https://example.com/a", "https://b.com"]
+		);
+
+		assert!(
+			excluded("`||not a spoiler||`", Exclusions::SPOILERS).is_empty()
+		);
+	}
+}
@@ -10,13 +10,36 @@ mod util;
 use util::{error, question};
 
 mod highlighting;
+use highlighting::{KeywordMatcher, MatcherKeyword};
+
+mod digests;
+
+mod cooldowns;
+
+mod activity;
+
+mod scheduler;
+
+mod dispatcher;
+
+mod actions;
+
+mod expression;
+
+mod markdown;
+
+mod normalization;
+use normalization::{NormalizeOptions, NormalizedText};
 
 use crate::{
-	db::{Ignore, Keyword, Notification, UserState},
-	global::{bot_mention, bot_nick_mention, init_mentions},
-	log_discord_error,
-	monitoring::Timer,
-	regex,
+	db::{
+		ActivitySignals, Ignore, Keyword, Notification, PendingNotification,
+		ScheduledNotification, UserSettings, UserState,
+	},
+	global::{
+		bot_mention, bot_nick_mention, init_mentions, INDEX_CHECK_INTERVAL,
+	},
+	monitoring::{record_index_mismatch, Timer},
 	settings::settings,
 };
 
@@ -24,15 +47,23 @@ use anyhow::{Context as _, Result};
 use serenity::{
 	client::{bridge::gateway::GatewayIntents, Client, Context, EventHandler},
 	model::{
-		channel::Message,
-		event::MessageUpdateEvent,
+		channel::{Message, Reaction},
+		event::{MessageUpdateEvent, TypingStartEvent},
 		gateway::{Activity, Ready},
 		id::{ChannelId, GuildId, MessageId, UserId},
+		voice::VoiceState,
 	},
 };
 use tokio::task;
 
-use std::{collections::HashMap, convert::TryInto};
+use std::{
+	collections::{HashMap, HashSet},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Number of messages checked for keywords, used to occasionally compare the in-memory index with
+/// the database.
+static CHECKED_MESSAGES: AtomicUsize = AtomicUsize::new(0);
 
 /// Type to serve as an event handler.
 struct Handler;
@@ -49,6 +80,14 @@ impl EventHandler for Handler {
 			return;
 		}
 
+		if let Some(guild_id) = message.guild_id {
+			activity::record(
+				message.author.id,
+				guild_id,
+				ActivitySignals::MESSAGES,
+			);
+		}
+
 		let content = message.content.as_str();
 
 		let result = match content
@@ -80,7 +119,7 @@ impl EventHandler for Handler {
 					}
 					.await
 				} else {
-					handle_keywords(&ctx, &message)
+					handle_keywords(&ctx, &message, false)
 						.await
 						.context("Failed to handle keywords")
 				}
@@ -92,7 +131,8 @@ impl EventHandler for Handler {
 		}
 	}
 
-	/// Deletes sent notifications if their original messages were deleted.
+	/// Deletes sent notifications if their original messages were deleted, and removes them from
+	/// pending digests.
 	async fn message_delete(
 		&self,
 		ctx: Context,
@@ -106,6 +146,20 @@ impl EventHandler for Handler {
 			return;
 		}
 
+		if let Err(e) =
+			PendingNotification::delete_notifications_of_message(message_id)
+				.await
+		{
+			log_discord_error!(in channel_id, deleted message_id, e);
+		}
+
+		if let Err(e) =
+			ScheduledNotification::delete_notifications_of_message(message_id)
+				.await
+		{
+			log_discord_error!(in channel_id, deleted message_id, e);
+		}
+
 		let notifications =
 			match Notification::notifications_of_message(message_id).await {
 				Ok(n) => n,
@@ -135,11 +189,16 @@ impl EventHandler for Handler {
 		}
 	}
 
-	/// Edits notifications if their original messages are edited.
+	/// Edits notifications if their original messages are edited, and notifies users whose keywords
+	/// newly match the edited message.
 	///
 	/// Edits the content of a notification to reflect the new content of the original message if
-	/// the original message still contains the keyword the notification was created for. Deletes
-	/// the notification if the new content no longer contains the keyword.
+	/// the original message still contains any of the keywords the notification was created for,
+	/// listing only those keywords. Deletes the notification if the new content no longer contains
+	/// any of them.
+	///
+	/// If the message's content was edited, its keywords are also handled like those of a new
+	/// message, skipping users who were already notified about it.
 	async fn message_update(
 		&self,
 		ctx: Context,
@@ -161,7 +220,10 @@ impl EventHandler for Handler {
 				}
 			};
 
-		if notifications.is_empty() {
+		let content_edited = event.content.is_some()
+			&& event.author.as_ref().is_some_and(|author| !author.bot);
+
+		if notifications.is_empty() && !content_edited {
 			return;
 		}
 
@@ -181,28 +243,72 @@ impl EventHandler for Handler {
 			}
 		};
 
-		highlighting::update_sent_notifications(
-			&ctx,
-			event.channel_id,
-			guild_id,
-			message,
-			notifications,
-		)
-		.await;
+		if content_edited {
+			if let Err(e) = handle_keywords(&ctx, &message, true).await {
+				log_discord_error!(in event.channel_id, edited event.id, e);
+			}
+		}
+
+		if !notifications.is_empty() {
+			highlighting::update_sent_notifications(
+				&ctx,
+				event.channel_id,
+				guild_id,
+				message,
+				notifications,
+			)
+			.await;
+		}
+	}
+
+	/// Takes actions users choose by reacting to their notifications.
+	async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+		if let Err(e) = actions::handle_reaction(&ctx, &reaction).await {
+			log::error!(
+				"Error handling reaction to {} in {}:\n{:?}",
+				reaction.message_id,
+				reaction.channel_id,
+				e
+			);
+		}
+	}
+
+	/// Records that a user started typing in a server, for users who count that as activity.
+	async fn typing_start(&self, _: Context, event: TypingStartEvent) {
+		if let Some(guild_id) = event.guild_id {
+			activity::record(event.user_id, guild_id, ActivitySignals::TYPING);
+		}
+	}
+
+	/// Records that a user's voice state changed in a server, for users who count that as
+	/// activity.
+	async fn voice_state_update(
+		&self,
+		_: Context,
+		guild_id: Option<GuildId>,
+		_: Option<VoiceState>,
+		new: VoiceState,
+	) {
+		if let Some(guild_id) = guild_id.or(new.guild_id) {
+			activity::record(new.user_id, guild_id, ActivitySignals::VOICE);
+		}
 	}
 
 	/// Runs minor setup for when the bot starts.
 	///
-	/// This calls [`init_mentions`](crate::global::init_mentions), sets the bot's status, and
-	/// logs a ready message.
+	/// This calls [`init_mentions`](crate::global::init_mentions), sets the bot's status, starts
+	/// sending digests, and logs a ready message.
 	async fn ready(&self, ctx: Context, ready: Ready) {
 		init_mentions(ready.user.id);
 
 		let username = ctx.cache.current_user_field(|u| u.name.clone()).await;
 
-		ctx.set_activity(Activity::listening(&format!("@{} help", username)))
+		ctx.set_activity(Activity::listening(format!("@{} help", username)))
 			.await;
 
+		digests::start_digest_cycle(ctx.clone());
+		scheduler::start_scheduler(ctx);
+
 		log::info!("Ready to highlight!");
 	}
 }
@@ -317,6 +423,20 @@ async fn handle_command(
 			"opt-in" => {
 				spawn(async move { opt_in(&ctx, &message, &args).await })
 			}
+			"quiet" => spawn(async move { quiet(&ctx, &message, &args).await }),
+			"unquiet" => {
+				spawn(async move { unquiet(&ctx, &message, &args).await })
+			}
+			"quiet-hours" => {
+				spawn(async move { quiet_hours(&ctx, &message, &args).await })
+			}
+			"set" => spawn(async move { set(&ctx, &message, &args).await }),
+			"settings" => {
+				spawn(async move { list_settings(&ctx, &message, &args).await })
+			}
+			"history" => {
+				spawn(async move { history(&ctx, &message, &args).await })
+			}
 			"help" => spawn(async move { help(&ctx, &message, &args).await }),
 			"ping" => spawn(async move { ping(&ctx, &message, &args).await }),
 			"about" => spawn(async move { about(&ctx, &message, &args).await }),
@@ -339,12 +459,23 @@ async fn handle_command(
 
 /// Handles any keywords present in a message.
 ///
-/// This function queries for any keywords that could be relevant to the sent message with
-/// [`get_relevant_keywords`](Keyword::get_relevant_keywords), collects [`Ignore`](Ignore)s for any
-/// users with those keywords. It uses (`should_notify_keyword`)[highlighting::should_notify_keyword]
-/// to determine if there is a keyword that should be highlighted, then calls
-/// (`notify_keyword`)[highlighting::notify_keyword].
-async fn handle_keywords(ctx: &Context, message: &Message) -> Result<()> {
+/// This function looks up any keywords that could be relevant to the sent message with
+/// [`indexed_relevant_keywords`](Keyword::indexed_relevant_keywords), collects
+/// [`Ignore`](Ignore)s and [`UserSettings`](UserSettings) for any users with those keywords from
+/// the in-memory index, and normalizes the message
+/// content with each user's options. For each set of options, a
+/// [`KeywordMatcher`](highlighting::KeywordMatcher) finds all of the literal keywords and ignored
+/// phrases of those users in one pass. It uses
+/// (`should_notify_keyword`)[highlighting::should_notify_keyword] to determine if there is a
+/// keyword that should be highlighted, then calls (`notify_keyword`)[highlighting::notify_keyword].
+///
+/// If `edited` is true, the message was edited, and users who were already notified about it are
+/// skipped.
+async fn handle_keywords(
+	ctx: &Context,
+	message: &Message,
+	edited: bool,
+) -> Result<()> {
 	let _timer = Timer::notification("create");
 	let guild_id = match message.guild_id {
 		Some(id) => id,
@@ -353,66 +484,160 @@ async fn handle_keywords(ctx: &Context, message: &Message) -> Result<()> {
 
 	let channel_id = message.channel_id;
 
-	let lowercase_content = message.content.to_lowercase();
+	let author_id = message.author.id;
+
+	if CHECKED_MESSAGES.fetch_add(1, Ordering::Relaxed) % INDEX_CHECK_INTERVAL
+		== 0
+	{
+		task::spawn(async move {
+			match Keyword::check_index(guild_id, channel_id, author_id).await {
+				Ok(true) => {}
+				Ok(false) => {
+					record_index_mismatch();
+					log::warn!(
+						"Index disagreed with the database on keywords for a \
+						message by {} in {}",
+						author_id,
+						channel_id
+					);
+				}
+				Err(e) => log::error!("Failed to check index: {:?}", e),
+			}
+		});
+	}
+
+	let mut keywords =
+		Keyword::indexed_relevant_keywords(guild_id, channel_id, author_id);
 
-	let keywords =
-		Keyword::get_relevant_keywords(guild_id, channel_id, message.author.id)
-			.await?;
+	if edited {
+		let notified = notified_users(message.id).await?;
+
+		keywords.retain(|keyword| !notified.contains(&keyword.user_id));
+	}
 
 	let mut ignores_by_user = HashMap::new();
 
+	let mut settings_by_user = HashMap::new();
+
+	for keyword in &keywords {
+		ignores_by_user
+			.entry(keyword.user_id)
+			.or_insert_with_key(|&user_id| {
+				Ignore::indexed_user_guild_ignores(user_id, guild_id)
+			});
+
+		settings_by_user.entry(keyword.user_id).or_insert_with_key(
+			|&user_id| UserSettings::indexed_user_settings(user_id),
+		);
+	}
+
+	let mut keywords_by_options = HashMap::new();
+
+	for keyword in &keywords {
+		keywords_by_options
+			.entry(NormalizeOptions::of(&settings_by_user[&keyword.user_id]))
+			.or_insert_with(Vec::new)
+			.push(MatcherKeyword::from(keyword));
+	}
+
+	for (user_id, ignores) in &ignores_by_user {
+		keywords_by_options
+			.entry(NormalizeOptions::of(&settings_by_user[user_id]))
+			.or_insert_with(Vec::new)
+			.extend(ignores.iter().map(MatcherKeyword::from));
+	}
+
+	let matchers_by_options = keywords_by_options
+		.into_iter()
+		.map(|(options, keywords)| {
+			let content = NormalizedText::new(&message.content, options);
+			(options, KeywordMatcher::new(content, keywords))
+		})
+		.collect::<HashMap<_, _>>();
+
+	let mut matched_by_user = HashMap::<_, Vec<_>>::new();
+
 	for keyword in keywords {
-		let ignores = match ignores_by_user.get(&keyword.user_id) {
-			Some(ignores) => ignores,
-			None => {
-				let user_ignores = Ignore::user_guild_ignores(
-					UserId(keyword.user_id.try_into().unwrap()),
-					guild_id,
-				)
-				.await?;
-				ignores_by_user
-					.entry(keyword.user_id)
-					.or_insert(user_ignores)
-			}
-		};
+		let ignores = &ignores_by_user[&keyword.user_id];
+
+		let user_settings = &settings_by_user[&keyword.user_id];
+
+		let matcher =
+			&matchers_by_options[&NormalizeOptions::of(user_settings)];
 
 		if highlighting::should_notify_keyword(
 			ctx,
 			message,
-			&lowercase_content,
+			matcher,
 			&keyword,
-			&ignores,
+			ignores,
+			highlighting::stem_language(user_settings),
 		)
 		.await?
 		{
-			let ctx = ctx.clone();
-			task::spawn(highlighting::notify_keyword(
-				ctx,
-				message.clone(),
-				keyword,
-				ignores.clone(),
-				guild_id,
-			));
+			matched_by_user
+				.entry(keyword.user_id)
+				.or_default()
+				.push(keyword);
 		}
 	}
 
+	for (user_id, keywords) in matched_by_user {
+		let ctx = ctx.clone();
+		task::spawn(highlighting::notify_keywords(
+			ctx,
+			message.clone(),
+			keywords,
+			ignores_by_user[&user_id].clone(),
+			settings_by_user[&user_id].clone(),
+			guild_id,
+			edited,
+		));
+	}
+
 	Ok(())
 }
 
+/// Gets the IDs of the users who were notified about the message with the given ID, or will be.
+///
+/// This includes users with [`PendingNotification`](PendingNotification)s and
+/// [`ScheduledNotification`](ScheduledNotification)s about the message.
+async fn notified_users(message_id: MessageId) -> Result<HashSet<UserId>> {
+	let mut users = Notification::notifications_of_message(message_id)
+		.await?
+		.into_iter()
+		.map(|notification| notification.user_id)
+		.collect::<HashSet<_>>();
+
+	users.extend(PendingNotification::users_of_message(message_id).await?);
+	users.extend(ScheduledNotification::users_of_message(message_id).await?);
+
+	Ok(users)
+}
+
 pub async fn init() {
+	let mut intents = GatewayIntents::DIRECT_MESSAGES
+		| GatewayIntents::DIRECT_MESSAGE_REACTIONS
+		| GatewayIntents::GUILD_MESSAGE_REACTIONS
+		| GatewayIntents::GUILD_MESSAGES
+		| GatewayIntents::GUILD_MESSAGE_TYPING
+		| GatewayIntents::GUILD_VOICE_STATES
+		| GatewayIntents::GUILDS
+		| GatewayIntents::GUILD_MEMBERS;
+
+	if settings().bot.presences {
+		intents |= GatewayIntents::GUILD_PRESENCES;
+	}
+
 	let mut client = Client::builder(&settings().bot.token)
 		.event_handler(Handler)
-		.intents(
-			GatewayIntents::DIRECT_MESSAGES
-				| GatewayIntents::GUILD_MESSAGE_REACTIONS
-				| GatewayIntents::GUILD_MESSAGES
-				| GatewayIntents::GUILDS
-				| GatewayIntents::GUILD_MEMBERS,
-		)
+		.intents(intents)
 		.await
 		.expect("Failed to create client");
 
 	responses::init(&client).await;
 
+	scheduler::init();
+
 	client.start().await.expect("Failed to run client");
 }
@@ -0,0 +1,320 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Normalization of message content and keywords before they're matched.
+
+use caseless::default_case_fold_str;
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::skeleton;
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::ops::Range;
+
+use super::{markdown::excluded_spans, util::MENTION_REGEX};
+use crate::db::{Exclusions, UserSettings};
+
+/// Optional steps of normalization, chosen by each user (or for each keyword, in the case of
+/// `case_sensitive`, and in addition to the user's for `exclusions`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NormalizeOptions {
+	/// Whether to keep differences in case, instead of case folding.
+	pub case_sensitive: bool,
+	/// Whether to remove accents and other diacritics, so "café" becomes "cafe".
+	pub strip_accents: bool,
+	/// Whether to replace characters with the characters they can be confused with, so "раypal"
+	/// written with Cyrillic letters becomes "paypal".
+	pub confusables: bool,
+	/// Parts of the text written with markdown that keywords shouldn't match in.
+	pub exclusions: Exclusions,
+}
+
+impl NormalizeOptions {
+	/// Gets the normalization options the user with the given settings chose.
+	pub fn of(settings: &UserSettings) -> Self {
+		Self {
+			case_sensitive: false,
+			strip_accents: settings.strip_accents,
+			confusables: settings.match_confusables,
+			exclusions: settings.exclusions,
+		}
+	}
+}
+
+/// Text after normalization, which remembers where each part of it came from in the original
+/// text.
+///
+/// Text is always normalized with NFKC, so full-width letters and other compatibility characters
+/// don't matter, and case folded unless it's case-sensitive. Accents and confusable characters are
+/// also normalized according to the [`NormalizeOptions`](NormalizeOptions) used.
+#[derive(Debug, Clone)]
+pub struct NormalizedText {
+	original: String,
+	text: String,
+	/// For each byte of `text`, the start of the original text it was normalized from.
+	starts: Vec<usize>,
+	/// For each byte of `text`, the end of the original text it was normalized from.
+	ends: Vec<usize>,
+	/// The byte ranges of user, role, channel, and emoji mentions in the original text.
+	mentions: Vec<Range<usize>>,
+	/// The byte ranges of the parts of the original text in `options.exclusions`.
+	excluded: Vec<Range<usize>>,
+	options: NormalizeOptions,
+}
+
+impl NormalizedText {
+	/// Normalizes the given text.
+	///
+	/// Each grapheme cluster is normalized separately, so ranges of the normalized text can be
+	/// mapped back to whole grapheme clusters of the original text.
+	pub fn new(original: &str, options: NormalizeOptions) -> Self {
+		let mut text = String::with_capacity(original.len());
+		let mut starts = Vec::with_capacity(original.len());
+		let mut ends = Vec::with_capacity(original.len());
+
+		for (start, grapheme) in original.grapheme_indices(true) {
+			normalize_into(grapheme, options, &mut text);
+
+			starts.resize(text.len(), start);
+			ends.resize(text.len(), start + grapheme.len());
+		}
+
+		let mentions = MENTION_REGEX
+			.find_iter(original)
+			.map(|m| m.range())
+			.collect();
+
+		let excluded = excluded_spans(original, options.exclusions);
+
+		Self {
+			original: original.to_owned(),
+			text,
+			starts,
+			ends,
+			mentions,
+			excluded,
+			options,
+		}
+	}
+
+	/// Normalizes a keyword or ignored phrase with the same options this text was normalized
+	/// with.
+	pub fn normalize_keyword(&self, keyword: &str) -> String {
+		let mut normalized = String::with_capacity(keyword.len());
+
+		for grapheme in keyword.graphemes(true) {
+			normalize_into(grapheme, self.options, &mut normalized);
+		}
+
+		normalized
+	}
+
+	/// Normalizes the original text again, keeping differences in case.
+	pub fn preserving_case(&self) -> Self {
+		Self::new(
+			&self.original,
+			NormalizeOptions {
+				case_sensitive: true,
+				..self.options
+			},
+		)
+	}
+
+	/// Excludes more parts of the original text, in addition to the ones already excluded.
+	pub fn with_exclusions(&self, exclusions: Exclusions) -> Self {
+		let exclusions = self.options.exclusions.union(exclusions);
+
+		Self {
+			excluded: excluded_spans(&self.original, exclusions),
+			options: NormalizeOptions {
+				exclusions,
+				..self.options
+			},
+			..self.clone()
+		}
+	}
+
+	/// Checks if differences in case were kept when normalizing.
+	pub fn is_case_sensitive(&self) -> bool {
+		self.options.case_sensitive
+	}
+
+	/// Checks if all of the given parts of the original text are already excluded.
+	pub fn excludes(&self, exclusions: Exclusions) -> bool {
+		self.options.exclusions.contains(exclusions)
+	}
+
+	/// Gets the normalized text.
+	pub fn as_str(&self) -> &str {
+		&self.text
+	}
+
+	/// Gets the original text, before normalization.
+	pub fn original(&self) -> &str {
+		&self.original
+	}
+
+	/// Gets the range of the original text that the given range of the normalized text came from.
+	pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+		let start = self
+			.starts
+			.get(range.start)
+			.copied()
+			.unwrap_or_else(|| self.ends.last().copied().unwrap_or(0));
+
+		let end = match range.end.checked_sub(1) {
+			Some(last) if range.end > range.start => self.ends[last],
+			_ => start,
+		};
+
+		start..end
+	}
+
+	/// Checks if the given range of the normalized text overlaps with a user, role, channel, or
+	/// emoji mention in the original text, or with an excluded part of it.
+	pub fn overlaps_with_excluded(&self, range: Range<usize>) -> bool {
+		self.original_overlaps_with_excluded(self.original_range(range))
+	}
+
+	/// Checks if the given range of the original text overlaps with a user, role, channel, or
+	/// emoji mention, or with an excluded part of it.
+	pub fn original_overlaps_with_excluded(&self, range: Range<usize>) -> bool {
+		self.mentions.iter().any(|mention| {
+			range.start <= mention.end && range.end >= mention.start
+		}) || self.excluded.iter().any(|excluded| {
+			range.start < excluded.end && range.end > excluded.start
+		})
+	}
+}
+
+/// Normalizes one grapheme cluster, appending the result to `output`.
+fn normalize_into(
+	grapheme: &str,
+	options: NormalizeOptions,
+	output: &mut String,
+) {
+	let mut normalized = grapheme.nfkc().collect::<String>();
+
+	if !options.case_sensitive {
+		normalized = default_case_fold_str(&normalized).nfkc().collect();
+	}
+
+	if options.strip_accents {
+		normalized = normalized
+			.nfd()
+			.filter(|&c| !is_diacritic(c))
+			.nfc()
+			.collect();
+	}
+
+	if options.confusables {
+		normalized = skeleton(&normalized).collect();
+	}
+
+	output.push_str(&normalized);
+}
+
+/// Checks if the character is a combining diacritical mark, like the accent in "é" after
+/// decomposition.
+///
+/// Other combining marks, like vowel signs in many South and Southeast Asian scripts, are
+/// necessary to spell words and aren't considered diacritics.
+fn is_diacritic(c: char) -> bool {
+	matches!(
+		c,
+		'\u{0300}'..='\u{036F}'
+			| '\u{1AB0}'..='\u{1AFF}'
+			| '\u{1DC0}'..='\u{1DFF}'
+			| '\u{20D0}'..='\u{20FF}'
+			| '\u{FE20}'..='\u{FE2F}'
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn case_and_width() {
+		let text = NormalizedText::new("ＨＥＬＬＯ Straße", Default::default());
+
+		assert_eq!(text.as_str(), "hello strasse");
+	}
+
+	#[test]
+	fn case_sensitive() {
+		let options = NormalizeOptions {
+			case_sensitive: true,
+			..Default::default()
+		};
+
+		let text = NormalizedText::new("ＩＣＥ ice", options);
+
+		assert_eq!(text.as_str(), "ICE ice");
+	}
+
+	#[test]
+	fn strip_accents() {
+		let options = NormalizeOptions {
+			strip_accents: true,
+			..Default::default()
+		};
+
+		assert_eq!(NormalizedText::new("Café", options).as_str(), "cafe");
+
+		assert_eq!(
+			NormalizedText::new("Cafe\u{301}", Default::default()).as_str(),
+			"café"
+		);
+	}
+
+	#[test]
+	fn confusables() {
+		let options = NormalizeOptions {
+			confusables: true,
+			..Default::default()
+		};
+
+		let text = NormalizedText::new("раypal", options);
+
+		assert_eq!(text.as_str(), text.normalize_keyword("paypal"));
+	}
+
+	#[test]
+	fn original_ranges() {
+		let original = "ﬁle <@123>";
+		let text = NormalizedText::new(original, Default::default());
+
+		assert_eq!(text.as_str(), "file <@123>");
+
+		assert_eq!(&original[text.original_range(0..4)], "ﬁle");
+
+		assert!(!text.overlaps_with_excluded(0..4));
+
+		assert!(text.overlaps_with_excluded(7..10));
+
+		assert!(!text.original_overlaps_with_excluded(0..3));
+		assert!(text.original_overlaps_with_excluded(7..10));
+	}
+
+	#[test]
+	fn exclusions() {
+		let original = "see `build` ||build||";
+		let text = NormalizedText::new(
+			original,
+			NormalizeOptions {
+				exclusions: Exclusions::INLINE_CODE,
+				..Default::default()
+			},
+		);
+
+		assert!(text.overlaps_with_excluded(5..10));
+		assert!(!text.overlaps_with_excluded(14..19));
+		assert!(!text.overlaps_with_excluded(0..3));
+
+		let text = text.with_exclusions(Exclusions::SPOILERS);
+
+		assert!(text.excludes(Exclusions::INLINE_CODE));
+		assert!(text.overlaps_with_excluded(5..10));
+		assert!(text.overlaps_with_excluded(14..19));
+	}
+}
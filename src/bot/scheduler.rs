@@ -0,0 +1,242 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Resuming notifications that were waiting when the bot stopped, and retrying notifications that
+//! failed to send.
+//!
+//! Notifications that fail to send too many times are marked dead, and counted in the
+//! `dead_notifications` metric.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::OnceCell;
+use serenity::client::Context;
+use tokio::{task, time::interval};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+	bot::highlighting::{
+		fetch_message, seen_during_patience, send_keyword_notification,
+	},
+	db::{Ignore, Keyword, ScheduledNotification, UserSettings},
+	global::{DEAD_NOTIFICATION_RETENTION_DAYS, NOTIFICATION_CHECK_INTERVAL},
+	monitoring::set_dead_notifications,
+	settings::settings,
+};
+
+/// Whether the scheduler has been started, so it's only started once if the bot reconnects.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Due time before which unattempted notifications were scheduled by an earlier run of the bot.
+static RESUME_BEFORE: OnceCell<DateTime<Utc>> = OnceCell::new();
+
+/// Records when notifications scheduled by this run of the bot are due after.
+///
+/// This should be called before the bot connects, since notifications are scheduled as soon as
+/// messages are received, possibly before the scheduler is started.
+pub fn init() {
+	let patience = Duration::from_std(settings().behavior.patience)
+		.expect("Patience duration out of range");
+
+	RESUME_BEFORE.set(Utc::now() + patience).unwrap();
+}
+
+/// Starts the scheduler.
+///
+/// Notifications that hadn't been attempted yet when the bot last stopped are resumed, and
+/// notifications that are due to be retried are retried every
+/// [`NOTIFICATION_CHECK_INTERVAL`](NOTIFICATION_CHECK_INTERVAL). Dead notifications are removed
+/// after [`DEAD_NOTIFICATION_RETENTION_DAYS`](DEAD_NOTIFICATION_RETENTION_DAYS).
+pub fn start_scheduler(ctx: Context) {
+	if STARTED.swap(true, Ordering::SeqCst) {
+		return;
+	}
+
+	let due_before = *RESUME_BEFORE
+		.get()
+		.expect("Scheduler was started before it was initialized");
+
+	task::spawn(resume_all(ctx.clone(), due_before));
+
+	task::spawn(async move {
+		let mut interval = interval(NOTIFICATION_CHECK_INTERVAL);
+
+		loop {
+			interval.tick().await;
+
+			retry_due(&ctx).await;
+
+			let dead_before =
+				Utc::now() - Duration::days(DEAD_NOTIFICATION_RETENTION_DAYS);
+
+			if let Err(e) =
+				ScheduledNotification::delete_dead_before(dead_before).await
+			{
+				log::error!("Failed to remove dead notifications: {:?}", e);
+			}
+
+			match ScheduledNotification::dead_count().await {
+				Ok(count) => set_dead_notifications(count),
+				Err(e) => {
+					log::error!("Failed to count dead notifications: {:?}", e)
+				}
+			}
+		}
+	});
+}
+
+/// Records the result of attempting to send a scheduled notification.
+///
+/// If it was sent, or didn't need to be, it's removed from the DB. Otherwise, the error is logged
+/// and it's scheduled to be retried, or marked dead if it's failed too many times.
+pub async fn finish(mut scheduled: ScheduledNotification, result: Result<()>) {
+	let result = match result {
+		Ok(()) => scheduled.delete().await,
+		Err(e) => {
+			log_discord_error!(in scheduled.channel_id, by scheduled.user_id, e);
+
+			scheduled.fail(Utc::now());
+
+			if scheduled.dead {
+				log::error!(
+					"Giving up on notifying {} about message {} after {} attempts",
+					scheduled.user_id,
+					scheduled.message_id,
+					scheduled.attempts
+				);
+			}
+
+			scheduled.insert().await
+		}
+	};
+
+	if let Err(e) = result {
+		log::error!("Failed to update scheduled notification: {:?}", e);
+	}
+}
+
+/// Resumes every notification that was waiting for the user's patience duration when the bot
+/// stopped, due before `due_before`.
+async fn resume_all(ctx: Context, due_before: DateTime<Utc>) {
+	let notifications =
+		match ScheduledNotification::unattempted(due_before).await {
+			Ok(notifications) => notifications,
+			Err(e) => {
+				log::error!("Failed to fetch scheduled notifications: {:?}", e);
+				return;
+			}
+		};
+
+	for scheduled in notifications {
+		task::spawn(resume(ctx.clone(), scheduled));
+	}
+}
+
+/// Resumes a notification that was waiting for the user's patience duration when the bot stopped.
+///
+/// This waits for the rest of the patience duration, then sends the notification unless the user
+/// seems to have seen the message, including by sending a message in the channel while the bot was
+/// stopped.
+async fn resume(ctx: Context, scheduled: ScheduledNotification) {
+	let user_settings = UserSettings::indexed_user_settings(scheduled.user_id);
+
+	let remaining =
+		(scheduled.due_at - Utc::now()).to_std().unwrap_or_default();
+
+	let seen = seen_during_patience(
+		&ctx,
+		scheduled.channel_id,
+		scheduled.user_id,
+		scheduled.guild_id,
+		user_settings.activity_signals,
+		remaining,
+	)
+	.await || replied_while_stopped(&ctx, scheduled).await;
+
+	let result = if seen {
+		Ok(())
+	} else {
+		send_scheduled(&ctx, scheduled).await
+	};
+
+	finish(scheduled, result).await;
+}
+
+/// Checks if the user of a scheduled notification sent a message in its channel after the message
+/// it's about.
+///
+/// Only the 100 messages after the message are checked. If they can't be fetched, this returns
+/// `false`.
+async fn replied_while_stopped(
+	ctx: &Context,
+	scheduled: ScheduledNotification,
+) -> bool {
+	let messages = scheduled
+		.channel_id
+		.messages(ctx, |r| r.after(scheduled.message_id).limit(100))
+		.await;
+
+	matches!(
+		messages,
+		Ok(messages) if messages.iter().any(|m| m.author.id == scheduled.user_id)
+	)
+}
+
+/// Retries every notification that's due to be retried.
+async fn retry_due(ctx: &Context) {
+	let notifications =
+		match ScheduledNotification::due_retries(Utc::now()).await {
+			Ok(notifications) => notifications,
+			Err(e) => {
+				log::error!("Failed to fetch notifications to retry: {:?}", e);
+				return;
+			}
+		};
+
+	for scheduled in notifications {
+		let result = send_scheduled(ctx, scheduled).await;
+
+		finish(scheduled, result).await;
+	}
+}
+
+/// Sends a scheduled notification, checking the user's current keywords against the message.
+async fn send_scheduled(
+	ctx: &Context,
+	scheduled: ScheduledNotification,
+) -> Result<()> {
+	let message =
+		match fetch_message(ctx, scheduled.channel_id, scheduled.message_id)
+			.await?
+		{
+			Some(m) => m,
+			None => return Ok(()),
+		};
+
+	let keywords = Keyword::indexed_relevant_keywords(
+		scheduled.guild_id,
+		scheduled.channel_id,
+		message.author.id,
+	)
+	.into_iter()
+	.filter(|keyword| keyword.user_id == scheduled.user_id)
+	.collect();
+
+	let ignores = Ignore::indexed_user_guild_ignores(
+		scheduled.user_id,
+		scheduled.guild_id,
+	);
+
+	let user_settings = UserSettings::indexed_user_settings(scheduled.user_id);
+
+	send_keyword_notification(
+		ctx,
+		&message,
+		keywords,
+		&ignores,
+		&user_settings,
+		&scheduled,
+	)
+	.await
+}
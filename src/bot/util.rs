@@ -4,6 +4,7 @@
 //! Miscellaneous utility functions and macros.
 
 use anyhow::{Context as _, Result};
+use chrono_tz::{Tz, TZ_VARIANTS};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serenity::{
@@ -13,6 +14,7 @@ use serenity::{
 		channel::{GuildChannel, Message},
 		guild::{Guild, PartialGuild},
 		id::UserId,
+		Permissions,
 	},
 	prelude::HttpError,
 	Error as SerenityError,
@@ -20,6 +22,7 @@ use serenity::{
 use std::fmt::Display;
 
 use super::responses::insert_command_response;
+use crate::db::UserSettings;
 
 /// Logs an error that happened handling a command or keyword in Discord.
 ///
@@ -73,9 +76,9 @@ macro_rules! log_discord_error {
 #[macro_export]
 macro_rules! regex {
 	($re:literal $(,)?) => {{
-		static RE: once_cell::sync::OnceCell<regex::Regex> =
-			once_cell::sync::OnceCell::new();
-		RE.get_or_init(|| regex::Regex::new($re).unwrap())
+		static RE: once_cell::sync::Lazy<regex::Regex> =
+			once_cell::sync::Lazy::new(|| regex::Regex::new($re).unwrap());
+		&*RE
 	}};
 }
 
@@ -86,6 +89,34 @@ macro_rules! regex {
 pub static MD_SYMBOL_REGEX: Lazy<Regex, fn() -> Regex> =
 	Lazy::new(|| Regex::new(r"[_*()\[\]~`]").unwrap());
 
+/// Regex for user, role, channel, and custom emoji mentions.
+pub static MENTION_REGEX: Lazy<Regex, fn() -> Regex> =
+	Lazy::new(|| Regex::new(r"<(@!?|&|#|a?:[a-zA-Z0-9_]*:)[0-9]+>").unwrap());
+
+/// Escapes markdown in text so it's displayed as written.
+///
+/// Symbols matched by [`MD_SYMBOL_REGEX`](MD_SYMBOL_REGEX) are escaped, along with backslashes and
+/// `|` (used for spoilers). Mentions and custom emoji are left intact so they're still displayed.
+pub fn escape_markdown(text: &str) -> String {
+	let escape = |text: &str| {
+		let text = text.replace('\\', r"\\").replace('|', r"\|");
+		MD_SYMBOL_REGEX.replace_all(&text, r"\$0").into_owned()
+	};
+
+	let mut escaped = String::with_capacity(text.len());
+	let mut last = 0;
+
+	for mention in MENTION_REGEX.find_iter(text) {
+		escaped.push_str(&escape(&text[last..mention.start()]));
+		escaped.push_str(mention.as_str());
+		last = mention.end();
+	}
+
+	escaped.push_str(&escape(&text[last..]));
+
+	escaped
+}
+
 /// Reacts to a message with a ✅ emoji.
 pub async fn success(ctx: &Context, message: &Message) -> Result<()> {
 	message
@@ -135,6 +166,19 @@ pub async fn user_can_read_channel(
 	channel: &GuildChannel,
 	user_id: UserId,
 ) -> Result<Option<bool>> {
+	let permissions = user_permissions_in(ctx, channel, user_id).await?;
+
+	Ok(permissions.map(|permissions| permissions.read_messages()))
+}
+
+/// Gets the permissions of a user with the given ID in the provided `GuildChannel`.
+///
+/// Returns `None` if the user isn't a member of the channel's guild.
+pub async fn user_permissions_in(
+	ctx: &Context,
+	channel: &GuildChannel,
+	user_id: UserId,
+) -> Result<Option<Permissions>> {
 	#[allow(clippy::large_enum_variant)]
 	enum MaybePartialGuild {
 		Partial(PartialGuild),
@@ -159,11 +203,11 @@ pub async fn user_can_read_channel(
 	};
 
 	let permissions = match &guild {
-		FullGuild(g) => g.user_permissions_in(&channel, &member)?,
-		Partial(g) => g.user_permissions_in(&channel, &member)?,
+		FullGuild(g) => g.user_permissions_in(channel, &member)?,
+		Partial(g) => g.user_permissions_in(channel, &member)?,
 	};
 
-	Ok(Some(permissions.read_messages()))
+	Ok(Some(permissions))
 }
 
 /// Makes the result of an HTTP call optional.
@@ -185,3 +229,22 @@ pub fn optional_result<T>(
 		Err(err) => Err(err),
 	}
 }
+
+/// Finds the time zone with the given name, like `America/New_York`, ignoring case.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+	TZ_VARIANTS
+		.iter()
+		.copied()
+		.find(|tz| tz.name().eq_ignore_ascii_case(name))
+}
+
+/// Gets the time zone of the user with the given settings.
+///
+/// Defaults to UTC if the user hasn't chosen a time zone, or chose one that no longer exists.
+pub fn user_timezone(settings: &UserSettings) -> Tz {
+	settings
+		.timezone
+		.as_deref()
+		.and_then(parse_timezone)
+		.unwrap_or(Tz::UTC)
+}
@@ -208,8 +208,6 @@ async fn clean_backups(backup_dir: &Path) {
 /// Creates `<data directory>/backup` if it doesn't exist already, creates a backup, cleans up old
 /// backups, and repeats once every 24hrs.
 pub fn start_backup_cycle(backup_dir: PathBuf) {
-	let _ = ensure_backup_dir_exists(&backup_dir);
-
 	task::spawn(async move {
 		let mut daily = interval(StdDuration::from_secs(60 * 60 * 24));
 
@@ -4,12 +4,12 @@
 //! Handling for blocked users.
 
 use anyhow::Result;
-use rusqlite::{params, Row};
+use rusqlite::{params, Connection, Row};
 use serenity::model::id::UserId;
 
 use crate::{await_db, db::connection};
 
-use super::IdI64Ext;
+use super::{index::index_mut, IdI64Ext};
 
 /// Represents a blocked user.
 #[derive(Debug, Clone)]
@@ -45,6 +45,16 @@ impl Block {
 		.expect("Failed to create blocks table");
 	}
 
+	/// Loads every block from the DB, for the index.
+	pub(super) fn load_all(conn: &Connection) -> rusqlite::Result<Vec<Self>> {
+		let mut stmt =
+			conn.prepare("SELECT user_id, blocked_id FROM blocks")?;
+
+		let blocks = stmt.query_map(params![], Self::from_row)?;
+
+		blocks.collect()
+	}
+
 	/// Fetches the list of blocks a user has added from the DB.
 	pub async fn user_blocks(user_id: UserId) -> Result<Vec<Self>> {
 		await_db!("user blocks": |conn| {
@@ -63,6 +73,8 @@ impl Block {
 	/// Adds this blocked user to the DB.
 	pub async fn insert(self) -> Result<()> {
 		await_db!("insert block": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"INSERT INTO blocks (user_id, blocked_id)
 				VALUES (?, ?)",
@@ -72,6 +84,8 @@ impl Block {
 				],
 			)?;
 
+			index.update(|index| index.insert_block(&self));
+
 			Ok(())
 		})
 	}
@@ -94,6 +108,8 @@ impl Block {
 	/// Deletes this blocked user from the DB (making them not blocked anymore).
 	pub async fn delete(self) -> Result<()> {
 		await_db!("delete block": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"DELETE FROM blocks
 				WHERE user_id = ? AND blocked_id = ?",
@@ -103,6 +119,8 @@ impl Block {
 				],
 			)?;
 
+			index.update(|index| index.delete_block(&self));
+
 			Ok(())
 		})
 	}
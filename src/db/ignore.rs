@@ -4,15 +4,30 @@
 //! Handling for ignored phrases.
 
 use anyhow::Result;
-use rusqlite::{params, Row};
+use rusqlite::{params, Connection, Row};
 use serenity::model::id::{GuildId, UserId};
 
-use crate::{await_db, db::connection};
+use crate::{await_db, db::connection, monitoring::Timer};
 
-use super::IdI64Ext;
+use super::{
+	ensure_column, ensure_primary_key,
+	index::{index, index_mut},
+	IdI64Ext, MatchKind,
+};
+
+/// Columns and primary key of the `guild_ignores` table.
+const GUILD_IGNORES_SCHEMA: &str = "
+	phrase TEXT NOT NULL,
+	user_id INTEGER NOT NULL,
+	guild_id INTEGER NOT NULL,
+	match_kind INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY (phrase, user_id, guild_id, match_kind)
+";
 
 /// Represents an ignored phrase.
-#[derive(Debug, Clone)]
+///
+/// Ignored phrases with the same text but a different kind are separate ignored phrases.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ignore {
 	/// The phrase that should be ignored.
 	pub phrase: String,
@@ -20,6 +35,8 @@ pub struct Ignore {
 	pub user_id: UserId,
 	/// The guild in which the user ignored the phrase.
 	pub guild_id: GuildId,
+	/// How the phrase is matched against messages.
+	pub match_kind: MatchKind,
 }
 
 impl Ignore {
@@ -27,11 +44,13 @@ impl Ignore {
 	/// - `phrase`: `TEXT`
 	/// - `user_id`: `INTEGER`
 	/// - `guild id`: `INTEGER`
+	/// - `match_kind`: `INTEGER`
 	fn from_row(row: &Row) -> rusqlite::Result<Self> {
 		Ok(Ignore {
 			phrase: row.get(0)?,
 			user_id: UserId::from_i64(row.get(1)?),
 			guild_id: GuildId::from_i64(row.get(2)?),
+			match_kind: row.get(3)?,
 		})
 	}
 
@@ -39,15 +58,44 @@ impl Ignore {
 	pub(super) fn create_table() {
 		let conn = connection();
 		conn.execute(
-			"CREATE TABLE IF NOT EXISTS guild_ignores (
-			phrase TEXT NOT NULL,
-			user_id INTEGER NOT NULL,
-			guild_id INTEGER NOT NULL,
-			PRIMARY KEY (phrase, user_id, guild_id)
-			)",
+			&format!(
+				"CREATE TABLE IF NOT EXISTS guild_ignores ({})",
+				GUILD_IGNORES_SCHEMA
+			),
 			params![],
 		)
 		.expect("Failed to create guild_ignores table");
+
+		ensure_column(
+			"guild_ignores",
+			"match_kind",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+
+		ensure_primary_key("guild_ignores", "match_kind", GUILD_IGNORES_SCHEMA);
+	}
+
+	/// Loads every ignored phrase from the DB, for the index.
+	pub(super) fn load_all(conn: &Connection) -> rusqlite::Result<Vec<Self>> {
+		let mut stmt = conn.prepare(
+			"SELECT phrase, user_id, guild_id, match_kind
+			FROM guild_ignores",
+		)?;
+
+		let ignores = stmt.query_map(params![], Ignore::from_row)?;
+
+		ignores.collect()
+	}
+
+	/// Fetches the list of ignored phrases of the specified user in the specified guild from the
+	/// in-memory index.
+	pub fn indexed_user_guild_ignores(
+		user_id: UserId,
+		guild_id: GuildId,
+	) -> Vec<Ignore> {
+		let _timer = Timer::index("user guild ignores");
+
+		index().user_guild_ignores(user_id, guild_id)
 	}
 
 	/// Fetches the list of ignored phrases of the specified user in the specified guild from the DB.
@@ -57,7 +105,7 @@ impl Ignore {
 	) -> Result<Vec<Ignore>> {
 		await_db!("user guild ignores": |conn| {
 			let mut stmt = conn.prepare(
-				"SELECT phrase, user_id, guild_id
+				"SELECT phrase, user_id, guild_id, match_kind
 				FROM guild_ignores
 				WHERE user_id = ? AND guild_id = ?"
 			)?;
@@ -75,7 +123,7 @@ impl Ignore {
 	pub async fn user_ignores(user_id: UserId) -> Result<Vec<Ignore>> {
 		await_db!("user ignores": |conn| {
 			let mut stmt = conn.prepare(
-				"SELECT phrase, user_id, guild_id
+				"SELECT phrase, user_id, guild_id, match_kind
 				FROM guild_ignores
 				WHERE user_id = ?"
 			)?;
@@ -92,11 +140,12 @@ impl Ignore {
 		await_db!("ignore exists": |conn| {
 			conn.query_row(
 				"SELECT COUNT(*) FROM guild_ignores
-				WHERE phrase = ? AND user_id = ? AND guild_id = ?",
+				WHERE phrase = ? AND user_id = ? AND guild_id = ? AND match_kind = ?",
 				params![
 					&*self.phrase,
 					self.user_id.into_i64(),
-					self.guild_id.into_i64()
+					self.guild_id.into_i64(),
+					self.match_kind,
 				],
 				|row| Ok(row.get::<_, u32>(0)? == 1),
 			).map_err(Into::into)
@@ -106,16 +155,21 @@ impl Ignore {
 	/// Adds this ignored phrase to the DB.
 	pub async fn insert(self) -> Result<()> {
 		await_db!("insert ignore": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
-				"INSERT INTO guild_ignores (phrase, user_id, guild_id)
-				VALUES (?, ?, ?)",
+				"INSERT INTO guild_ignores (phrase, user_id, guild_id, match_kind)
+				VALUES (?, ?, ?, ?)",
 				params![
 					&*self.phrase,
 					self.user_id.into_i64(),
-					self.guild_id.into_i64()
+					self.guild_id.into_i64(),
+					self.match_kind,
 				],
 			)?;
 
+			index.update(|index| index.insert_ignore(self));
+
 			Ok(())
 		})
 	}
@@ -123,16 +177,21 @@ impl Ignore {
 	/// Deletes this ignored phrase from the DB.
 	pub async fn delete(self) -> Result<()> {
 		await_db!("delete ignore": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"DELETE FROM guild_ignores
-				WHERE phrase = ? AND user_id = ? AND guild_id = ?",
+				WHERE phrase = ? AND user_id = ? AND guild_id = ? AND match_kind = ?",
 				params![
 					&*self.phrase,
 					self.user_id.into_i64(),
-					self.guild_id.into_i64()
+					self.guild_id.into_i64(),
+					self.match_kind,
 				],
 			)?;
 
+			index.update(|index| index.delete_ignore(&self));
+
 			Ok(())
 		})
 	}
@@ -143,11 +202,17 @@ impl Ignore {
 		guild_id: GuildId,
 	) -> Result<usize> {
 		await_db!("delete ignores in guild": |conn| {
-			conn.execute(
+			let mut index = index_mut();
+
+			let deleted = conn.execute(
 				"DELETE FROM guild_ignores
 					WHERE user_id = ? AND guild_id = ?",
 				params![user_id.into_i64(), guild_id.into_i64()]
-			).map_err(Into::into)
+			)?;
+
+			index.update(|index| index.delete_guild_ignores(user_id, guild_id));
+
+			Ok(deleted)
 		})
 	}
 }
@@ -0,0 +1,374 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! In-memory index of the data needed to find keywords relevant to a message.
+//!
+//! Every keyword, ignored phrase, mute, block, opt-out, and user's settings is loaded from the DB
+//! at startup, and the index is updated by the methods that change them in the DB. Each change
+//! holds an [`IndexWriter`](IndexWriter) while it runs its query, so the index and the DB are
+//! changed in the same order and stay consistent. The index itself is only locked while it's
+//! updated, so reading it never waits for a query.
+
+use once_cell::sync::Lazy;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+
+use std::{
+	collections::{HashMap, HashSet},
+	sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard},
+};
+
+use super::{
+	connection, Block, Ignore, Keyword, KeywordKind, Mute, OptOut, UserSettings,
+};
+
+/// The global index.
+static INDEX: Lazy<RwLock<Index>> = Lazy::new(Default::default);
+
+/// Lock held by each change to the DB and the index, so they happen in the same order.
+static WRITER: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+/// Copies of the relevant parts of the DB.
+#[derive(Debug, Default)]
+pub(super) struct Index {
+	guild_keywords: HashMap<GuildId, Vec<Keyword>>,
+	channel_keywords: HashMap<ChannelId, Vec<Keyword>>,
+	ignores: HashMap<(UserId, GuildId), Vec<Ignore>>,
+	/// Pairs of the user who muted a channel and the channel.
+	mutes: HashSet<(UserId, ChannelId)>,
+	/// Pairs of the user who blocked someone and the user they blocked.
+	blocks: HashSet<(UserId, UserId)>,
+	opt_outs: HashSet<UserId>,
+	user_settings: HashMap<UserId, UserSettings>,
+}
+
+/// Gets read access to the global index.
+pub(super) fn index() -> RwLockReadGuard<'static, Index> {
+	INDEX.read().unwrap()
+}
+
+/// Exclusive access to change the global index, from [`index_mut`](index_mut).
+pub(super) struct IndexWriter {
+	_guard: MutexGuard<'static, ()>,
+}
+
+impl IndexWriter {
+	/// Changes the index, only locking it while `change` runs.
+	pub(super) fn update<T>(
+		&mut self,
+		change: impl FnOnce(&mut Index) -> T,
+	) -> T {
+		change(&mut INDEX.write().unwrap())
+	}
+}
+
+/// Gets exclusive access to change the global index.
+///
+/// The writer should be held while the DB is changed, so changes to the DB and the index happen in
+/// the same order. It doesn't lock the index itself, so the index can still be read meanwhile.
+pub(super) fn index_mut() -> IndexWriter {
+	IndexWriter {
+		_guard: WRITER.lock().unwrap(),
+	}
+}
+
+/// Loads the index from the DB.
+pub(super) fn load() {
+	let conn = connection();
+
+	let mut writer = index_mut();
+
+	let mut index = Index::default();
+
+	for keyword in Keyword::load_all(&conn).expect("Failed to load keywords") {
+		index.insert_keyword(keyword);
+	}
+
+	for ignore in Ignore::load_all(&conn).expect("Failed to load ignores") {
+		index.insert_ignore(ignore);
+	}
+
+	for mute in Mute::load_all(&conn).expect("Failed to load mutes") {
+		index.insert_mute(&mute);
+	}
+
+	for block in Block::load_all(&conn).expect("Failed to load blocks") {
+		index.insert_block(&block);
+	}
+
+	for opt_out in OptOut::load_all(&conn).expect("Failed to load opt-outs") {
+		index.insert_opt_out(&opt_out);
+	}
+
+	for settings in
+		UserSettings::load_all(&conn).expect("Failed to load user settings")
+	{
+		index.set_user_settings(settings);
+	}
+
+	log::info!(
+		"Loaded {} keywords and {} ignored phrases into the index",
+		index
+			.guild_keywords
+			.values()
+			.chain(index.channel_keywords.values())
+			.map(Vec::len)
+			.sum::<usize>(),
+		index.ignores.values().map(Vec::len).sum::<usize>(),
+	);
+
+	writer.update(|old| *old = index);
+}
+
+impl Index {
+	/// Gets keywords that may be relevant to a message, like
+	/// [`get_relevant_keywords`](Keyword::get_relevant_keywords).
+	pub(super) fn relevant_keywords(
+		&self,
+		guild_id: GuildId,
+		channel_id: ChannelId,
+		author_id: UserId,
+	) -> Vec<Keyword> {
+		if self.opt_outs.contains(&author_id) {
+			return vec![];
+		}
+
+		let guild_keywords = self
+			.guild_keywords
+			.get(&guild_id)
+			.into_iter()
+			.flatten()
+			.filter(|keyword| {
+				!self.mutes.contains(&(keyword.user_id, channel_id))
+			});
+
+		let channel_keywords =
+			self.channel_keywords.get(&channel_id).into_iter().flatten();
+
+		guild_keywords
+			.chain(channel_keywords)
+			.filter(|keyword| {
+				keyword.user_id != author_id
+					&& !self.blocks.contains(&(keyword.user_id, author_id))
+			})
+			.cloned()
+			.collect()
+	}
+
+	/// Gets the ignored phrases of a user in a guild.
+	pub(super) fn user_guild_ignores(
+		&self,
+		user_id: UserId,
+		guild_id: GuildId,
+	) -> Vec<Ignore> {
+		self.ignores
+			.get(&(user_id, guild_id))
+			.cloned()
+			.unwrap_or_default()
+	}
+
+	/// Gets the settings of a user, if they've chosen any.
+	pub(super) fn user_settings(
+		&self,
+		user_id: UserId,
+	) -> Option<UserSettings> {
+		self.user_settings.get(&user_id).cloned()
+	}
+
+	pub(super) fn insert_keyword(&mut self, keyword: Keyword) {
+		match keyword.kind {
+			KeywordKind::Guild(guild_id) => self
+				.guild_keywords
+				.entry(guild_id)
+				.or_default()
+				.push(keyword),
+			KeywordKind::Channel(channel_id) => self
+				.channel_keywords
+				.entry(channel_id)
+				.or_default()
+				.push(keyword),
+		}
+	}
+
+	pub(super) fn delete_keyword(&mut self, keyword: &Keyword) {
+		match keyword.kind {
+			KeywordKind::Guild(guild_id) => {
+				if let Some(keywords) = self.guild_keywords.get_mut(&guild_id) {
+					keywords.retain(|other| other != keyword);
+				}
+			}
+			KeywordKind::Channel(channel_id) => {
+				if let Some(keywords) =
+					self.channel_keywords.get_mut(&channel_id)
+				{
+					keywords.retain(|other| other != keyword);
+				}
+			}
+		}
+	}
+
+	pub(super) fn delete_guild_keywords(
+		&mut self,
+		user_id: UserId,
+		guild_id: GuildId,
+	) {
+		if let Some(keywords) = self.guild_keywords.get_mut(&guild_id) {
+			keywords.retain(|keyword| keyword.user_id != user_id);
+		}
+	}
+
+	pub(super) fn delete_channel_keywords(
+		&mut self,
+		user_id: UserId,
+		channel_id: ChannelId,
+	) {
+		if let Some(keywords) = self.channel_keywords.get_mut(&channel_id) {
+			keywords.retain(|keyword| keyword.user_id != user_id);
+		}
+	}
+
+	pub(super) fn insert_ignore(&mut self, ignore: Ignore) {
+		self.ignores
+			.entry((ignore.user_id, ignore.guild_id))
+			.or_default()
+			.push(ignore);
+	}
+
+	pub(super) fn delete_ignore(&mut self, ignore: &Ignore) {
+		if let Some(ignores) =
+			self.ignores.get_mut(&(ignore.user_id, ignore.guild_id))
+		{
+			ignores.retain(|other| other != ignore);
+		}
+	}
+
+	pub(super) fn delete_guild_ignores(
+		&mut self,
+		user_id: UserId,
+		guild_id: GuildId,
+	) {
+		self.ignores.remove(&(user_id, guild_id));
+	}
+
+	pub(super) fn insert_mute(&mut self, mute: &Mute) {
+		self.mutes.insert((mute.user_id, mute.channel_id));
+	}
+
+	pub(super) fn delete_mute(&mut self, mute: &Mute) {
+		self.mutes.remove(&(mute.user_id, mute.channel_id));
+	}
+
+	pub(super) fn insert_block(&mut self, block: &Block) {
+		self.blocks.insert((block.user_id, block.blocked_id));
+	}
+
+	pub(super) fn delete_block(&mut self, block: &Block) {
+		self.blocks.remove(&(block.user_id, block.blocked_id));
+	}
+
+	pub(super) fn insert_opt_out(&mut self, opt_out: &OptOut) {
+		self.opt_outs.insert(opt_out.user_id);
+	}
+
+	pub(super) fn delete_opt_out(&mut self, opt_out: &OptOut) {
+		self.opt_outs.remove(&opt_out.user_id);
+	}
+
+	pub(super) fn set_user_settings(&mut self, settings: UserSettings) {
+		self.user_settings.insert(settings.user_id, settings);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::{Exclusions, MatchKind, Segmentation};
+
+	fn keyword(text: &str, user_id: u64, kind: KeywordKind) -> Keyword {
+		Keyword {
+			keyword: text.to_owned(),
+			user_id: UserId(user_id),
+			kind,
+			match_kind: MatchKind::Literal,
+			segmentation: Segmentation::Auto,
+			case_sensitive: false,
+			exclusions: Exclusions::none(),
+		}
+	}
+
+	fn relevant(index: &Index, author_id: u64) -> Vec<String> {
+		let mut keywords = index
+			.relevant_keywords(GuildId(1), ChannelId(10), UserId(author_id))
+			.into_iter()
+			.map(|keyword| keyword.keyword)
+			.collect::<Vec<_>>();
+		keywords.sort();
+		keywords
+	}
+
+	#[test]
+	fn relevant_keywords() {
+		let mut index = Index::default();
+
+		index.insert_keyword(keyword(
+			"guild",
+			100,
+			KeywordKind::Guild(GuildId(1)),
+		));
+		index.insert_keyword(keyword(
+			"other guild",
+			100,
+			KeywordKind::Guild(GuildId(2)),
+		));
+		index.insert_keyword(keyword(
+			"channel",
+			101,
+			KeywordKind::Channel(ChannelId(10)),
+		));
+		index.insert_keyword(keyword(
+			"other channel",
+			101,
+			KeywordKind::Channel(ChannelId(11)),
+		));
+
+		assert_eq!(relevant(&index, 200), ["channel", "guild"]);
+
+		assert_eq!(relevant(&index, 100), ["channel"]);
+
+		let mute = Mute {
+			user_id: UserId(100),
+			channel_id: ChannelId(10),
+		};
+		index.insert_mute(&mute);
+		assert_eq!(relevant(&index, 200), ["channel"]);
+		index.delete_mute(&mute);
+
+		let block = Block {
+			user_id: UserId(101),
+			blocked_id: UserId(200),
+		};
+		index.insert_block(&block);
+		assert_eq!(relevant(&index, 200), ["guild"]);
+		index.delete_block(&block);
+
+		let opt_out = OptOut {
+			user_id: UserId(200),
+		};
+		index.insert_opt_out(&opt_out);
+		assert!(relevant(&index, 200).is_empty());
+		index.delete_opt_out(&opt_out);
+
+		index.delete_keyword(&Keyword {
+			match_kind: MatchKind::Fuzzy,
+			..keyword("guild", 100, KeywordKind::Guild(GuildId(1)))
+		});
+		assert_eq!(relevant(&index, 200), ["channel", "guild"]);
+
+		index.delete_keyword(&keyword(
+			"guild",
+			100,
+			KeywordKind::Guild(GuildId(1)),
+		));
+		index.delete_channel_keywords(UserId(101), ChannelId(10));
+		assert!(relevant(&index, 200).is_empty());
+	}
+}
@@ -4,24 +4,172 @@
 //! Handling for keywords.
 
 use anyhow::Result;
-use rusqlite::{params, Row};
+use rusqlite::{
+	params,
+	types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+	Connection, Row, ToSql,
+};
 use serenity::model::id::{ChannelId, GuildId, UserId};
 
-use crate::{await_db, db::connection};
-
-use super::IdI64Ext;
-
-#[derive(Debug, Clone, Copy)]
+use crate::{await_db, db::connection, flag_set, monitoring::Timer};
+
+use super::{
+	ensure_column, ensure_primary_key,
+	index::{index, index_mut},
+	IdI64Ext,
+};
+
+/// Columns and primary key of the `guild_keywords` table.
+const GUILD_KEYWORDS_SCHEMA: &str = "
+	keyword TEXT NOT NULL,
+	user_id INTEGER NOT NULL,
+	guild_id INTEGER NOT NULL,
+	match_kind INTEGER NOT NULL DEFAULT 0,
+	segmentation INTEGER NOT NULL DEFAULT 0,
+	case_sensitive INTEGER NOT NULL DEFAULT 0,
+	exclusions INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY (
+		keyword, user_id, guild_id, match_kind, segmentation, case_sensitive, exclusions
+	)
+";
+
+/// Columns and primary key of the `channel_keywords` table.
+const CHANNEL_KEYWORDS_SCHEMA: &str = "
+	keyword TEXT NOT NULL,
+	user_id INTEGER NOT NULL,
+	channel_id INTEGER NOT NULL,
+	match_kind INTEGER NOT NULL DEFAULT 0,
+	segmentation INTEGER NOT NULL DEFAULT 0,
+	case_sensitive INTEGER NOT NULL DEFAULT 0,
+	exclusions INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY (
+		keyword, user_id, channel_id, match_kind, segmentation, case_sensitive, exclusions
+	)
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeywordKind {
 	Channel(ChannelId),
 	Guild(GuildId),
 }
 
-#[derive(Debug, Clone)]
+/// Description of how a keyword (or ignored phrase) is matched against message content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MatchKind {
+	/// Plain text, matched case-insensitively on word boundaries.
+	Literal = 0,
+	/// A regular expression, matched case-insensitively anywhere in a message.
+	Regex = 1,
+	/// Text with `*` wildcards, matched like literal text.
+	Wildcard = 2,
+	/// Plain text, matched like literal text but allowing a few typos in each word.
+	Fuzzy = 3,
+	/// Plain text, matched like literal text but also matching words with the same stem, which are
+	/// the same word with a different suffix, like a plural.
+	Stemmed = 4,
+	/// Literal terms combined with `AND`, `OR`, and `NOT`.
+	Expression = 5,
+	/// Two literal terms, matched within a given number of words of each other.
+	Proximity = 6,
+}
+
+impl MatchKind {
+	const LITERAL: u8 = MatchKind::Literal as u8;
+	const REGEX: u8 = MatchKind::Regex as u8;
+	const WILDCARD: u8 = MatchKind::Wildcard as u8;
+	const FUZZY: u8 = MatchKind::Fuzzy as u8;
+	const STEMMED: u8 = MatchKind::Stemmed as u8;
+	const EXPRESSION: u8 = MatchKind::Expression as u8;
+	const PROXIMITY: u8 = MatchKind::Proximity as u8;
+}
+
+impl FromSql for MatchKind {
+	fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+		match value.as_i64()? {
+			x if x == Self::LITERAL as i64 => Ok(MatchKind::Literal),
+			x if x == Self::REGEX as i64 => Ok(MatchKind::Regex),
+			x if x == Self::WILDCARD as i64 => Ok(MatchKind::Wildcard),
+			x if x == Self::FUZZY as i64 => Ok(MatchKind::Fuzzy),
+			x if x == Self::STEMMED as i64 => Ok(MatchKind::Stemmed),
+			x if x == Self::EXPRESSION as i64 => Ok(MatchKind::Expression),
+			x if x == Self::PROXIMITY as i64 => Ok(MatchKind::Proximity),
+			other => Err(FromSqlError::OutOfRange(other)),
+		}
+	}
+}
+
+impl ToSql for MatchKind {
+	fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+		Ok((*self as u8).into())
+	}
+}
+
+/// Description of which positions in a message a keyword can match at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Segmentation {
+	/// Keywords in scripts written without spaces between words, like Chinese, Japanese, or Thai,
+	/// match at Unicode word boundaries; other keywords match on whole words.
+	Auto = 0,
+	/// Keywords only match whole words separated by spaces or punctuation.
+	Words = 1,
+	/// Keywords match anywhere, even inside other words.
+	Substring = 2,
+}
+
+impl Segmentation {
+	const AUTO: u8 = Segmentation::Auto as u8;
+	const WORDS: u8 = Segmentation::Words as u8;
+	const SUBSTRING: u8 = Segmentation::Substring as u8;
+}
+
+impl FromSql for Segmentation {
+	fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+		match value.as_i64()? {
+			x if x == Self::AUTO as i64 => Ok(Segmentation::Auto),
+			x if x == Self::WORDS as i64 => Ok(Segmentation::Words),
+			x if x == Self::SUBSTRING as i64 => Ok(Segmentation::Substring),
+			other => Err(FromSqlError::OutOfRange(other)),
+		}
+	}
+}
+
+impl ToSql for Segmentation {
+	fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+		Ok((*self as u8).into())
+	}
+}
+
+flag_set! {
+	/// Set of parts of a message, written with markdown, that keywords aren't matched in.
+	#[derive(Default)]
+	pub struct Exclusions;
+
+	/// Inline code, like `` `code` ``.
+	INLINE_CODE = 0, "code";
+	/// Fenced code blocks, like ```` ```code``` ````.
+	CODE_BLOCKS = 1, "code-blocks";
+	/// Block quotes, like `> quote` or `>>> quote`.
+	QUOTES = 2, "quotes";
+	/// Spoilers, like `||spoiler||`.
+	SPOILERS = 3, "spoilers";
+	/// The targets of links, like `https://example.com`.
+	LINKS = 4, "links";
+}
+
+/// Represents a keyword.
+///
+/// Keywords with the same text but a different kind or different flags are separate keywords.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Keyword {
 	pub keyword: String,
 	pub user_id: UserId,
 	pub kind: KeywordKind,
+	pub match_kind: MatchKind,
+	pub segmentation: Segmentation,
+	pub case_sensitive: bool,
+	pub exclusions: Exclusions,
 }
 
 impl Keyword {
@@ -29,11 +177,19 @@ impl Keyword {
 	/// - `keyword`: `TEXT`
 	/// - `user_id`: `INTEGER`
 	/// - `<guild id>`: `INTEGER`
+	/// - `match_kind`: `INTEGER`
+	/// - `segmentation`: `INTEGER`
+	/// - `case_sensitive`: `INTEGER`
+	/// - `exclusions`: `INTEGER`
 	fn from_guild_row(row: &Row) -> rusqlite::Result<Self> {
 		Ok(Keyword {
 			keyword: row.get(0)?,
 			user_id: UserId::from_i64(row.get(1)?),
 			kind: KeywordKind::Guild(GuildId::from_i64(row.get(2)?)),
+			match_kind: row.get(3)?,
+			segmentation: row.get(4)?,
+			case_sensitive: row.get(5)?,
+			exclusions: row.get(6)?,
 		})
 	}
 
@@ -41,11 +197,19 @@ impl Keyword {
 	/// - `keyword`: `TEXT`
 	/// - `user_id`: `INTEGER`
 	/// - `<channel id>`: `INTEGER`
+	/// - `match_kind`: `INTEGER`
+	/// - `segmentation`: `INTEGER`
+	/// - `case_sensitive`: `INTEGER`
+	/// - `exclusions`: `INTEGER`
 	fn from_channel_row(row: &Row) -> rusqlite::Result<Self> {
 		Ok(Keyword {
 			keyword: row.get(0)?,
 			user_id: UserId::from_i64(row.get(1)?),
 			kind: KeywordKind::Channel(ChannelId::from_i64(row.get(2)?)),
+			match_kind: row.get(3)?,
+			segmentation: row.get(4)?,
+			case_sensitive: row.get(5)?,
+			exclusions: row.get(6)?,
 		})
 	}
 
@@ -54,26 +218,160 @@ impl Keyword {
 		let conn = connection();
 
 		conn.execute(
-			"CREATE TABLE IF NOT EXISTS guild_keywords (
-				keyword TEXT NOT NULL,
-				user_id INTEGER NOT NULL,
-				guild_id INTEGER NOT NULL,
-				PRIMARY KEY (keyword, user_id, guild_id)
-			)",
+			&format!(
+				"CREATE TABLE IF NOT EXISTS guild_keywords ({})",
+				GUILD_KEYWORDS_SCHEMA
+			),
 			params![],
 		)
 		.expect("Failed to create guild_keywords table");
 
 		conn.execute(
-			"CREATE TABLE IF NOT EXISTS channel_keywords (
-				keyword TEXT NOT NULL,
-				user_id INTEGER NOT NULL,
-				channel_id INTEGER NOT NULL,
-				PRIMARY KEY (keyword, user_id, channel_id)
-			)",
+			&format!(
+				"CREATE TABLE IF NOT EXISTS channel_keywords ({})",
+				CHANNEL_KEYWORDS_SCHEMA
+			),
 			params![],
 		)
 		.expect("Failed to create channel_keywords table");
+
+		ensure_column(
+			"guild_keywords",
+			"match_kind",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"channel_keywords",
+			"match_kind",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"guild_keywords",
+			"segmentation",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"channel_keywords",
+			"segmentation",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"guild_keywords",
+			"case_sensitive",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"channel_keywords",
+			"case_sensitive",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"guild_keywords",
+			"exclusions",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"channel_keywords",
+			"exclusions",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+
+		ensure_primary_key(
+			"guild_keywords",
+			"match_kind",
+			GUILD_KEYWORDS_SCHEMA,
+		);
+		ensure_primary_key(
+			"channel_keywords",
+			"match_kind",
+			CHANNEL_KEYWORDS_SCHEMA,
+		);
+	}
+
+	/// Loads every keyword from the DB, for the index.
+	pub(super) fn load_all(conn: &Connection) -> rusqlite::Result<Vec<Self>> {
+		let mut stmt = conn.prepare(
+			"SELECT keyword, user_id, guild_id, match_kind, segmentation, case_sensitive,
+				exclusions
+			FROM guild_keywords",
+		)?;
+
+		let mut keywords = stmt
+			.query_map(params![], Keyword::from_guild_row)?
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let mut stmt = conn.prepare(
+			"SELECT keyword, user_id, channel_id, match_kind, segmentation, case_sensitive,
+				exclusions
+			FROM channel_keywords",
+		)?;
+
+		keywords.extend(
+			stmt.query_map(params![], Keyword::from_channel_row)?
+				.collect::<Result<Vec<_>, _>>()?,
+		);
+
+		Ok(keywords)
+	}
+
+	/// Gets keywords that may be relevant to a message from the in-memory index.
+	///
+	/// The keywords are the same as those from
+	/// [`get_relevant_keywords`](Keyword::get_relevant_keywords), but the DB isn't queried.
+	pub fn indexed_relevant_keywords(
+		guild_id: GuildId,
+		channel_id: ChannelId,
+		author_id: UserId,
+	) -> Vec<Keyword> {
+		let _timer = Timer::index("get keywords");
+
+		index().relevant_keywords(guild_id, channel_id, author_id)
+	}
+
+	/// Checks that the in-memory index has the same keywords relevant to a message as the DB.
+	///
+	/// Returns `Ok(false)` if they differ.
+	pub async fn check_index(
+		guild_id: GuildId,
+		channel_id: ChannelId,
+		author_id: UserId,
+	) -> Result<bool> {
+		type Key = (String, UserId, u64, u8, u8, bool, u8);
+
+		fn key(keyword: &Keyword) -> Key {
+			let id = match keyword.kind {
+				KeywordKind::Guild(guild_id) => guild_id.0,
+				KeywordKind::Channel(channel_id) => channel_id.0,
+			};
+
+			(
+				keyword.keyword.clone(),
+				keyword.user_id,
+				id,
+				keyword.match_kind as u8,
+				keyword.segmentation as u8,
+				keyword.case_sensitive,
+				keyword.exclusions.0,
+			)
+		}
+
+		let mut queried =
+			Self::get_relevant_keywords(guild_id, channel_id, author_id)
+				.await?
+				.iter()
+				.map(key)
+				.collect::<Vec<_>>();
+
+		let mut indexed =
+			Self::indexed_relevant_keywords(guild_id, channel_id, author_id)
+				.iter()
+				.map(key)
+				.collect::<Vec<_>>();
+
+		queried.sort_unstable();
+		indexed.sort_unstable();
+
+		Ok(queried == indexed)
 	}
 
 	/// Gets keywords that may be relelvant to a message.
@@ -94,7 +392,9 @@ impl Keyword {
 			let author_id = author_id.into_i64();
 
 			let mut stmt = conn.prepare(
-				"SELECT guild_keywords.keyword, guild_keywords.user_id, guild_keywords.guild_id
+				"SELECT guild_keywords.keyword, guild_keywords.user_id, guild_keywords.guild_id,
+						guild_keywords.match_kind, guild_keywords.segmentation,
+						guild_keywords.case_sensitive, guild_keywords.exclusions
 					FROM guild_keywords
 					WHERE guild_keywords.guild_id = ?
 						AND guild_keywords.user_id != ?
@@ -132,7 +432,8 @@ impl Keyword {
 			let mut keywords = guild_keywords.collect::<Result<Vec<_>, _>>()?;
 
 			let mut stmt = conn.prepare(
-				"SELECT keyword, user_id, channel_id
+				"SELECT keyword, user_id, channel_id, match_kind, segmentation, case_sensitive,
+				exclusions
 					FROM channel_keywords
 					WHERE user_id != ?
 						AND channel_id = ?
@@ -170,7 +471,8 @@ impl Keyword {
 		await_db!("user guild keywords": |conn| {
 
 			let mut stmt = conn.prepare(
-				"SELECT keyword, user_id, guild_id
+				"SELECT keyword, user_id, guild_id, match_kind, segmentation, case_sensitive,
+				exclusions
 				FROM guild_keywords
 				WHERE user_id = ? AND guild_id = ?"
 			)?;
@@ -190,7 +492,8 @@ impl Keyword {
 	) -> Result<Vec<Keyword>> {
 		await_db!("user channel keywords": |conn| {
 			let mut stmt = conn.prepare(
-				"SELECT keyword, user_id, channel_id
+				"SELECT keyword, user_id, channel_id, match_kind, segmentation, case_sensitive,
+				exclusions
 				FROM channel_keywords
 				WHERE user_id = ?"
 			)?;
@@ -208,7 +511,8 @@ impl Keyword {
 	pub async fn user_keywords(user_id: UserId) -> Result<Vec<Keyword>> {
 		await_db!("user keywords": |conn| {
 			let mut stmt = conn.prepare(
-				"SELECT keyword, user_id, guild_id
+				"SELECT keyword, user_id, guild_id, match_kind, segmentation, case_sensitive,
+				exclusions
 				FROM guild_keywords
 				WHERE user_id = ?"
 			)?;
@@ -221,7 +525,8 @@ impl Keyword {
 			let mut keywords = guild_keywords.collect::<Result<Vec<_>, _>>()?;
 
 			let mut stmt = conn.prepare(
-				"SELECT keyword, user_id, channel_id
+				"SELECT keyword, user_id, channel_id, match_kind, segmentation, case_sensitive,
+				exclusions
 				FROM channel_keywords
 				WHERE user_id = ?"
 			)?;
@@ -244,11 +549,17 @@ impl Keyword {
 				KeywordKind::Guild(guild_id) => {
 					conn.query_row(
 						"SELECT COUNT(*) FROM guild_keywords
-						WHERE keyword = ? AND user_id = ? AND guild_id = ?",
+						WHERE keyword = ? AND user_id = ? AND guild_id = ?
+							AND match_kind = ? AND segmentation = ?
+							AND case_sensitive = ? AND exclusions = ?",
 						params![
 							&self.keyword,
 							self.user_id.into_i64(),
-							guild_id.into_i64()
+							guild_id.into_i64(),
+							self.match_kind,
+							self.segmentation,
+							self.case_sensitive,
+							self.exclusions,
 						],
 						|row| Ok(row.get::<_, u32>(0)? == 1),
 					).map_err(Into::into)
@@ -256,11 +567,17 @@ impl Keyword {
 				KeywordKind::Channel(channel_id) => {
 					conn.query_row(
 						"SELECT COUNT(*) FROM channel_keywords
-						WHERE keyword = ? AND user_id = ? AND channel_id = ?",
+						WHERE keyword = ? AND user_id = ? AND channel_id = ?
+							AND match_kind = ? AND segmentation = ?
+							AND case_sensitive = ? AND exclusions = ?",
 						params![
 							&self.keyword,
 							self.user_id.into_i64(),
-							channel_id.into_i64()
+							channel_id.into_i64(),
+							self.match_kind,
+							self.segmentation,
+							self.case_sensitive,
+							self.exclusions,
 						],
 						|row| Ok(row.get::<_, u32>(0)? == 1),
 					).map_err(Into::into)
@@ -295,31 +612,49 @@ impl Keyword {
 	/// Adds this keyword to the DB.
 	pub async fn insert(self) -> Result<()> {
 		await_db!("insert keyword": |conn| {
+			let mut index = index_mut();
+
 			match self.kind {
 				KeywordKind::Guild(guild_id) => {
 					conn.execute(
-						"INSERT INTO guild_keywords (keyword, user_id, guild_id)
-							VALUES (?, ?, ?)",
+						"INSERT INTO guild_keywords (
+								keyword, user_id, guild_id, match_kind, segmentation,
+								case_sensitive, exclusions
+							)
+							VALUES (?, ?, ?, ?, ?, ?, ?)",
 						params![
 							&self.keyword,
 							self.user_id.into_i64(),
-							guild_id.into_i64()
+							guild_id.into_i64(),
+							self.match_kind,
+							self.segmentation,
+							self.case_sensitive,
+							self.exclusions,
 						],
 					)?;
 				}
 				KeywordKind::Channel(channel_id) => {
 					conn.execute(
-						"INSERT INTO channel_keywords (keyword, user_id, channel_id)
-							VALUES (?, ?, ?)",
+						"INSERT INTO channel_keywords (
+								keyword, user_id, channel_id, match_kind, segmentation,
+								case_sensitive, exclusions
+							)
+							VALUES (?, ?, ?, ?, ?, ?, ?)",
 						params![
 							&self.keyword,
 							self.user_id.into_i64(),
-							channel_id.into_i64()
+							channel_id.into_i64(),
+							self.match_kind,
+							self.segmentation,
+							self.case_sensitive,
+							self.exclusions,
 						],
 					)?;
 				}
 			}
 
+			index.update(|index| index.insert_keyword(self));
+
 			Ok(())
 		})
 	}
@@ -327,31 +662,47 @@ impl Keyword {
 	/// Deletes this keyword from the DB.
 	pub async fn delete(self) -> Result<()> {
 		await_db!("delete keyword": |conn| {
+			let mut index = index_mut();
+
 			match self.kind {
 				KeywordKind::Guild(guild_id) => {
 					conn.execute(
 						"DELETE FROM guild_keywords
-							WHERE keyword = ? AND user_id = ? AND guild_id = ?",
+							WHERE keyword = ? AND user_id = ? AND guild_id = ?
+								AND match_kind = ? AND segmentation = ?
+								AND case_sensitive = ? AND exclusions = ?",
 						params![
 							&self.keyword,
 							self.user_id.into_i64(),
-							guild_id.into_i64()
+							guild_id.into_i64(),
+							self.match_kind,
+							self.segmentation,
+							self.case_sensitive,
+							self.exclusions,
 						],
 					)?;
 				}
 				KeywordKind::Channel(channel_id) => {
 					conn.execute(
 						"DELETE FROM channel_keywords
-							WHERE keyword = ? AND user_id = ? AND channel_id = ?",
+							WHERE keyword = ? AND user_id = ? AND channel_id = ?
+								AND match_kind = ? AND segmentation = ?
+								AND case_sensitive = ? AND exclusions = ?",
 						params![
 							&self.keyword,
 							self.user_id.into_i64(),
-							channel_id.into_i64()
+							channel_id.into_i64(),
+							self.match_kind,
+							self.segmentation,
+							self.case_sensitive,
+							self.exclusions,
 						],
 					)?;
 				}
 			}
 
+			index.update(|index| index.delete_keyword(&self));
+
 			Ok(())
 		})
 	}
@@ -362,11 +713,17 @@ impl Keyword {
 		guild_id: GuildId,
 	) -> Result<usize> {
 		await_db!("delete keywords in guild": |conn| {
-			conn.execute(
+			let mut index = index_mut();
+
+			let deleted = conn.execute(
 				"DELETE FROM guild_keywords
 					WHERE user_id = ? AND guild_id = ?",
 				params![user_id.into_i64(), guild_id.into_i64()]
-			).map_err(Into::into)
+			)?;
+
+			index.update(|index| index.delete_guild_keywords(user_id, guild_id));
+
+			Ok(deleted)
 		})
 	}
 
@@ -376,11 +733,17 @@ impl Keyword {
 		channel_id: ChannelId,
 	) -> Result<usize> {
 		await_db!("delete keywords in channel": |conn| {
-			conn.execute(
+			let mut index = index_mut();
+
+			let deleted = conn.execute(
 				"DELETE FROM channel_keywords
 					WHERE user_id = ? AND channel_id = ?",
 				params![user_id.into_i64(), channel_id.into_i64()]
-			).map_err(Into::into)
+			)?;
+
+			index.update(|index| index.delete_channel_keywords(user_id, channel_id));
+
+			Ok(deleted)
 		})
 	}
 }
@@ -9,24 +9,33 @@ use backup::start_backup_cycle;
 
 mod block;
 mod ignore;
+mod index;
 mod keyword;
 mod mute;
 mod notification;
 mod opt_out;
+mod pending_notification;
+mod quiet_hours;
+mod scheduled_notification;
+mod user_settings;
 mod user_state;
 
 pub use block::Block;
 pub use ignore::Ignore;
-pub use keyword::{Keyword, KeywordKind};
+pub use keyword::{Exclusions, Keyword, KeywordKind, MatchKind, Segmentation};
 pub use mute::Mute;
-pub use notification::Notification;
+pub use notification::{HistoryFilter, Notification};
 pub use opt_out::OptOut;
+pub use pending_notification::PendingNotification;
+pub use quiet_hours::QuietHours;
+pub use scheduled_notification::ScheduledNotification;
+pub use user_settings::{ActivitySignals, Delivery, UserSettings};
 pub use user_state::{UserState, UserStateKind};
 
 use once_cell::sync::OnceCell;
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::OpenFlags;
+use rusqlite::{params, OpenFlags};
 use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
 
 use std::{fs, io::ErrorKind};
@@ -52,7 +61,7 @@ pub fn init() {
 
 	if let Err(error) = fs::create_dir(data_dir) {
 		if error.kind() != ErrorKind::AlreadyExists {
-			Err::<(), _>(error).expect("Failed to create data directory");
+			panic!("Failed to create data directory: {}", error);
 		}
 	}
 
@@ -71,13 +80,114 @@ pub fn init() {
 	OptOut::create_table();
 	Keyword::create_tables();
 	UserState::create_table();
+	UserSettings::create_table();
 	Notification::create_table();
+	PendingNotification::create_table();
+	QuietHours::create_table();
+	ScheduledNotification::create_table();
+
+	index::load();
 
 	if settings().database.backup {
 		let backup_dir = data_dir.join("backup");
 
 		start_backup_cycle(backup_dir);
 	}
+
+	let history_days = settings().database.notification_history_days;
+
+	if history_days != 0 {
+		Notification::start_pruning_cycle(history_days);
+	}
+}
+
+/// Adds a column to an existing table if it doesn't have it yet.
+///
+/// Tables are created with `CREATE TABLE IF NOT EXISTS`, so columns added after a table was first
+/// created need to be added to databases made by older versions separately. `definition` is the
+/// column's type and constraints, e.g. `"INTEGER NOT NULL DEFAULT 0"`.
+fn ensure_column(table: &str, column: &str, definition: &str) {
+	let conn = connection();
+
+	let exists = conn
+		.prepare(&format!("PRAGMA table_info({})", table))
+		.and_then(|mut stmt| {
+			stmt.query_map(params![], |row| row.get::<_, String>(1))?
+				.collect::<Result<Vec<_>, _>>()
+		})
+		.unwrap_or_else(|e| {
+			panic!("Failed to get columns of {} table: {}", table, e)
+		})
+		.iter()
+		.any(|name| name == column);
+
+	if !exists {
+		conn.execute(
+			&format!(
+				"ALTER TABLE {} ADD COLUMN {} {}",
+				table, column, definition
+			),
+			params![],
+		)
+		.unwrap_or_else(|e| {
+			panic!("Failed to add {} column to {} table: {}", column, table, e)
+		});
+	}
+}
+
+/// Rebuilds an existing table with a new definition if `column` isn't part of its primary key yet.
+///
+/// SQLite can't change the primary key of a table, so when columns are added to the key of a table
+/// made by an older version, its rows are copied into a new table made with `definition`, the
+/// table's columns and constraints, which replaces it. Columns added since the table was made
+/// should already have been added with [`ensure_column`](ensure_column).
+fn ensure_primary_key(table: &str, column: &str, definition: &str) {
+	let mut conn = connection();
+
+	let columns = conn
+		.prepare(&format!("PRAGMA table_info({})", table))
+		.and_then(|mut stmt| {
+			stmt.query_map(params![], |row| {
+				Ok((row.get::<_, String>(1)?, row.get::<_, i64>(5)?))
+			})?
+			.collect::<Result<Vec<_>, _>>()
+		})
+		.unwrap_or_else(|e| {
+			panic!("Failed to get columns of {} table: {}", table, e)
+		});
+
+	if columns
+		.iter()
+		.any(|(name, key_index)| name == column && *key_index > 0)
+	{
+		return;
+	}
+
+	let names = columns
+		.iter()
+		.map(|(name, _)| name.as_str())
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	conn.transaction()
+		.and_then(|tx| {
+			tx.execute_batch(&format!(
+				"CREATE TABLE {table}_rebuild ({definition});
+				INSERT INTO {table}_rebuild ({names}) SELECT {names} FROM {table};
+				DROP TABLE {table};
+				ALTER TABLE {table}_rebuild RENAME TO {table};",
+				table = table,
+				definition = definition,
+				names = names,
+			))?;
+
+			tx.commit()
+		})
+		.unwrap_or_else(|e| {
+			panic!("Failed to rebuild {} table with new key: {}", table, e)
+		});
+
+	log::info!("Rebuilt {} table with new primary key", table);
 }
 
 /// Convenience macro to make a blocking tokio task and await it, creating a
@@ -100,6 +210,124 @@ macro_rules! await_db {
 	}};
 }
 
+/// A set of flags defined with [`flag_set!`](crate::flag_set), each with a name used in commands.
+pub trait FlagSet: Copy + 'static {
+	/// Every flag, with the name used for it in commands.
+	const NAMED: &'static [(&'static str, Self)];
+
+	/// No flags.
+	fn none() -> Self;
+
+	/// Every flag.
+	fn all() -> Self;
+
+	fn is_empty(self) -> bool;
+
+	/// Checks if every flag in `other` is also in this set.
+	fn contains(self, other: Self) -> bool;
+
+	fn union(self, other: Self) -> Self;
+}
+
+/// Defines a set of flags that's stored in the DB as an integer, implementing
+/// [`FlagSet`](crate::db::FlagSet) for it.
+///
+/// Each flag is given as `NAME = bit, "name in commands";`.
+#[macro_export]
+macro_rules! flag_set {
+	(
+		$(#[$meta:meta])*
+		pub struct $set:ident;
+
+		$($(#[$flag_meta:meta])* $flag:ident = $bit:literal, $name:literal;)+
+	) => {
+		$(#[$meta])*
+		#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+		pub struct $set(u8);
+
+		impl $set {
+			$($(#[$flag_meta])* pub const $flag: Self = Self(1 << $bit);)+
+
+			/// No flags.
+			pub const fn none() -> Self {
+				Self(0)
+			}
+
+			/// Every flag.
+			pub const fn all() -> Self {
+				Self(0 $(| (1 << $bit))+)
+			}
+
+			pub const fn is_empty(self) -> bool {
+				self.0 == 0
+			}
+
+			/// Checks if every flag in `other` is also in this set.
+			pub const fn contains(self, other: Self) -> bool {
+				self.0 & other.0 == other.0
+			}
+
+			/// Checks if any flag in `other` is also in this set.
+			pub const fn intersects(self, other: Self) -> bool {
+				self.0 & other.0 != 0
+			}
+
+			pub const fn union(self, other: Self) -> Self {
+				Self(self.0 | other.0)
+			}
+
+			/// Iterates over the names of the flags in this set.
+			pub fn names(self) -> impl Iterator<Item = &'static str> {
+				<Self as $crate::db::FlagSet>::NAMED
+					.iter()
+					.filter(move |&&(_, flag)| self.contains(flag))
+					.map(|&(name, _)| name)
+			}
+		}
+
+		impl $crate::db::FlagSet for $set {
+			const NAMED: &'static [(&'static str, Self)] = &[$(($name, Self::$flag)),+];
+
+			fn none() -> Self {
+				Self::none()
+			}
+
+			fn all() -> Self {
+				Self::all()
+			}
+
+			fn is_empty(self) -> bool {
+				self.is_empty()
+			}
+
+			fn contains(self, other: Self) -> bool {
+				self.contains(other)
+			}
+
+			fn union(self, other: Self) -> Self {
+				self.union(other)
+			}
+		}
+
+		impl ::rusqlite::types::FromSql for $set {
+			fn column_result(
+				value: ::rusqlite::types::ValueRef<'_>,
+			) -> ::rusqlite::types::FromSqlResult<Self> {
+				match value.as_i64()? {
+					x if x & !(Self::all().0 as i64) == 0 => Ok(Self(x as u8)),
+					other => Err(::rusqlite::types::FromSqlError::OutOfRange(other)),
+				}
+			}
+		}
+
+		impl ::rusqlite::ToSql for $set {
+			fn to_sql(&self) -> ::rusqlite::Result<::rusqlite::types::ToSqlOutput<'_>> {
+				Ok(self.0.into())
+			}
+		}
+	};
+}
+
 trait IdI64Ext {
 	fn into_i64(self) -> i64;
 
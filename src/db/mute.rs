@@ -4,12 +4,12 @@
 //! Handling for mutes.
 
 use anyhow::Result;
-use rusqlite::{params, Row};
+use rusqlite::{params, Connection, Row};
 use serenity::model::id::{ChannelId, UserId};
 
 use crate::{await_db, db::connection};
 
-use super::IdI64Ext;
+use super::{index::index_mut, IdI64Ext};
 
 /// Represents a muted channel.
 #[derive(Debug, Clone)]
@@ -45,6 +45,15 @@ impl Mute {
 		.expect("Failed to create follows table");
 	}
 
+	/// Loads every mute from the DB, for the index.
+	pub(super) fn load_all(conn: &Connection) -> rusqlite::Result<Vec<Self>> {
+		let mut stmt = conn.prepare("SELECT user_id, channel_id FROM mutes")?;
+
+		let mutes = stmt.query_map(params![], Mute::from_row)?;
+
+		mutes.collect()
+	}
+
 	/// Fetches a list of mutes for the user with the given ID from the DB.
 	pub async fn user_mutes(user_id: UserId) -> Result<Vec<Mute>> {
 		await_db!("user mutes": |conn| {
@@ -79,12 +88,16 @@ impl Mute {
 	/// Inserts this mute into the DB.
 	pub async fn insert(self) -> Result<()> {
 		await_db!("insert mute": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"INSERT INTO mutes (user_id, channel_id)
 				VALUES (?, ?)",
 				params![self.user_id.into_i64(), self.channel_id.into_i64()],
 			)?;
 
+			index.update(|index| index.insert_mute(&self));
+
 			Ok(())
 		})
 	}
@@ -92,12 +105,16 @@ impl Mute {
 	/// Deletes this mute from the DB.
 	pub async fn delete(self) -> Result<()> {
 		await_db!("delete mute": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"DELETE FROM mutes
 				WHERE user_id = ? AND channel_id = ?",
 				params![self.user_id.into_i64(), self.channel_id.into_i64()],
 			)?;
 
+			index.update(|index| index.delete_mute(&self));
+
 			Ok(())
 		})
 	}
@@ -4,24 +4,64 @@
 //! Handling for sent notification messages.
 
 use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use rusqlite::{params, Row};
-use serenity::model::id::{MessageId, UserId};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use tokio::{task, time::interval};
+
+use std::time::Duration as StdDuration;
 
 use crate::{await_db, db::connection};
 
-use super::IdI64Ext;
+use super::{ensure_column, Exclusions, IdI64Ext, MatchKind, Segmentation};
 
-/// Represents a sent notification message.
+/// Represents a sent notification message, and one of the keywords it was sent for.
+///
+/// A notification about several keywords in the same message is stored once for each keyword,
+/// with the same `notification_message`.
 #[derive(Debug, Clone)]
 pub struct Notification {
 	/// The ID of the message that caused the notification to be sent.
 	pub original_message: MessageId,
 	/// The ID of the sent notification message.
 	pub notification_message: MessageId,
-	/// The keyword in the original message that caused the notification to be sent.
+	/// A keyword in the original message that caused the notification to be sent.
 	pub keyword: String,
+	/// How the keyword was matched against the original message.
+	pub match_kind: MatchKind,
+	/// Where in the original message the keyword could match.
+	pub segmentation: Segmentation,
+	/// Whether the keyword was matched case-sensitively.
+	pub case_sensitive: bool,
+	/// Parts of the original message the keyword isn't matched in, besides those the user
+	/// excludes for all of their keywords.
+	pub exclusions: Exclusions,
 	/// The ID of the user that the notification was sent to.
 	pub user_id: UserId,
+	/// The ID of the guild the original message was sent in, if it was stored.
+	pub guild_id: Option<GuildId>,
+	/// The ID of the channel the original message was sent in, if it was stored.
+	pub channel_id: Option<ChannelId>,
+	/// The ID of the author of the original message, if it was stored.
+	pub author_id: Option<UserId>,
+	/// Whether the keyword was only for the channel the original message was sent in, rather than
+	/// the whole guild.
+	pub channel_keyword: bool,
+	/// When the notification was sent, if it was stored.
+	pub sent_at: Option<DateTime<Utc>>,
+}
+
+/// Which notifications to include in a user's notification history.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+	/// Only include notifications about messages in this guild.
+	pub guild_id: Option<GuildId>,
+	/// Only include notifications for this keyword.
+	pub keyword: Option<String>,
+	/// Only include notifications sent at or after this time.
+	pub after: Option<DateTime<Utc>>,
+	/// Only include notifications sent before this time.
+	pub before: Option<DateTime<Utc>>,
 }
 
 impl Notification {
@@ -30,12 +70,32 @@ impl Notification {
 	/// - `notification_message`: `INTEGER`
 	/// - `keyword`: `TEXT`
 	/// - `user_id`: `INTEGER`
+	/// - `match_kind`: `INTEGER`
+	/// - `segmentation`: `INTEGER`
+	/// - `case_sensitive`: `INTEGER`
+	/// - `exclusions`: `INTEGER`
+	/// - `guild_id`: `INTEGER`
+	/// - `channel_id`: `INTEGER`
+	/// - `author_id`: `INTEGER`
+	/// - `channel_keyword`: `INTEGER`
+	/// - `sent_at`: `INTEGER`
 	fn from_row(row: &Row) -> rusqlite::Result<Self> {
 		Ok(Self {
 			original_message: MessageId::from_i64(row.get(0)?),
 			notification_message: MessageId::from_i64(row.get(1)?),
 			keyword: row.get(2)?,
 			user_id: UserId::from_i64(row.get(3)?),
+			match_kind: row.get(4)?,
+			segmentation: row.get(5)?,
+			case_sensitive: row.get(6)?,
+			exclusions: row.get(7)?,
+			guild_id: row.get::<_, Option<i64>>(8)?.map(GuildId::from_i64),
+			channel_id: row.get::<_, Option<i64>>(9)?.map(ChannelId::from_i64),
+			author_id: row.get::<_, Option<i64>>(10)?.map(UserId::from_i64),
+			channel_keyword: row.get(11)?,
+			sent_at: row
+				.get::<_, Option<i64>>(12)?
+				.map(|t| Utc.timestamp(t, 0)),
 		})
 	}
 
@@ -52,6 +112,64 @@ impl Notification {
 			params![],
 		)
 		.expect("Failed to create sent_notifications table");
+
+		ensure_column(
+			"sent_notifications",
+			"match_kind",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"sent_notifications",
+			"segmentation",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"sent_notifications",
+			"case_sensitive",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"sent_notifications",
+			"exclusions",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column("sent_notifications", "guild_id", "INTEGER");
+		ensure_column("sent_notifications", "channel_id", "INTEGER");
+		ensure_column("sent_notifications", "author_id", "INTEGER");
+		ensure_column(
+			"sent_notifications",
+			"channel_keyword",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column("sent_notifications", "sent_at", "INTEGER");
+	}
+
+	/// Starts removing notifications sent more than `days` days ago from the DB once a day.
+	///
+	/// Notifications stored by older versions, which don't have a time they were sent, are kept.
+	pub(super) fn start_pruning_cycle(days: u32) {
+		task::spawn(async move {
+			let mut daily = interval(StdDuration::from_secs(60 * 60 * 24));
+
+			loop {
+				daily.tick().await;
+
+				let before = Utc::now() - Duration::days(days.into());
+
+				match Self::delete_sent_before(before).await {
+					Ok(0) => {}
+					Ok(pruned) => {
+						log::info!("Pruned {} old notifications", pruned)
+					}
+					Err(e) => {
+						log::error!(
+							"Failed to prune old notifications: {:?}",
+							e
+						)
+					}
+				}
+			}
+		});
 	}
 
 	/// Fetches the notifications that were sent because of the given message from the DB.
@@ -60,7 +178,9 @@ impl Notification {
 	) -> Result<Vec<Self>> {
 		await_db!("notifications from message": |conn| {
 			let mut stmt = conn.prepare(
-				"SELECT original_message, notification_message, keyword, user_id
+				"SELECT original_message, notification_message, keyword, user_id,
+					match_kind, segmentation, case_sensitive, exclusions,
+					guild_id, channel_id, author_id, channel_keyword, sent_at
 				FROM sent_notifications
 				WHERE original_message = ?"
 			)?;
@@ -74,6 +194,92 @@ impl Notification {
 		})
 	}
 
+	/// Fetches the notifications that were sent as the given notification message from the DB,
+	/// one for each of its keywords.
+	pub async fn from_notification_message(
+		message_id: MessageId,
+	) -> Result<Vec<Self>> {
+		await_db!("notifications of notification message": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT original_message, notification_message, keyword, user_id,
+					match_kind, segmentation, case_sensitive, exclusions,
+					guild_id, channel_id, author_id, channel_keyword, sent_at
+				FROM sent_notifications
+				WHERE notification_message = ?"
+			)?;
+
+			let notifications = stmt.query_map(
+				params![message_id.into_i64()],
+				Self::from_row
+			)?;
+
+			notifications.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Fetches a page of the notification history of the user with the given ID from the DB, newest
+	/// first, along with the total number of notifications in their history.
+	///
+	/// Only notifications matching `filter` are included. Notifications stored by older versions,
+	/// which don't have a time they were sent, aren't included.
+	pub async fn user_history(
+		user_id: UserId,
+		filter: HistoryFilter,
+		offset: u32,
+		limit: u32,
+	) -> Result<(Vec<Self>, u32)> {
+		await_db!("notification history": |conn| {
+			const CONDITIONS: &str = "user_id = ?1
+				AND sent_at IS NOT NULL
+				AND (?2 IS NULL OR guild_id = ?2)
+				AND (?3 IS NULL OR keyword = ?3)
+				AND (?4 IS NULL OR sent_at >= ?4)
+				AND (?5 IS NULL OR sent_at < ?5)";
+
+			let user_id = user_id.into_i64();
+			let guild_id = filter.guild_id.map(|id| id.into_i64());
+			let after = filter.after.map(|time| time.timestamp());
+			let before = filter.before.map(|time| time.timestamp());
+
+			let total = conn.query_row(
+				&format!(
+					"SELECT COUNT(*) FROM sent_notifications WHERE {}",
+					CONDITIONS
+				),
+				params![user_id, guild_id, filter.keyword, after, before],
+				|row| row.get(0),
+			)?;
+
+			let mut stmt = conn.prepare(&format!(
+				"SELECT original_message, notification_message, keyword, user_id,
+					match_kind, segmentation, case_sensitive, exclusions,
+					guild_id, channel_id, author_id, channel_keyword, sent_at
+				FROM sent_notifications
+				WHERE {}
+				ORDER BY sent_at DESC, rowid DESC
+				LIMIT ?6 OFFSET ?7",
+				CONDITIONS
+			))?;
+
+			let notifications = stmt.query_map(
+				params![
+					user_id,
+					guild_id,
+					filter.keyword,
+					after,
+					before,
+					limit,
+					offset
+				],
+				Self::from_row,
+			)?;
+
+			let notifications = notifications.collect::<Result<_, _>>()?;
+
+			Ok((notifications, total))
+		})
+	}
+
 	/// Inserts this notification into the DB.
 	pub async fn insert(self) -> Result<()> {
 		await_db!("insert notification": |conn| {
@@ -82,14 +288,32 @@ impl Notification {
 					original_message,
 					notification_message,
 					keyword,
-					user_id
+					user_id,
+					match_kind,
+					segmentation,
+					case_sensitive,
+					exclusions,
+					guild_id,
+					channel_id,
+					author_id,
+					channel_keyword,
+					sent_at
 				)
-				VALUES (?, ?, ?, ?)",
+				VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
 				params![
 					self.original_message.into_i64(),
 					self.notification_message.into_i64(),
 					&*self.keyword,
-					self.user_id.into_i64()
+					self.user_id.into_i64(),
+					self.match_kind,
+					self.segmentation,
+					self.case_sensitive,
+					self.exclusions,
+					self.guild_id.map(|id| id.into_i64()),
+					self.channel_id.map(|id| id.into_i64()),
+					self.author_id.map(|id| id.into_i64()),
+					self.channel_keyword,
+					self.sent_at.map(|time| time.timestamp()),
 				],
 			)?;
 
@@ -97,7 +321,7 @@ impl Notification {
 		})
 	}
 
-	/// Removes this notification from the DB.
+	/// Removes this notification from the DB, along with the other keywords it was sent for.
 	pub async fn delete(self) -> Result<()> {
 		await_db!("delete notification": |conn| {
 			conn.execute(
@@ -110,6 +334,19 @@ impl Notification {
 		})
 	}
 
+	/// Removes the notifications sent before the given time from the DB, returning how many were
+	/// removed.
+	async fn delete_sent_before(before: DateTime<Utc>) -> Result<usize> {
+		await_db!("prune notifications": |conn| {
+			conn.execute(
+				"DELETE FROM sent_notifications
+				WHERE sent_at < ?",
+				params![before.timestamp()],
+			)
+			.map_err(Into::into)
+		})
+	}
+
 	/// Removes all notifications sent because of the given message from the DB.
 	pub async fn delete_notifications_of_message(
 		message_id: MessageId,
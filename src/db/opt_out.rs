@@ -4,12 +4,12 @@
 //! Handling for user opt-outs.
 
 use anyhow::Result;
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use serenity::model::id::UserId;
 
 use crate::{await_db, db::connection};
 
-use super::IdI64Ext;
+use super::{index::index_mut, IdI64Ext};
 
 /// Represents an opt-out made by a user.
 ///
@@ -33,6 +33,19 @@ impl OptOut {
 		.expect("Failed to create opt_outs table");
 	}
 
+	/// Loads every opt-out from the DB, for the index.
+	pub(super) fn load_all(conn: &Connection) -> rusqlite::Result<Vec<Self>> {
+		let mut stmt = conn.prepare("SELECT user_id FROM opt_outs")?;
+
+		let opt_outs = stmt.query_map(params![], |row| {
+			Ok(Self {
+				user_id: UserId::from_i64(row.get(0)?),
+			})
+		})?;
+
+		opt_outs.collect()
+	}
+
 	/// Checks if this opt-out already exists in the DB.
 	pub async fn exists(self) -> Result<bool> {
 		await_db!("opt-out exists": |conn| {
@@ -48,12 +61,16 @@ impl OptOut {
 	/// Adds this opt-out to the DB.
 	pub async fn insert(self) -> Result<()> {
 		await_db!("insert opt-out": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"INSERT INTO opt_outs (user_id)
 				VALUES (?)",
 				params![self.user_id.into_i64()],
 			)?;
 
+			index.update(|index| index.insert_opt_out(&self));
+
 			Ok(())
 		})
 	}
@@ -61,12 +78,16 @@ impl OptOut {
 	/// Deletes this opt-out from the DB.
 	pub async fn delete(self) -> Result<()> {
 		await_db!("delete opt-out": |conn| {
+			let mut index = index_mut();
+
 			conn.execute(
 				"DELETE FROM opt_outs
 				WHERE user_id = ?",
 				params![self.user_id.into_i64()],
 			)?;
 
+			index.update(|index| index.delete_opt_out(&self));
+
 			Ok(())
 		})
 	}
@@ -0,0 +1,216 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Handling for pending notifications; keywords that matched while a user's notifications are
+//! being collected into digests.
+
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Row};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+use crate::{await_db, db::connection};
+
+use super::{IdI64Ext, MatchKind};
+
+/// Represents a keyword that matched a message, waiting to be included in a digest.
+///
+/// A message that matched several keywords of the same user is stored once for each keyword.
+#[derive(Debug, Clone)]
+pub struct PendingNotification {
+	/// The ID of the user to notify.
+	pub user_id: UserId,
+	pub guild_id: GuildId,
+	pub channel_id: ChannelId,
+	/// The ID of the message the keyword matched.
+	pub message_id: MessageId,
+	/// The name of the author of the message.
+	pub author_name: String,
+	/// The start of the message's content, shortened when the keyword matched.
+	pub content: String,
+	pub keyword: String,
+	/// How the keyword was matched against the message.
+	pub match_kind: MatchKind,
+	/// When the keyword matched the message.
+	pub created_at: DateTime<Utc>,
+}
+
+impl PendingNotification {
+	/// Builds a `PendingNotification` from a `Row`, in this order:
+	/// - user_id: INTEGER
+	/// - guild_id: INTEGER
+	/// - channel_id: INTEGER
+	/// - message_id: INTEGER
+	/// - author_name: TEXT
+	/// - content: TEXT
+	/// - keyword: TEXT
+	/// - match_kind: INTEGER
+	/// - created_at: INTEGER
+	fn from_row(row: &Row) -> rusqlite::Result<Self> {
+		Ok(Self {
+			user_id: UserId::from_i64(row.get(0)?),
+			guild_id: GuildId::from_i64(row.get(1)?),
+			channel_id: ChannelId::from_i64(row.get(2)?),
+			message_id: MessageId::from_i64(row.get(3)?),
+			author_name: row.get(4)?,
+			content: row.get(5)?,
+			keyword: row.get(6)?,
+			match_kind: row.get(7)?,
+			created_at: Utc.timestamp(row.get(8)?, 0),
+		})
+	}
+
+	/// Creates the DB table for storing pending notifications.
+	pub(super) fn create_table() {
+		let conn = connection();
+		conn.execute(
+			"CREATE TABLE IF NOT EXISTS pending_notifications (
+			user_id INTEGER NOT NULL,
+			guild_id INTEGER NOT NULL,
+			channel_id INTEGER NOT NULL,
+			message_id INTEGER NOT NULL,
+			author_name TEXT NOT NULL,
+			content TEXT NOT NULL,
+			keyword TEXT NOT NULL,
+			match_kind INTEGER NOT NULL,
+			created_at INTEGER NOT NULL
+			)",
+			params![],
+		)
+		.expect("Failed to create pending_notifications table");
+	}
+
+	/// Inserts pending notifications into the DB.
+	pub async fn insert_all(notifications: Vec<Self>) -> Result<()> {
+		await_db!("insert pending notifications": |conn| {
+			let mut stmt = conn.prepare(
+				"INSERT INTO pending_notifications (
+					user_id,
+					guild_id,
+					channel_id,
+					message_id,
+					author_name,
+					content,
+					keyword,
+					match_kind,
+					created_at
+				)
+				VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+			)?;
+
+			for notification in notifications {
+				stmt.execute(params![
+					notification.user_id.into_i64(),
+					notification.guild_id.into_i64(),
+					notification.channel_id.into_i64(),
+					notification.message_id.into_i64(),
+					notification.author_name,
+					notification.content,
+					notification.keyword,
+					notification.match_kind,
+					notification.created_at.timestamp(),
+				])?;
+			}
+
+			Ok(())
+		})
+	}
+
+	/// Fetches the users with pending notifications from the DB, along with when their oldest
+	/// pending notification was created.
+	pub async fn pending_users() -> Result<Vec<(UserId, DateTime<Utc>)>> {
+		await_db!("pending notification users": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id, MIN(created_at)
+				FROM pending_notifications
+				GROUP BY user_id"
+			)?;
+
+			let users = stmt.query_map(params![], |row| {
+				Ok((
+					UserId::from_i64(row.get(0)?),
+					Utc.timestamp(row.get(1)?, 0),
+				))
+			})?;
+
+			users.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Fetches the pending notifications of the user with the given ID from the DB, oldest first.
+	pub async fn user_notifications(user_id: UserId) -> Result<Vec<Self>> {
+		await_db!("user pending notifications": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id, guild_id, channel_id, message_id, author_name,
+					content, keyword, match_kind, created_at
+				FROM pending_notifications
+				WHERE user_id = ?
+				ORDER BY created_at, rowid"
+			)?;
+
+			let notifications = stmt.query_map(
+				params![user_id.into_i64()],
+				Self::from_row
+			)?;
+
+			notifications.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Fetches the IDs of the users with pending notifications about the given message from the DB.
+	pub async fn users_of_message(
+		message_id: MessageId,
+	) -> Result<Vec<UserId>> {
+		await_db!("pending notification users of message": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT DISTINCT user_id
+				FROM pending_notifications
+				WHERE message_id = ?"
+			)?;
+
+			let users = stmt.query_map(
+				params![message_id.into_i64()],
+				|row| row.get(0).map(UserId::from_i64),
+			)?;
+
+			users.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Removes pending notifications that were sent in a digest from the DB.
+	///
+	/// Notifications created after they were fetched aren't removed.
+	pub async fn delete_sent(notifications: Vec<Self>) -> Result<()> {
+		await_db!("delete pending notifications": |conn| {
+			let mut stmt = conn.prepare(
+				"DELETE FROM pending_notifications
+				WHERE user_id = ? AND message_id = ? AND keyword = ?",
+			)?;
+
+			for notification in notifications {
+				stmt.execute(params![
+					notification.user_id.into_i64(),
+					notification.message_id.into_i64(),
+					notification.keyword,
+				])?;
+			}
+
+			Ok(())
+		})
+	}
+
+	/// Removes all pending notifications about the given message from the DB.
+	pub async fn delete_notifications_of_message(
+		message_id: MessageId,
+	) -> Result<()> {
+		await_db!("delete pending notifications of message": |conn| {
+			conn.execute(
+				"DELETE FROM pending_notifications
+				WHERE message_id = ?",
+				params![message_id.into_i64()],
+			)?;
+
+			Ok(())
+		})
+	}
+}
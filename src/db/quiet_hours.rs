@@ -0,0 +1,200 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Handling for quiet hours; times of the week users don't want to be notified.
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDateTime, Timelike, Weekday};
+use rusqlite::{params, Row};
+use serenity::model::id::UserId;
+
+use crate::{await_db, db::connection};
+
+use super::IdI64Ext;
+
+/// Represents a window of quiet hours on one day of the week, in the user's time zone.
+///
+/// Windows that end before they start continue past midnight into the next day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+	/// The ID of the user who doesn't want to be notified.
+	pub user_id: UserId,
+	/// The day the window starts on.
+	pub day: Weekday,
+	/// The minute of the day the window starts at.
+	pub start: u16,
+	/// The minute of the day the window ends at, not included in the window.
+	pub end: u16,
+}
+
+impl QuietHours {
+	/// Builds a `QuietHours` from a `Row`, in this order:
+	/// - user_id: INTEGER
+	/// - day: INTEGER
+	/// - start_minute: INTEGER
+	/// - end_minute: INTEGER
+	fn from_row(row: &Row) -> rusqlite::Result<Self> {
+		let day = match row.get::<_, u8>(1)? {
+			0 => Weekday::Mon,
+			1 => Weekday::Tue,
+			2 => Weekday::Wed,
+			3 => Weekday::Thu,
+			4 => Weekday::Fri,
+			5 => Weekday::Sat,
+			6 => Weekday::Sun,
+			other => {
+				return Err(rusqlite::Error::IntegralValueOutOfRange(
+					1,
+					other.into(),
+				))
+			}
+		};
+
+		Ok(Self {
+			user_id: UserId::from_i64(row.get(0)?),
+			day,
+			start: row.get(2)?,
+			end: row.get(3)?,
+		})
+	}
+
+	/// Creates the DB table for storing quiet hours.
+	pub(super) fn create_table() {
+		let conn = connection();
+		conn.execute(
+			"CREATE TABLE IF NOT EXISTS quiet_hours (
+			user_id INTEGER NOT NULL,
+			day INTEGER NOT NULL,
+			start_minute INTEGER NOT NULL,
+			end_minute INTEGER NOT NULL,
+			PRIMARY KEY (user_id, day, start_minute, end_minute)
+			)",
+			params![],
+		)
+		.expect("Failed to create quiet_hours table");
+	}
+
+	/// Checks if the given time, in the user's time zone, is in this window.
+	pub fn contains(&self, time: NaiveDateTime) -> bool {
+		let minute = (time.hour() * 60 + time.minute()) as u16;
+		let day = time.weekday();
+
+		if self.start < self.end {
+			day == self.day && self.start <= minute && minute < self.end
+		} else {
+			(day == self.day && minute >= self.start)
+				|| (day == self.day.succ() && minute < self.end)
+		}
+	}
+
+	/// Fetches the quiet hours of the user with the given ID from the DB.
+	pub async fn user_quiet_hours(user_id: UserId) -> Result<Vec<Self>> {
+		await_db!("user quiet hours": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id, day, start_minute, end_minute
+				FROM quiet_hours
+				WHERE user_id = ?
+				ORDER BY day, start_minute"
+			)?;
+
+			let quiet_hours =
+				stmt.query_map(params![user_id.into_i64()], Self::from_row)?;
+
+			quiet_hours.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Adds this window of quiet hours to the DB, if it isn't there already.
+	pub async fn insert(self) -> Result<()> {
+		await_db!("insert quiet hours": |conn| {
+			conn.execute(
+				"INSERT OR IGNORE INTO quiet_hours (
+					user_id, day, start_minute, end_minute
+				)
+				VALUES (?, ?, ?, ?)",
+				params![
+					self.user_id.into_i64(),
+					self.day.num_days_from_monday(),
+					self.start,
+					self.end,
+				],
+			)?;
+
+			Ok(())
+		})
+	}
+
+	/// Deletes this window of quiet hours from the DB.
+	///
+	/// Returns whether it existed.
+	pub async fn delete(self) -> Result<bool> {
+		await_db!("delete quiet hours": |conn| {
+			let deleted = conn.execute(
+				"DELETE FROM quiet_hours
+				WHERE user_id = ? AND day = ? AND start_minute = ? AND end_minute = ?",
+				params![
+					self.user_id.into_i64(),
+					self.day.num_days_from_monday(),
+					self.start,
+					self.end,
+				],
+			)?;
+
+			Ok(deleted != 0)
+		})
+	}
+
+	/// Deletes all of the quiet hours of the user with the given ID from the DB.
+	pub async fn delete_user_quiet_hours(user_id: UserId) -> Result<usize> {
+		await_db!("delete user quiet hours": |conn| {
+			conn.execute(
+				"DELETE FROM quiet_hours
+				WHERE user_id = ?",
+				params![user_id.into_i64()],
+			)
+			.map_err(Into::into)
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::NaiveDate;
+
+	fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+		// June 14, 2021 was a Monday
+		NaiveDate::from_ymd(2021, 6, day).and_hms(hour, minute, 0)
+	}
+
+	fn window(day: Weekday, start: u16, end: u16) -> QuietHours {
+		QuietHours {
+			user_id: UserId(1),
+			day,
+			start,
+			end,
+		}
+	}
+
+	#[test]
+	fn same_day_window() {
+		let lunch = window(Weekday::Mon, 12 * 60, 13 * 60);
+
+		assert!(lunch.contains(at(14, 12, 0)));
+		assert!(lunch.contains(at(14, 12, 59)));
+		assert!(!lunch.contains(at(14, 13, 0)));
+		assert!(!lunch.contains(at(14, 11, 59)));
+		assert!(!lunch.contains(at(15, 12, 30)));
+	}
+
+	#[test]
+	fn overnight_window() {
+		let night = window(Weekday::Sun, 22 * 60, 7 * 60);
+
+		assert!(night.contains(at(20, 23, 0)));
+		assert!(night.contains(at(21, 6, 59)));
+		assert!(!night.contains(at(21, 7, 0)));
+		assert!(!night.contains(at(20, 6, 0)));
+		assert!(!night.contains(at(14, 23, 0)));
+	}
+}
@@ -0,0 +1,302 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Handling for scheduled notifications; notifications waiting for the user's patience duration to
+//! pass, or to be retried after failing to send.
+//!
+//! These are stored so they aren't lost if the bot restarts while they're waiting.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rusqlite::{params, Row};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+use crate::{
+	await_db,
+	db::connection,
+	global::{NOTIFICATION_MAX_ATTEMPTS, NOTIFICATION_RETRY_DELAY},
+};
+
+use super::{ensure_column, IdI64Ext};
+
+/// Represents a notification about a message that hasn't been sent yet.
+///
+/// Which keywords matched isn't stored; they're checked against the message again when it's sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledNotification {
+	/// The ID of the user to notify.
+	pub user_id: UserId,
+	pub guild_id: GuildId,
+	pub channel_id: ChannelId,
+	/// The ID of the message the user's keywords matched.
+	pub message_id: MessageId,
+	/// When the notification should be sent; the end of the user's patience duration, or when it
+	/// should be retried.
+	pub due_at: DateTime<Utc>,
+	/// How many times sending the notification has failed.
+	pub attempts: u32,
+	/// Whether sending the notification failed too many times, and it won't be retried.
+	///
+	/// `due_at` is when dead notifications were marked dead.
+	pub dead: bool,
+	/// Whether the user's keywords only matched the message after it was edited.
+	pub edited: bool,
+}
+
+impl ScheduledNotification {
+	/// Builds a `ScheduledNotification` from a `Row`, in this order:
+	/// - user_id: INTEGER
+	/// - guild_id: INTEGER
+	/// - channel_id: INTEGER
+	/// - message_id: INTEGER
+	/// - due_at: INTEGER
+	/// - attempts: INTEGER
+	/// - dead: INTEGER
+	/// - edited: INTEGER
+	fn from_row(row: &Row) -> rusqlite::Result<Self> {
+		Ok(Self {
+			user_id: UserId::from_i64(row.get(0)?),
+			guild_id: GuildId::from_i64(row.get(1)?),
+			channel_id: ChannelId::from_i64(row.get(2)?),
+			message_id: MessageId::from_i64(row.get(3)?),
+			due_at: Utc.timestamp(row.get(4)?, 0),
+			attempts: row.get(5)?,
+			dead: row.get(6)?,
+			edited: row.get(7)?,
+		})
+	}
+
+	/// Creates the DB table for storing scheduled notifications.
+	pub(super) fn create_table() {
+		let conn = connection();
+		conn.execute(
+			"CREATE TABLE IF NOT EXISTS scheduled_notifications (
+			user_id INTEGER NOT NULL,
+			guild_id INTEGER NOT NULL,
+			channel_id INTEGER NOT NULL,
+			message_id INTEGER NOT NULL,
+			due_at INTEGER NOT NULL,
+			attempts INTEGER NOT NULL DEFAULT 0,
+			dead INTEGER NOT NULL DEFAULT 0,
+			edited INTEGER NOT NULL DEFAULT 0,
+			PRIMARY KEY (user_id, message_id)
+			)",
+			params![],
+		)
+		.expect("Failed to create scheduled_notifications table");
+
+		ensure_column(
+			"scheduled_notifications",
+			"edited",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+	}
+
+	/// Records that sending this notification failed at `now`.
+	///
+	/// The notification is scheduled to be retried after a delay that doubles with each attempt,
+	/// starting from [`NOTIFICATION_RETRY_DELAY`](NOTIFICATION_RETRY_DELAY). Once it's failed
+	/// [`NOTIFICATION_MAX_ATTEMPTS`](NOTIFICATION_MAX_ATTEMPTS) times, it's marked dead instead.
+	pub fn fail(&mut self, now: DateTime<Utc>) {
+		self.attempts += 1;
+
+		if self.attempts >= NOTIFICATION_MAX_ATTEMPTS {
+			self.dead = true;
+			self.due_at = now;
+		} else {
+			let delay = NOTIFICATION_RETRY_DELAY * 2u32.pow(self.attempts - 1);
+
+			self.due_at = now
+				+ Duration::from_std(delay)
+					.expect("Notification retry delay out of range");
+		}
+	}
+
+	/// Fetches the notifications due before `due_before` that haven't been attempted yet from the
+	/// DB.
+	///
+	/// When the bot starts, these are the notifications that were waiting for the user's patience
+	/// duration when it stopped.
+	pub async fn unattempted(due_before: DateTime<Utc>) -> Result<Vec<Self>> {
+		await_db!("unattempted scheduled notifications": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id, guild_id, channel_id, message_id, due_at,
+					attempts, dead, edited
+				FROM scheduled_notifications
+				WHERE attempts = 0 AND NOT dead AND due_at < ?
+				ORDER BY due_at"
+			)?;
+
+			let notifications =
+				stmt.query_map(params![due_before.timestamp()], Self::from_row)?;
+
+			notifications.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Fetches the notifications that failed to send and are due to be retried at `now` from the
+	/// DB.
+	pub async fn due_retries(now: DateTime<Utc>) -> Result<Vec<Self>> {
+		await_db!("due scheduled notifications": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id, guild_id, channel_id, message_id, due_at,
+					attempts, dead, edited
+				FROM scheduled_notifications
+				WHERE attempts > 0 AND NOT dead AND due_at <= ?
+				ORDER BY due_at"
+			)?;
+
+			let notifications =
+				stmt.query_map(params![now.timestamp()], Self::from_row)?;
+
+			notifications.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Counts the dead notifications in the DB.
+	pub async fn dead_count() -> Result<u64> {
+		await_db!("count dead notifications": |conn| {
+			conn.query_row(
+				"SELECT COUNT(*) FROM scheduled_notifications WHERE dead",
+				params![],
+				|row| row.get::<_, i64>(0),
+			)
+			.map(|count| count as u64)
+			.map_err(Into::into)
+		})
+	}
+
+	/// Removes the notifications that were marked dead before the given time from the DB.
+	pub async fn delete_dead_before(before: DateTime<Utc>) -> Result<()> {
+		await_db!("delete dead scheduled notifications": |conn| {
+			conn.execute(
+				"DELETE FROM scheduled_notifications
+				WHERE dead AND due_at < ?",
+				params![before.timestamp()],
+			)?;
+
+			Ok(())
+		})
+	}
+
+	/// Adds this notification to the DB, replacing the existing notification of the same user about
+	/// the same message, if any.
+	pub async fn insert(self) -> Result<()> {
+		await_db!("insert scheduled notification": |conn| {
+			conn.execute(
+				"INSERT OR REPLACE INTO scheduled_notifications (
+					user_id,
+					guild_id,
+					channel_id,
+					message_id,
+					due_at,
+					attempts,
+					dead,
+					edited
+				)
+				VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+				params![
+					self.user_id.into_i64(),
+					self.guild_id.into_i64(),
+					self.channel_id.into_i64(),
+					self.message_id.into_i64(),
+					self.due_at.timestamp(),
+					self.attempts,
+					self.dead,
+					self.edited,
+				],
+			)?;
+
+			Ok(())
+		})
+	}
+
+	/// Fetches the IDs of the users with scheduled notifications about the given message from the
+	/// DB, including dead notifications.
+	pub async fn users_of_message(
+		message_id: MessageId,
+	) -> Result<Vec<UserId>> {
+		await_db!("scheduled notification users of message": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id
+				FROM scheduled_notifications
+				WHERE message_id = ?"
+			)?;
+
+			let users = stmt.query_map(
+				params![message_id.into_i64()],
+				|row| row.get(0).map(UserId::from_i64),
+			)?;
+
+			users.map(|res| res.map_err(Into::into)).collect()
+		})
+	}
+
+	/// Deletes this notification from the DB.
+	pub async fn delete(self) -> Result<()> {
+		await_db!("delete scheduled notification": |conn| {
+			conn.execute(
+				"DELETE FROM scheduled_notifications
+				WHERE user_id = ? AND message_id = ?",
+				params![self.user_id.into_i64(), self.message_id.into_i64()],
+			)?;
+
+			Ok(())
+		})
+	}
+
+	/// Deletes all scheduled notifications about the given message from the DB.
+	pub async fn delete_notifications_of_message(
+		message_id: MessageId,
+	) -> Result<()> {
+		await_db!("delete scheduled notifications of message": |conn| {
+			conn.execute(
+				"DELETE FROM scheduled_notifications
+				WHERE message_id = ?",
+				params![message_id.into_i64()],
+			)?;
+
+			Ok(())
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn retry_backoff() {
+		let start = Utc.timestamp(1_600_000_000, 0);
+
+		let mut notification = ScheduledNotification {
+			user_id: UserId(1),
+			guild_id: GuildId(2),
+			channel_id: ChannelId(3),
+			message_id: MessageId(4),
+			due_at: start,
+			attempts: 0,
+			dead: false,
+			edited: false,
+		};
+
+		let base = Duration::from_std(NOTIFICATION_RETRY_DELAY).unwrap();
+
+		notification.fail(start);
+		assert_eq!(notification.due_at, start + base);
+
+		notification.fail(start);
+		assert_eq!(notification.due_at, start + base * 2);
+
+		notification.fail(start);
+		assert_eq!(notification.due_at, start + base * 4);
+		assert!(!notification.dead);
+
+		for _ in 3..NOTIFICATION_MAX_ATTEMPTS {
+			notification.fail(start);
+		}
+
+		assert!(notification.dead);
+		assert_eq!(notification.attempts, NOTIFICATION_MAX_ATTEMPTS);
+	}
+}
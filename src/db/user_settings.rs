@@ -0,0 +1,342 @@
+// Copyright 2021 ThatsNoMoon
+// Licensed under the Open Software License version 3.0
+
+//! Handling for user settings; preferences about how a user's keywords are matched and how they're
+//! notified.
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serenity::model::id::UserId;
+
+use crate::{await_db, db::connection, flag_set, monitoring::Timer};
+
+use super::{
+	ensure_column,
+	index::{index, index_mut},
+	Exclusions, IdI64Ext,
+};
+
+/// Settings chosen by a user.
+///
+/// Users that haven't chosen any settings have the defaults, where every setting is `None`,
+/// `false`, or empty, notifications are sent immediately, and typing or sending messages in a
+/// server counts as activity there.
+#[derive(Debug, Clone)]
+pub struct UserSettings {
+	pub user_id: UserId,
+	/// Name of the language the user's stemmed keywords are stemmed in.
+	pub stem_language: Option<String>,
+	/// Whether accents are ignored when matching the user's keywords.
+	pub strip_accents: bool,
+	/// Whether confusable characters are treated as equal when matching the user's keywords.
+	pub match_confusables: bool,
+	/// Parts of messages none of the user's keywords are matched in.
+	pub exclusions: Exclusions,
+	/// Number of messages sent before a highlighted message to show in its notifications, or 0
+	/// to show none.
+	pub context_messages: u8,
+	/// When the user's notifications are sent.
+	pub delivery: Delivery,
+	/// Name of the time zone the user's daily digests and quiet hours are in, or `None` for UTC.
+	pub timezone: Option<String>,
+	/// Minimum number of minutes between notifications about the same channel, or `None` to use
+	/// the hoster's default.
+	pub cooldown_minutes: Option<u16>,
+	/// Maximum number of notifications sent each hour, or `None` to use the hoster's default.
+	///
+	/// 0 means there's no limit.
+	pub hourly_limit: Option<u16>,
+	/// Whether notifications during the user's [`QuietHours`](super::QuietHours) are dropped,
+	/// rather than sent together once their quiet hours end.
+	pub drop_in_quiet_hours: bool,
+	/// Whether the user is only notified while they're idle, on do not disturb, or offline.
+	pub only_when_away: bool,
+	/// Kinds of activity in a server that stop the user from being notified about messages sent
+	/// there just before, besides replying or reacting in the same channel.
+	pub activity_signals: ActivitySignals,
+}
+
+flag_set! {
+	/// Set of kinds of activity in a server that show a user has probably seen a message there.
+	pub struct ActivitySignals;
+
+	/// Starting to type in any channel.
+	TYPING = 0, "typing";
+	/// Sending a message in any channel.
+	MESSAGES = 1, "messages";
+	/// Joining, leaving, muting, or unmuting in a voice channel.
+	VOICE = 2, "voice";
+}
+
+/// Typing and sending messages count by default, since they're the clearest signs that a user is
+/// looking at a server.
+impl Default for ActivitySignals {
+	fn default() -> Self {
+		Self::TYPING.union(Self::MESSAGES)
+	}
+}
+
+/// When a user's notifications are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+	/// Each notification is sent as soon as its keywords match.
+	Immediate,
+	/// Notifications are collected and sent together in a digest, once the oldest of them has
+	/// waited the given number of minutes.
+	Batched { minutes: u16 },
+	/// Notifications are collected and sent together in a digest once a day, at the given minute
+	/// of the day in the user's time zone.
+	Daily { minute: u16 },
+}
+
+impl Delivery {
+	const IMMEDIATE: u8 = 0;
+	const BATCHED: u8 = 1;
+	const DAILY: u8 = 2;
+
+	/// Splits this delivery mode into the two values it's stored in the DB as.
+	fn into_parts(self) -> (u8, u16) {
+		match self {
+			Delivery::Immediate => (Self::IMMEDIATE, 0),
+			Delivery::Batched { minutes } => (Self::BATCHED, minutes),
+			Delivery::Daily { minute } => (Self::DAILY, minute),
+		}
+	}
+
+	/// Rebuilds a delivery mode from the two values it's stored in the DB as.
+	///
+	/// `column` is the index of the column the kind of delivery was read from, for errors.
+	fn from_parts(
+		column: usize,
+		kind: u8,
+		value: u16,
+	) -> rusqlite::Result<Self> {
+		match kind {
+			Self::IMMEDIATE => Ok(Delivery::Immediate),
+			Self::BATCHED => Ok(Delivery::Batched { minutes: value }),
+			Self::DAILY => Ok(Delivery::Daily { minute: value }),
+			other => Err(rusqlite::Error::IntegralValueOutOfRange(
+				column,
+				other.into(),
+			)),
+		}
+	}
+}
+
+impl UserSettings {
+	/// Builds `UserSettings` from a `Row`, in this order:
+	/// - user_id: INTEGER
+	/// - stem_language: TEXT
+	/// - strip_accents: INTEGER
+	/// - match_confusables: INTEGER
+	/// - exclusions: INTEGER
+	/// - context_messages: INTEGER
+	/// - delivery: INTEGER
+	/// - delivery_value: INTEGER
+	/// - timezone: TEXT
+	/// - cooldown_minutes: INTEGER
+	/// - hourly_limit: INTEGER
+	/// - drop_in_quiet_hours: INTEGER
+	/// - only_when_away: INTEGER
+	/// - activity_signals: INTEGER
+	fn from_row(row: &Row) -> rusqlite::Result<Self> {
+		Ok(Self {
+			user_id: UserId::from_i64(row.get(0)?),
+			stem_language: row.get(1)?,
+			strip_accents: row.get(2)?,
+			match_confusables: row.get(3)?,
+			exclusions: row.get(4)?,
+			context_messages: row.get(5)?,
+			delivery: Delivery::from_parts(6, row.get(6)?, row.get(7)?)?,
+			timezone: row.get(8)?,
+			cooldown_minutes: row.get(9)?,
+			hourly_limit: row.get(10)?,
+			drop_in_quiet_hours: row.get(11)?,
+			only_when_away: row.get(12)?,
+			activity_signals: row.get(13)?,
+		})
+	}
+
+	/// Gets the default settings for the user with the given ID.
+	fn default_for(user_id: UserId) -> Self {
+		Self {
+			user_id,
+			stem_language: None,
+			strip_accents: false,
+			match_confusables: false,
+			exclusions: Exclusions::none(),
+			context_messages: 0,
+			delivery: Delivery::Immediate,
+			timezone: None,
+			cooldown_minutes: None,
+			hourly_limit: None,
+			drop_in_quiet_hours: false,
+			only_when_away: false,
+			activity_signals: ActivitySignals::default(),
+		}
+	}
+
+	/// Creates DB table for storing user settings
+	pub(super) fn create_table() {
+		let conn = connection();
+		conn.execute(
+			"CREATE TABLE IF NOT EXISTS user_settings (
+			user_id INTEGER PRIMARY KEY,
+			stem_language TEXT
+			)",
+			params![],
+		)
+		.expect("Failed to create user_settings table");
+
+		ensure_column(
+			"user_settings",
+			"strip_accents",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"match_confusables",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"exclusions",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"context_messages",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"delivery",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"delivery_value",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column("user_settings", "timezone", "TEXT");
+		ensure_column("user_settings", "cooldown_minutes", "INTEGER");
+		ensure_column("user_settings", "hourly_limit", "INTEGER");
+		ensure_column(
+			"user_settings",
+			"drop_in_quiet_hours",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"only_when_away",
+			"INTEGER NOT NULL DEFAULT 0",
+		);
+		ensure_column(
+			"user_settings",
+			"activity_signals",
+			"INTEGER NOT NULL DEFAULT 3",
+		);
+	}
+
+	/// Loads the settings of every user who has chosen any from the DB, for the index.
+	pub(super) fn load_all(conn: &Connection) -> rusqlite::Result<Vec<Self>> {
+		let mut stmt = conn.prepare(
+			"SELECT user_id, stem_language, strip_accents, match_confusables,
+				exclusions, context_messages, delivery, delivery_value, timezone,
+				cooldown_minutes, hourly_limit, drop_in_quiet_hours, only_when_away,
+				activity_signals
+			FROM user_settings",
+		)?;
+
+		let settings = stmt.query_map(params![], Self::from_row)?;
+
+		settings.collect()
+	}
+
+	/// Fetches the settings of the user with the given ID from the in-memory index.
+	///
+	/// Returns the default settings if the user hasn't chosen any.
+	pub fn indexed_user_settings(user_id: UserId) -> Self {
+		let _timer = Timer::index("user settings");
+
+		index()
+			.user_settings(user_id)
+			.unwrap_or_else(|| Self::default_for(user_id))
+	}
+
+	/// Fetches the settings of the user with the given ID from the DB.
+	///
+	/// Returns the default settings if the user hasn't chosen any.
+	pub async fn user_settings(user_id: UserId) -> Result<Self> {
+		await_db!("user settings": |conn| {
+			let mut stmt = conn.prepare(
+				"SELECT user_id, stem_language, strip_accents, match_confusables,
+				exclusions, context_messages, delivery, delivery_value, timezone,
+				cooldown_minutes, hourly_limit, drop_in_quiet_hours, only_when_away,
+				activity_signals
+				FROM user_settings
+				WHERE user_id = ?"
+			)?;
+
+			let settings = stmt
+				.query_row(params![user_id.into_i64()], Self::from_row)
+				.optional()?;
+
+			Ok(settings.unwrap_or_else(|| Self::default_for(user_id)))
+		})
+	}
+
+	/// Sets the settings of the user in the DB.
+	pub async fn set(self) -> Result<()> {
+		await_db!("set user settings": |conn| {
+			let mut index = index_mut();
+
+			let (delivery, delivery_value) = self.delivery.into_parts();
+
+			conn.execute(
+				"INSERT INTO user_settings (
+					user_id, stem_language, strip_accents, match_confusables,
+					exclusions, context_messages, delivery, delivery_value,
+					timezone, cooldown_minutes, hourly_limit, drop_in_quiet_hours,
+					only_when_away, activity_signals
+				)
+				VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+				ON CONFLICT (user_id)
+					DO UPDATE SET
+						stem_language = excluded.stem_language,
+						strip_accents = excluded.strip_accents,
+						match_confusables = excluded.match_confusables,
+						exclusions = excluded.exclusions,
+						context_messages = excluded.context_messages,
+						delivery = excluded.delivery,
+						delivery_value = excluded.delivery_value,
+						timezone = excluded.timezone,
+						cooldown_minutes = excluded.cooldown_minutes,
+						hourly_limit = excluded.hourly_limit,
+						drop_in_quiet_hours = excluded.drop_in_quiet_hours,
+						only_when_away = excluded.only_when_away,
+						activity_signals = excluded.activity_signals",
+				params![
+					self.user_id.into_i64(),
+					self.stem_language,
+					self.strip_accents,
+					self.match_confusables,
+					self.exclusions,
+					self.context_messages,
+					delivery,
+					delivery_value,
+					self.timezone,
+					self.cooldown_minutes,
+					self.hourly_limit,
+					self.drop_in_quiet_hours,
+					self.only_when_away,
+					self.activity_signals,
+				],
+			)?;
+
+			index.update(|index| index.set_user_settings(self));
+
+			Ok(())
+		})
+	}
+}
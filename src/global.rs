@@ -6,8 +6,83 @@
 use once_cell::sync::OnceCell;
 use serenity::model::id::UserId;
 
-/// How many times to retry notifications after internal server errors from Discord.
-pub const NOTIFICATION_RETRIES: u8 = 5;
+use std::time::Duration;
+
+/// How many times a scheduled notification can fail to send before it's given up on.
+pub const NOTIFICATION_MAX_ATTEMPTS: u32 = 8;
+
+/// How long to wait before retrying a scheduled notification the first time it fails to send;
+/// each later retry waits twice as long as the last.
+pub const NOTIFICATION_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How many days notifications that failed to send too many times are kept before they're removed.
+pub const DEAD_NOTIFICATION_RETENTION_DAYS: i64 = 7;
+
+/// How often to check for scheduled notifications that are due to be retried.
+pub const NOTIFICATION_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Maximum number of requests to send or edit notification messages that can be made at once.
+pub const NOTIFICATION_CONCURRENCY: usize = 8;
+
+/// How many messages to check for keywords between comparisons of the in-memory index with the
+/// database.
+pub const INDEX_CHECK_INTERVAL: usize = 1000;
+
+/// Maximum size of a compiled regex keyword or ignored phrase, in bytes.
+pub const REGEX_KEYWORD_SIZE_LIMIT: usize = 1 << 20;
+
+/// Maximum length of a regex keyword or ignored phrase's pattern, in bytes.
+pub const REGEX_KEYWORD_MAX_LEN: usize = 256;
+
+/// Maximum number of compiled regex keywords and ignored phrases, and separately of parsed
+/// expression keywords and ignored phrases, kept in memory; the least recently used one is dropped
+/// when another needs to be compiled.
+pub const COMPILED_KEYWORD_CACHE_SIZE: usize = 1024;
+
+/// Maximum number of terms in an expression keyword or ignored phrase.
+pub const EXPRESSION_KEYWORD_MAX_TERMS: usize = 16;
+
+/// Maximum nesting depth of parentheses and `NOT`s in an expression keyword or ignored phrase.
+pub const EXPRESSION_KEYWORD_MAX_DEPTH: usize = 8;
+
+/// Maximum number of words between the terms of a proximity keyword or ignored phrase.
+pub const PROXIMITY_KEYWORD_MAX_DISTANCE: usize = 20;
+
+/// Maximum length of the message content shown in a notification, in characters, which is the
+/// maximum length of an embed's description.
+pub const NOTIFICATION_CONTENT_MAX_LEN: usize = 2048;
+
+/// Number of characters of context to show on each side of a keyword in a notification, when the
+/// whole message is too long to show.
+pub const NOTIFICATION_EXCERPT_CONTEXT: usize = 500;
+
+/// Maximum number of messages before a highlighted message that can be shown in its notification.
+pub const NOTIFICATION_CONTEXT_MAX_MESSAGES: u8 = 5;
+
+/// Maximum length of each message shown as context in a notification, in characters.
+pub const CONTEXT_MESSAGE_MAX_LEN: usize = 200;
+
+/// Maximum length of the value of an embed field, in characters.
+pub const EMBED_FIELD_MAX_LEN: usize = 1024;
+
+/// Maximum number of fields in an embed.
+pub const EMBED_MAX_FIELDS: usize = 25;
+
+/// Maximum total length of the fields of one digest embed, in characters, leaving room for the
+/// rest of the embed under Discord's limit of 6000.
+pub const DIGEST_EMBED_MAX_LEN: usize = 5000;
+
+/// How often to check for digests that are due to be sent.
+pub const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Minimum number of minutes between batched digests.
+pub const DIGEST_MIN_INTERVAL: u16 = 5;
+
+/// Maximum number of minutes between batched digests.
+pub const DIGEST_MAX_INTERVAL: u16 = 24 * 60;
+
+/// Number of notifications shown on each page of the history command.
+pub const HISTORY_PAGE_SIZE: u32 = 10;
 
 /// Color of normal embeds (from help command and notifications).
 pub const EMBED_COLOR: u32 = 0xefff47;
@@ -13,8 +13,9 @@ mod enabled {
 	};
 	use once_cell::sync::{Lazy, OnceCell};
 	use prometheus::{
-		core::Collector, proto::MetricFamily, register_gauge_vec, Encoder,
-		GaugeVec, TextEncoder,
+		core::Collector, proto::MetricFamily, register_gauge_vec,
+		register_int_counter, register_int_gauge, Encoder, GaugeVec,
+		IntCounter, IntGauge, TextEncoder,
 	};
 
 	use std::{net::SocketAddr, time::Instant};
@@ -57,10 +58,52 @@ mod enabled {
 			.unwrap()
 		});
 
+	/// Gauge of in-memory index lookup time.
+	static INDEX_TIME_GAUGE: Lazy<GaugeVec, fn() -> GaugeVec> =
+		Lazy::new(|| {
+			register_gauge_vec!(
+				concat!(env!("CARGO_PKG_NAME"), "_index_time"),
+				"Index lookup time, in seconds",
+				&["name"]
+			)
+			.unwrap()
+		});
+
+	/// Counter of differences found between the in-memory index and the database.
+	static INDEX_MISMATCH_COUNTER: Lazy<IntCounter, fn() -> IntCounter> =
+		Lazy::new(|| {
+			register_int_counter!(
+				concat!(env!("CARGO_PKG_NAME"), "_index_mismatches"),
+				"Number of times the index and the database disagreed"
+			)
+			.unwrap()
+		});
+
+	/// Gauge of notifications that failed to send too many times to be retried.
+	static DEAD_NOTIFICATIONS_GAUGE: Lazy<IntGauge, fn() -> IntGauge> =
+		Lazy::new(|| {
+			register_int_gauge!(
+				concat!(env!("CARGO_PKG_NAME"), "_dead_notifications"),
+				"Number of notifications that won't be retried after failing to send"
+			)
+			.unwrap()
+		});
+
+	/// Gauge of notification messages waiting to be sent or edited.
+	static DISPATCH_QUEUE_GAUGE: Lazy<IntGauge, fn() -> IntGauge> =
+		Lazy::new(|| {
+			register_int_gauge!(
+				concat!(env!("CARGO_PKG_NAME"), "_dispatch_queue"),
+				"Number of notification messages waiting to be sent or edited"
+			)
+			.unwrap()
+		});
+
 	#[derive(Copy, Clone)]
 	enum TimerType {
 		Command,
 		Query,
+		Index,
 		Notification,
 	}
 
@@ -104,6 +147,18 @@ mod enabled {
 			}
 		}
 
+		/// Creates a timer for an in-memory index lookup.
+		///
+		/// `name` should be the name of the database query the lookup replaces, like
+		/// `"get keywords"`, so the two can be compared.
+		pub fn index(name: &'static str) -> Self {
+			Self {
+				kind: TimerType::Index,
+				name,
+				start: Instant::now(),
+			}
+		}
+
 		/// Creates a timer for a keyword notificationexecution.
 		///
 		/// `name` should be the type of notification, `"create"`, `"edit"`, or `"delete"`.
@@ -135,6 +190,11 @@ mod enabled {
 						.with_label_values(&[self.name])
 						.set(elapsed);
 				}
+				TimerType::Index => {
+					INDEX_TIME_GAUGE
+						.with_label_values(&[self.name])
+						.set(elapsed);
+				}
 				TimerType::Notification => {
 					NOTIFY_TIME_GAUGE
 						.with_label_values(&[self.name])
@@ -186,6 +246,27 @@ mod enabled {
 		avg_metrics(NOTIFY_TIME_GAUGE.collect())
 	}
 
+	/// Records that the in-memory index and the database were found to disagree.
+	pub fn record_index_mismatch() {
+		if *ENABLED.get().unwrap() {
+			INDEX_MISMATCH_COUNTER.inc();
+		}
+	}
+
+	/// Records the number of notifications that failed to send too many times to be retried.
+	pub fn set_dead_notifications(count: u64) {
+		if *ENABLED.get().unwrap() {
+			DEAD_NOTIFICATIONS_GAUGE.set(count as i64);
+		}
+	}
+
+	/// Records the number of notification messages waiting to be sent or edited.
+	pub fn set_dispatch_queue(depth: usize) {
+		if *ENABLED.get().unwrap() {
+			DISPATCH_QUEUE_GAUGE.set(depth as i64);
+		}
+	}
+
 	/// Calculates the average of a collection of `MetricFamily`s.
 	fn avg_metrics(metric_families: Vec<MetricFamily>) -> Option<f64> {
 		let mut count = 0;
@@ -263,16 +344,29 @@ mod disabled {
 			Timer
 		}
 
+		pub fn index(_: &'static str) -> Self {
+			Timer
+		}
+
 		pub fn notification(_: &'static str) -> Self {
 			Timer
 		}
 	}
+
+	pub fn record_index_mismatch() {}
+
+	pub fn set_dead_notifications(_: u64) {}
+
+	pub fn set_dispatch_queue(_: usize) {}
 }
 
 #[cfg(feature = "monitoring")]
 pub use enabled::{
-	avg_command_time, avg_notify_time, avg_query_time, init, Timer,
+	avg_command_time, avg_notify_time, avg_query_time, init,
+	record_index_mismatch, set_dead_notifications, set_dispatch_queue, Timer,
 };
 
 #[cfg(not(feature = "monitoring"))]
-pub use disabled::Timer;
+pub use disabled::{
+	record_index_mismatch, set_dead_notifications, set_dispatch_queue, Timer,
+};
@@ -90,7 +90,9 @@ mod webhook {
 	}
 
 	/// Reports a panic to the configured webhook URL.
-	pub fn report_panic(info: &panic::PanicInfo) -> Result<blocking::Response> {
+	pub fn report_panic(
+		info: &panic::PanicHookInfo,
+	) -> Result<blocking::Response> {
 		let url = settings()
 			.logging
 			.webhook
@@ -133,7 +135,7 @@ pub fn init() {
 		let default_panic_hook = panic::take_hook();
 
 		let reporting_panic_hook: Box<
-			dyn Fn(&panic::PanicInfo<'_>) + Send + Sync + 'static,
+			dyn Fn(&panic::PanicHookInfo<'_>) + Send + Sync + 'static,
 		> = Box::new(move |info| {
 			if let Err(e) = report_panic(info) {
 				log::error!("Error reporting panic: {}", e);
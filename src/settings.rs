@@ -81,14 +81,10 @@ mod user_address {
 		where
 			E: de::Error,
 		{
-			let socket_addr = v
-				.to_socket_addrs()
-				.map_err(E::custom)?
-				.into_iter()
-				.next()
-				.ok_or_else(|| {
-					E::custom("provided host did not resolve to an address")
-				})?;
+			let socket_addr =
+				v.to_socket_addrs().map_err(E::custom)?.next().ok_or_else(
+					|| E::custom("provided host did not resolve to an address"),
+				)?;
 
 			Ok(UserAddress {
 				socket_addr,
@@ -114,6 +110,19 @@ pub struct BehaviorSettings {
 	)]
 	#[cfg(feature = "bot")]
 	pub patience: Duration,
+
+	/// Default minimum duration between notifications about the same channel for one user, for
+	/// users who haven't chosen their own.
+	#[serde(
+		rename = "cooldown_seconds",
+		deserialize_with = "deserialize_duration"
+	)]
+	#[cfg(feature = "bot")]
+	pub cooldown: Duration,
+
+	/// Default maximum number of notifications sent to one user each hour, for users who haven't
+	/// chosen their own, or 0 for no limit.
+	pub max_notifications_per_hour: u32,
 }
 
 /// Settings for the account of the bot.
@@ -126,6 +135,10 @@ pub struct BotSettings {
 	///
 	/// Controls whether the `about` command outputs an invite link.
 	pub private: bool,
+	/// Whether to request the privileged presence intent.
+	///
+	/// Users can only choose to be notified just while they're away if this is enabled.
+	pub presences: bool,
 }
 
 /// Settings for various logging facilities.
@@ -151,6 +164,12 @@ pub struct DatabaseSettings {
 	pub path: PathBuf,
 	/// Whether or not to run automatic daily backups.
 	pub backup: bool,
+	/// How many days notifications are kept in users' notification history, or 0 to keep them
+	/// forever.
+	///
+	/// Removed notifications also stop following edits and deletions of the original message, and
+	/// can't be acted on with reactions anymore.
+	pub notification_history_days: u32,
 }
 
 /// Collection of settings.
@@ -171,15 +190,21 @@ impl Settings {
 		s.set_default("behavior.max_keywords", 100)?;
 		#[cfg(feature = "bot")]
 		s.set_default("behavior.patience_seconds", 60 * 2)?;
+		#[cfg(feature = "bot")]
+		s.set_default("behavior.cooldown_seconds", 0)?;
+		s.set_default("behavior.max_notifications_per_hour", 0)?;
 
 		#[cfg(feature = "bot")]
 		s.set_default("bot.private", false)?;
+		#[cfg(feature = "bot")]
+		s.set_default("bot.presences", false)?;
 
 		s.set_default("logging.level", "WARN")?;
 		s.set_default("logging.filters.highlights", "INFO")?;
 
 		s.set_default("database.path", "./data")?;
 		s.set_default("database.backup", true)?;
+		s.set_default("database.notification_history_days", 0)?;
 
 		let filename = env::var("HIGHLIGHTS_CONFIG")
 			.unwrap_or_else(|_| "./config.toml".to_owned());